@@ -0,0 +1,105 @@
+//! Reading a chunk into an `Nbt` tree against walking it in place.
+//!
+//! Run with `cargo bench` on a nightly compiler.
+
+#![feature(test)]
+
+extern crate byteorder;
+extern crate flate2;
+extern crate rustc_serialize;
+#[macro_use] extern crate serde;
+#[macro_use] extern crate serde_derive;
+extern crate test;
+
+// Hematite is a binary, so the NBT code is built in from its source, at
+// the path it uses for itself.
+#[allow(dead_code)]
+#[path = "../src/minecraft/nbt/mod.rs"]
+pub mod nbt;
+
+mod minecraft {
+    pub use nbt;
+}
+
+use std::io::Read;
+
+use flate2::read::ZlibDecoder;
+use test::Bencher;
+
+use minecraft::nbt::{ Nbt, NbtLimits };
+use minecraft::nbt::slice;
+
+/// A 1.12 chunk, with the byte arrays of two sections.
+const CHUNK: &'static [u8] = include_bytes!("../fixtures/chunk_1_12.zlib");
+
+fn chunk() -> Vec<u8> {
+    let mut data = vec![];
+    ZlibDecoder::new(CHUNK).read_to_end(&mut data).unwrap();
+    data
+}
+
+#[bench]
+fn from_reader(b: &mut Bencher) {
+    let data = chunk();
+    b.bytes = data.len() as u64;
+    b.iter(|| Nbt::from_reader(&data[..]).unwrap());
+}
+
+/// Only the root, which is all `slice::root` parses up front.
+#[bench]
+fn slice_root(b: &mut Bencher) {
+    let data = chunk();
+    b.bytes = data.len() as u64;
+    b.iter(|| slice::root(&data).unwrap());
+}
+
+/// The sections of the chunk, as the region reader looks them up.
+#[bench]
+fn slice_sections(b: &mut Bencher) {
+    let data = chunk();
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let (_, root) = slice::root(&data).unwrap();
+        let level = root.as_compound().unwrap().get("Level").unwrap().unwrap();
+        let sections = level.as_compound().unwrap().get("Sections").unwrap().unwrap();
+        let mut blocks = 0;
+        for section in sections.as_list().unwrap().iter() {
+            let section = section.unwrap().as_compound().unwrap();
+            blocks += section.get("Blocks").unwrap().unwrap().as_bytearray().unwrap().len();
+        }
+        blocks
+    });
+}
+
+/// Everything, copied out into an `Nbt` like `from_reader` does.
+#[bench]
+fn slice_to_nbt(b: &mut Bencher) {
+    let data = chunk();
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let (_, root) = slice::root_with_limits(&data, NbtLimits::default()).unwrap();
+        root.to_nbt().unwrap()
+    });
+}
+
+/// Counts the tags, entering everything.
+struct Counter(usize);
+
+impl<'a> slice::Visitor<'a> for Counter {
+    fn tag(&mut self, _name: slice::Str<'a>, _value: slice::Value<'a>) -> slice::Visit {
+        self.0 += 1;
+        slice::Visit::Enter
+    }
+}
+
+/// Every tag, walked in place.
+#[bench]
+fn slice_visit(b: &mut Bencher) {
+    let data = chunk();
+    b.bytes = data.len() as u64;
+    b.iter(|| {
+        let mut counter = Counter(0);
+        slice::visit(&data, &mut counter).unwrap();
+        counter.0
+    });
+}
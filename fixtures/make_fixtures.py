@@ -0,0 +1,527 @@
+#!/usr/bin/env python3
+"""Writes the NBT fixtures the tests read.
+
+These are not saved by the game: they are built here, tag by tag, in the
+layout and tag order vanilla uses for each version, so that they can be
+rebuilt and checked without a copy of Minecraft. The writer below is
+deliberately independent of the crate's own.
+
+    level.dat            gzipped level.dat of a 1.12.2 world
+    chunk_mcregion.zlib  McRegion chunk, as stored in an .mcr region
+    chunk_1_12.zlib      pre-flattening Anvil chunk, DataVersion 1343
+    chunk_1_13.zlib      palette chunk with 5-bit spanning indices, 1631
+    chunk_1_18.zlib      sectioned chunk with negative sections, 2975
+    chunk_mcregion.lz4   the McRegion chunk as an LZ4 stream (see `lz4_java`)
+
+The LZ4 stream needs the reference `lz4` command line tool.
+"""
+
+import gzip
+import os
+import struct
+import subprocess
+import zlib
+
+END, BYTE, SHORT, INT, LONG, FLOAT, DOUBLE, BYTE_ARRAY, STRING, LIST, COMPOUND, \
+    INT_ARRAY, LONG_ARRAY = range(13)
+
+
+def mutf8(s):
+    out = bytearray()
+    for c in s.encode('utf-16-be', 'surrogatepass').decode('utf-16-be', 'surrogatepass'):
+        n = ord(c)
+        if 0 < n < 0x80:
+            out.append(n)
+        elif n < 0x800:
+            out += bytes([0xc0 | n >> 6, 0x80 | n & 0x3f])
+        else:
+            out += bytes([0xe0 | n >> 12, 0x80 | n >> 6 & 0x3f, 0x80 | n & 0x3f])
+    return bytes(out)
+
+
+def string(s):
+    data = mutf8(s)
+    return struct.pack('>H', len(data)) + data
+
+
+def payload(tag, value):
+    if tag == BYTE:
+        return struct.pack('>b', value)
+    if tag == SHORT:
+        return struct.pack('>h', value)
+    if tag == INT:
+        return struct.pack('>i', value)
+    if tag == LONG:
+        return struct.pack('>q', value)
+    if tag == FLOAT:
+        return struct.pack('>f', value)
+    if tag == DOUBLE:
+        return struct.pack('>d', value)
+    if tag == BYTE_ARRAY:
+        return struct.pack('>i', len(value)) + bytes(value)
+    if tag == STRING:
+        return string(value)
+    if tag == LIST:
+        element, items = value
+        return struct.pack('>bi', element, len(items)) + \
+            b''.join(payload(element, item) for item in items)
+    if tag == COMPOUND:
+        return b''.join(struct.pack('>b', t) + string(name) + payload(t, v)
+                        for name, (t, v) in value) + b'\0'
+    if tag == INT_ARRAY:
+        return struct.pack('>i%di' % len(value), len(value), *value)
+    if tag == LONG_ARRAY:
+        return struct.pack('>i%dq' % len(value), len(value), *value)
+    raise ValueError(tag)
+
+
+def root(entries, name=''):
+    return struct.pack('>b', COMPOUND) + string(name) + payload(COMPOUND, entries)
+
+
+def b(v): return (BYTE, v)
+def s(v): return (SHORT, v)
+def i(v): return (INT, v)
+def l(v): return (LONG, v)
+def f(v): return (FLOAT, v)
+def d(v): return (DOUBLE, v)
+def st(v): return (STRING, v)
+def c(*entries): return (COMPOUND, list(entries))
+def lst(element, items): return (LIST, (element, items))
+def ba(v): return (BYTE_ARRAY, v)
+def ia(v): return (INT_ARRAY, v)
+def la(v): return (LONG_ARRAY, v)
+
+
+def signed64(n):
+    n &= (1 << 64) - 1
+    return n - (1 << 64) if n >> 63 else n
+
+
+def nibbles(values):
+    """Packs 4-bit values two to a byte, the even index in the low half."""
+    out = bytearray(len(values) // 2)
+    for n, v in enumerate(values):
+        out[n >> 1] |= (v & 15) << (n & 1) * 4
+    return bytes(out)
+
+
+def pack(indices, bits, spanning):
+    """Packs palette indices into longs, like the game."""
+    if spanning:
+        total = 0
+        for n, v in enumerate(indices):
+            total |= v << n * bits
+        count = (len(indices) * bits + 63) // 64
+        return [signed64(total >> 64 * n) for n in range(count)]
+    per_long = 64 // bits
+    longs = []
+    for start in range(0, len(indices), per_long):
+        word = 0
+        for n, v in enumerate(indices[start:start + per_long]):
+            word |= v << n * bits
+        longs.append(signed64(word))
+    return longs
+
+
+def yzx(y, z, x):
+    return (y * 16 + z) * 16 + x
+
+
+def level_dat():
+    player = c(
+        ('DataVersion', i(1343)),
+        ('Dimension', i(0)),
+        ('Pos', lst(DOUBLE, [52.5, 71.0, -30.5])),
+        ('Motion', lst(DOUBLE, [0.0, -0.0784000015258789, 0.0])),
+        ('Rotation', lst(FLOAT, [-91.5, 12.75])),
+        ('FallDistance', f(0.0)),
+        ('OnGround', b(1)),
+        ('Air', s(300)),
+        ('Fire', s(-20)),
+        ('Health', f(20.0)),
+        ('foodLevel', i(20)),
+        ('XpLevel', i(3)),
+        ('XpP', f(0.25)),
+        ('SelectedItemSlot', i(0)),
+        ('Inventory', lst(COMPOUND, [
+            [('Slot', b(0)), ('id', st('minecraft:stone_pickaxe')), ('Count', b(1)),
+             ('Damage', s(12))],
+            [('Slot', b(1)), ('id', st('minecraft:torch')), ('Count', b(61)),
+             ('Damage', s(0))],
+        ])),
+        # The game writes lists it never added anything to as TAG_END.
+        ('EnderItems', lst(END, [])),
+        ('Attributes', lst(COMPOUND, [
+            [('Name', st('generic.maxHealth')), ('Base', d(20.0))],
+            [('Name', st('generic.movementSpeed')), ('Base', d(0.10000000149011612))],
+        ])),
+        ('abilities', c(
+            ('walkSpeed', f(0.1)), ('flySpeed', f(0.05)), ('mayfly', b(0)),
+            ('flying', b(0)), ('invulnerable', b(0)), ('mayBuild', b(1)),
+            ('instabuild', b(0)))),
+        ('UUIDMost', l(-6023406291380482227)),
+        ('UUIDLeast', l(-7476185337519474183)),
+    )
+    data = c(
+        ('version', i(19133)),
+        ('DataVersion', i(1343)),
+        ('Version', c(('Id', i(1343)), ('Name', st('1.12.2')), ('Snapshot', b(0)))),
+        ('LevelName', st('Fixture é\u0000')),
+        ('generatorName', st('default')),
+        ('generatorVersion', i(1)),
+        ('generatorOptions', st('')),
+        ('RandomSeed', l(-4530634556500121041)),
+        ('MapFeatures', b(1)),
+        ('LastPlayed', l(1514764800000)),
+        ('SizeOnDisk', l(0)),
+        ('allowCommands', b(0)),
+        ('hardcore', b(0)),
+        ('GameType', i(0)),
+        ('Difficulty', b(2)),
+        ('DifficultyLocked', b(0)),
+        ('Time', l(48213)),
+        ('DayTime', l(48213)),
+        ('SpawnX', i(48)), ('SpawnY', i(64)), ('SpawnZ', i(-32)),
+        ('BorderCenterX', d(0.0)), ('BorderCenterZ', d(0.0)),
+        ('BorderSize', d(60000000.0)),
+        ('BorderSafeZone', d(5.0)), ('BorderWarningBlocks', d(5.0)),
+        ('BorderWarningTime', d(15.0)), ('BorderSizeLerpTarget', d(60000000.0)),
+        ('BorderSizeLerpTime', l(0)), ('BorderDamagePerBlock', d(0.2)),
+        ('raining', b(0)), ('rainTime', i(61234)),
+        ('thundering', b(0)), ('thunderTime', i(103456)),
+        ('clearWeatherTime', i(0)),
+        ('initialized', b(1)),
+        ('GameRules', c(
+            ('doDaylightCycle', st('true')), ('keepInventory', st('false')),
+            ('doMobSpawning', st('true')), ('randomTickSpeed', st('3')))),
+        ('DimensionData', c(
+            ('1', c(('DragonFight', c(
+                ('DragonKilled', b(0)), ('PreviouslyKilled', b(0)),
+                ('Gateways', lst(INT, [7, 3, 15, 0, 12, 9, 1, 18, 4, 11,
+                                       16, 6, 19, 2, 13, 8, 10, 17, 5, 14])))))))),
+        ('Player', player),
+    )
+    return root([('Data', data)])
+
+
+def chunk_mcregion():
+    height = 128
+    # XZY order, a column of 128 blocks after another.
+    blocks = bytearray(16 * 16 * height)
+    data = [0] * (16 * 16 * height)
+    block_light = [0] * (16 * 16 * height)
+    sky_light = [0] * (16 * 16 * height)
+    index = lambda x, y, z: (x * 16 + z) * height + y
+    for x in range(16):
+        for z in range(16):
+            blocks[index(x, 0, z)] = 7
+            for y in range(1, 60):
+                blocks[index(x, y, z)] = 1
+            blocks[index(x, 60, z)] = 3
+            blocks[index(x, 61, z)] = 2
+            for y in range(62, height):
+                sky_light[index(x, y, z)] = 15
+    # A red wool block and a torch.
+    blocks[index(1, 62, 2)] = 35
+    data[index(1, 62, 2)] = 14
+    blocks[index(3, 62, 3)] = 50
+    data[index(3, 62, 3)] = 5
+    block_light[index(3, 62, 3)] = 14
+    level = c(
+        ('Blocks', ba(bytes(blocks))),
+        ('Data', ba(nibbles(data))),
+        ('SkyLight', ba(nibbles(sky_light))),
+        ('BlockLight', ba(nibbles(block_light))),
+        ('HeightMap', ba(bytes([62] * 256))),
+        ('Entities', lst(END, [])),
+        ('TileEntities', lst(END, [])),
+        ('LastUpdate', l(2304)),
+        ('xPos', i(-1)),
+        ('zPos', i(2)),
+        ('TerrainPopulated', b(1)),
+    )
+    return root([('Level', level)])
+
+
+def chunk_1_12():
+    sections = []
+    # Section 0: bedrock, stone, dirt and grass, with a red wool block and
+    # a torch on top.
+    blocks = bytearray(4096)
+    data = [0] * 4096
+    block_light = [0] * 4096
+    sky_light = [0] * 4096
+    for z in range(16):
+        for x in range(16):
+            blocks[yzx(0, z, x)] = 7
+            for y in range(1, 4):
+                blocks[yzx(y, z, x)] = 1
+            blocks[yzx(4, z, x)] = 3
+            blocks[yzx(5, z, x)] = 2
+            for y in range(6, 16):
+                sky_light[yzx(y, z, x)] = 15
+    blocks[yzx(6, 2, 1)] = 35
+    data[yzx(6, 2, 1)] = 14
+    blocks[yzx(6, 3, 3)] = 50
+    data[yzx(6, 3, 3)] = 5
+    block_light[yzx(6, 3, 3)] = 14
+    block_light[yzx(6, 3, 4)] = 13
+    sections.append([
+        ('Y', b(0)),
+        ('Blocks', ba(bytes(blocks))),
+        ('Data', ba(nibbles(data))),
+        ('BlockLight', ba(nibbles(block_light))),
+        ('SkyLight', ba(nibbles(sky_light))),
+    ])
+    # Section 1: a glowstone block in the air.
+    blocks = bytearray(4096)
+    block_light = [0] * 4096
+    blocks[yzx(4, 8, 8)] = 89
+    block_light[yzx(4, 8, 8)] = 15
+    sections.append([
+        ('Y', b(1)),
+        ('Blocks', ba(bytes(blocks))),
+        ('Data', ba(bytes(2048))),
+        ('BlockLight', ba(nibbles(block_light))),
+        ('SkyLight', ba(nibbles([15] * 4096))),
+    ])
+    biomes = [4] * 256
+    biomes[15 * 16 + 15] = 7
+    level = c(
+        ('xPos', i(3)),
+        ('zPos', i(-2)),
+        ('LastUpdate', l(48190)),
+        ('LightPopulated', b(1)),
+        ('TerrainPopulated', b(1)),
+        ('V', b(1)),
+        ('InhabitedTime', l(1200)),
+        ('Biomes', ba(bytes(biomes))),
+        ('HeightMap', ia([7] * 256)),
+        ('Sections', lst(COMPOUND, sections)),
+        ('Entities', lst(END, [])),
+        ('TileEntities', lst(END, [])),
+    )
+    return root([('DataVersion', i(1343)), ('Level', level)])
+
+
+def chunk_1_13():
+    palette = [
+        [('Name', st('minecraft:air'))],
+        [('Name', st('minecraft:bedrock'))],
+        [('Name', st('minecraft:stone'))],
+        [('Name', st('minecraft:dirt'))],
+        [('Name', st('minecraft:grass_block')),
+         ('Properties', c(('snowy', st('false'))))],
+        [('Name', st('minecraft:sand'))],
+        [('Name', st('minecraft:gravel'))],
+        [('Name', st('minecraft:cobblestone'))],
+        [('Name', st('minecraft:oak_planks'))],
+        [('Name', st('minecraft:oak_log')), ('Properties', c(('axis', st('x'))))],
+        [('Name', st('minecraft:gold_ore'))],
+        [('Name', st('minecraft:iron_ore'))],
+        [('Name', st('minecraft:coal_ore'))],
+        [('Name', st('minecraft:glass'))],
+        [('Name', st('minecraft:lapis_ore'))],
+        [('Name', st('minecraft:diamond_ore'))],
+        [('Name', st('minecraft:wall_torch')), ('Properties', c(('facing', st('north'))))],
+    ]
+    indices = [0] * 4096
+    block_light = [0] * 4096
+    sky_light = [0] * 4096
+    for z in range(16):
+        for x in range(16):
+            indices[yzx(0, z, x)] = 1
+            for y in range(1, 4):
+                indices[yzx(y, z, x)] = 2
+            indices[yzx(4, z, x)] = 3
+            indices[yzx(5, z, x)] = 4
+            for y in range(6, 16):
+                sky_light[yzx(y, z, x)] = 15
+    # Every other entry once, so that all of them are used.
+    for n in range(5, 16):
+        indices[yzx(6, 0, n - 5)] = n
+    indices[yzx(7, 5, 5)] = 16
+    block_light[yzx(7, 5, 5)] = 14
+    sections = [[
+        ('Y', b(0)),
+        ('Palette', lst(COMPOUND, palette)),
+        ('BlockStates', la(pack(indices, 5, True))),
+        ('BlockLight', ba(nibbles(block_light))),
+        ('SkyLight', ba(nibbles(sky_light))),
+    ]]
+    level = c(
+        ('xPos', i(0)),
+        ('zPos', i(0)),
+        ('LastUpdate', l(912)),
+        ('InhabitedTime', l(0)),
+        ('Status', st('postprocessed')),
+        ('Biomes', ia([2] * 256)),
+        ('Heightmaps', c(
+            ('MOTION_BLOCKING', la(pack([6] * 256, 9, True))),
+            ('WORLD_SURFACE', la(pack([6] * 256, 9, True))))),
+        ('Sections', lst(COMPOUND, sections)),
+        ('Entities', lst(END, [])),
+        ('TileEntities', lst(END, [])),
+        ('LiquidTicks', lst(END, [])),
+        ('PostProcessing', lst(LIST, [(END, [])] * 16)),
+    )
+    return root([('DataVersion', i(1631)), ('Level', level)])
+
+
+def chunk_1_18():
+    air = [('Name', st('minecraft:air'))]
+    sections = []
+    # Light only, below the world.
+    sections.append([
+        ('Y', b(-5)),
+        ('SkyLight', ba(bytes(2048))),
+    ])
+    # The bottom of the world: bedrock under deepslate.
+    indices = [1] * 4096
+    for z in range(16):
+        for x in range(16):
+            indices[yzx(0, z, x)] = 0
+    sections.append([
+        ('Y', b(-4)),
+        ('block_states', c(
+            ('palette', lst(COMPOUND, [
+                [('Name', st('minecraft:bedrock'))],
+                [('Name', st('minecraft:deepslate')),
+                 ('Properties', c(('axis', st('y'))))]])),
+            ('data', la(pack(indices, 4, False))))),
+        ('biomes', c(('palette', lst(STRING, ['minecraft:dripstone_caves'])))),
+        ('BlockLight', ba(bytes(2048))),
+        ('SkyLight', ba(bytes(2048))),
+    ])
+    # Stone with a glow lichen lit cave at y = -10.
+    indices = [0] * 4096
+    block_light = [0] * 4096
+    indices[yzx(6, 5, 5)] = 1
+    block_light[yzx(6, 5, 5)] = 7
+    sections.append([
+        ('Y', b(-1)),
+        ('block_states', c(
+            ('palette', lst(COMPOUND, [[('Name', st('minecraft:stone'))], air])),
+            ('data', la(pack(indices, 4, False))))),
+        ('biomes', c(('palette', lst(STRING, ['minecraft:desert'])))),
+        ('BlockLight', ba(nibbles(block_light))),
+        ('SkyLight', ba(bytes(2048))),
+    ])
+    # Sea level: air, forest to the west and plains to the east.
+    cells = [0] * 64
+    for z in range(4):
+        for x in range(2, 4):
+            cells[z * 4 + x] = 1
+    sections.append([
+        ('Y', b(4)),
+        ('block_states', c(('palette', lst(COMPOUND, [air])))),
+        ('biomes', c(
+            ('palette', lst(STRING, ['minecraft:forest', 'minecraft:plains'])),
+            ('data', la(pack(cells, 1, False))))),
+        ('SkyLight', ba(nibbles([15] * 4096))),
+    ])
+    return root([
+        ('DataVersion', i(2975)),
+        ('xPos', i(-7)),
+        ('zPos', i(12)),
+        ('yPos', i(-4)),
+        ('Status', st('full')),
+        ('LastUpdate', l(73021)),
+        ('InhabitedTime', l(5312)),
+        ('sections', lst(COMPOUND, sections)),
+        ('block_entities', lst(END, [])),
+        ('Heightmaps', c(
+            ('WORLD_SURFACE', la(pack([1] * 256, 9, False))))),
+        ('isLightOn', b(1)),
+        ('structures', c(('References', c()), ('starts', c()))),
+    ])
+
+
+def xxhash32(data, seed):
+    """The 32-bit xxHash, after the reference implementation."""
+    p1, p2, p3, p4, p5 = 2654435761, 2246822519, 3266489917, 668265263, 374761393
+    mask = 0xffffffff
+
+    def rotl(x, r):
+        return ((x << r) | (x >> (32 - r))) & mask
+
+    def round_(acc, lane):
+        return (rotl((acc + lane * p2) & mask, 13) * p1) & mask
+
+    i = 0
+    if len(data) >= 16:
+        v = [(seed + p1 + p2) & mask, (seed + p2) & mask, seed, (seed - p1) & mask]
+        while i + 16 <= len(data):
+            for j in range(4):
+                v[j] = round_(v[j], struct.unpack_from('<I', data, i + 4 * j)[0])
+            i += 16
+        h = (rotl(v[0], 1) + rotl(v[1], 7) + rotl(v[2], 12) + rotl(v[3], 18)) & mask
+    else:
+        h = (seed + p5) & mask
+    h = (h + len(data)) & mask
+    while i + 4 <= len(data):
+        h = (h + struct.unpack_from('<I', data, i)[0] * p3) & mask
+        h = (rotl(h, 17) * p4) & mask
+        i += 4
+    while i < len(data):
+        h = (h + data[i] * p5) & mask
+        h = (rotl(h, 11) * p1) & mask
+        i += 1
+    h ^= h >> 15
+    h = (h * p2) & mask
+    h ^= h >> 13
+    h = (h * p3) & mask
+    return h ^ (h >> 16)
+
+
+assert xxhash32(b'', 0) == 0x02cc5d05
+assert xxhash32(b'abc', 0) == 0x32d153ff
+
+
+def lz4_java(data):
+    """Frames `data` like lz4-java's LZ4BlockOutputStream with its default
+    64 KiB blocks. The blocks are compressed by the reference `lz4` tool
+    and taken out of its frame, whose blocks are the same LZ4 format."""
+    frame = subprocess.run(['lz4', '-q', '-c', '-B4', '-BI', '--no-frame-crc'],
+                           input=data, stdout=subprocess.PIPE, check=True).stdout
+    assert frame[:4] == b'\x04\x22\x4d\x18'
+    flags = frame[4]
+    i = 7 + (8 if flags & 0x08 else 0)
+    out = b''
+    start = 0
+    while True:
+        size, = struct.unpack_from('<I', frame, i)
+        i += 4
+        if size == 0:
+            break
+        stored = frame[i:i + (size & 0x7fffffff)]
+        i += size & 0x7fffffff
+        block = data[start:start + 65536]
+        start += len(block)
+        method = 0x10 if size & 0x80000000 else 0x20
+        out += b'LZ4Block' + struct.pack('<BIII', method | 6, len(stored), len(block),
+                                         xxhash32(block, 0x9747b28c) & 0x0fffffff)
+        out += stored
+    assert start == len(data)
+    return out + b'LZ4Block' + struct.pack('<BIII', 0x10 | 6, 0, 0, 0)
+
+
+def main():
+    here = os.path.dirname(os.path.abspath(__file__))
+
+    def write(name, data):
+        with open(os.path.join(here, name), 'wb') as f:
+            f.write(data)
+
+    # A fixed mtime, so the gzip header is the same every time.
+    write('level.dat', gzip.compress(level_dat(), mtime=0))
+    write('chunk_mcregion.zlib', zlib.compress(chunk_mcregion()))
+    write('chunk_1_12.zlib', zlib.compress(chunk_1_12()))
+    write('chunk_1_13.zlib', zlib.compress(chunk_1_13()))
+    write('chunk_1_18.zlib', zlib.compress(chunk_1_18()))
+    write('chunk_mcregion.lz4', lz4_java(chunk_mcregion()))
+
+
+if __name__ == '__main__':
+    main()
@@ -1,9 +1,5 @@
-use std::cell::RefCell;
-use std::collections::HashMap;
-
-use array::*;
-use shader::Vertex;
-use gfx;
+//! The blocks, light and biomes of chunks, as read from a world. Their
+//! meshes are the renderer's business, see `chunk_manager`.
 
 #[derive(Copy, Clone)]
 pub struct BlockState {
@@ -46,68 +42,49 @@ pub const EMPTY_CHUNK: &'static Chunk = &Chunk {
     light_levels: [[[LightLevel {value: 0xf0}; SIZE]; SIZE]; SIZE]
 };
 
-pub struct ChunkColumn<R: gfx::Resources> {
+/// The chunks of a column from `min_section` up. Sections above the last
+/// chunk are empty.
+pub struct ChunkColumn {
+    /// The lowest section, in chunks: 0 before 1.18, -4 since.
+    pub min_section: i32,
+    /// The highest section a chunk may be in: 15 before 1.18, 19 since.
+    pub max_section: i32,
     pub chunks: Vec<Chunk>,
-    pub buffers: [RefCell<Option<gfx::handle::Buffer<R, Vertex>>>; SIZE],
     pub biomes: [[BiomeId; SIZE]; SIZE]
 }
 
-pub struct ChunkManager<R: gfx::Resources> {
-    chunk_columns: HashMap<(i32, i32), ChunkColumn<R>>
-}
-
-impl<R: gfx::Resources> ChunkManager<R> {
-    pub fn new() -> ChunkManager<R> {
-        ChunkManager {
-            chunk_columns: HashMap::new()
+impl ChunkColumn {
+    /// An empty column with room for sections `min_section` to
+    /// `max_section`, inclusive.
+    pub fn new(min_section: i32, max_section: i32, biomes: [[BiomeId; SIZE]; SIZE])
+               -> ChunkColumn {
+        ChunkColumn {
+            min_section: min_section,
+            max_section: max_section,
+            chunks: vec![],
+            biomes: biomes
         }
     }
 
-    pub fn add_chunk_column(&mut self, x: i32, z: i32, c: ChunkColumn<R>) {
-        self.chunk_columns.insert((x, z), c);
-    }
-
-    pub fn each_chunk_and_neighbors<'a, F>(&'a self, mut f: F)
-        where F: FnMut(/*coords:*/ [i32; 3],
-                       /*buffer:*/ &'a RefCell<Option<gfx::handle::Buffer<R, Vertex>>>,
-                       /*chunks:*/ [[[&'a Chunk; 3]; 3]; 3],
-                       /*biomes:*/ [[Option<&'a [[BiomeId; SIZE]; SIZE]>; 3]; 3])
-
-    {
-        for &(x, z) in self.chunk_columns.keys() {
-            let columns = [-1, 0, 1].map(
-                    |dz| [-1, 0, 1].map(
-                        |dx| self.chunk_columns.get(&(x + dx, z + dz))
-                    )
-                );
-            let central = columns[1][1].unwrap();
-            for y in 0..central.chunks.len() {
-                let chunks = [-1, 0, 1].map(|dy| {
-                    let y = y as i32 + dy;
-                    columns.map(
-                        |cz| cz.map(
-                            |cx| cx.and_then(
-                                |c| c.chunks[..].get(y as usize)
-                            ).unwrap_or(EMPTY_CHUNK)
-                        )
-                    )
-                });
-                f([x, y as i32, z], &central.buffers[y], chunks,
-                  columns.map(|cz| cz.map(|cx| cx.map(|c| &c.biomes))))
-            }
+    /// The chunk in section `y`, if there is one.
+    pub fn chunk(&self, y: i32) -> Option<&Chunk> {
+        if y < self.min_section {
+            return None;
         }
+        self.chunks.get((y - self.min_section) as usize)
     }
 
-    pub fn each_chunk<F>(&self, mut f: F)
-        where F: FnMut(/*x:*/ i32, /*y:*/ i32, /*z:*/ i32, /*c:*/ &Chunk, 
-            /*b:*/ &RefCell<Option<gfx::handle::Buffer<R, Vertex>>>)
-    {
-        for (&(x, z), c) in self.chunk_columns.iter() {
-            for (y, (c, b)) in c.chunks.iter()
-                .zip(c.buffers.iter()).enumerate() {
-
-                f(x, y as i32, z, c, b)
-            }
+    /// Puts a chunk in section `y`, filling any sections below it with
+    /// empty chunks. Returns false if `y` is out of range.
+    pub fn set_chunk(&mut self, y: i32, chunk: Chunk) -> bool {
+        if y < self.min_section || y > self.max_section {
+            return false;
+        }
+        let i = (y - self.min_section) as usize;
+        while self.chunks.len() <= i {
+            self.chunks.push(*EMPTY_CHUNK);
         }
+        self.chunks[i] = chunk;
+        true
     }
 }
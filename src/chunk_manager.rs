@@ -0,0 +1,76 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use array::*;
+use chunk::{ BiomeId, Chunk, ChunkColumn, EMPTY_CHUNK, SIZE };
+use shader::Vertex;
+use gfx;
+
+/// The vertex buffer of a chunk, once its mesh has been built.
+pub type ChunkBuffer<R> = RefCell<Option<gfx::handle::Buffer<R, Vertex>>>;
+
+/// The chunk columns being shown, and the vertex buffer of each of their
+/// chunks by chunk coordinates.
+pub struct ChunkManager<R: gfx::Resources> {
+    chunk_columns: HashMap<(i32, i32), Rc<ChunkColumn>>,
+    buffers: HashMap<(i32, i32, i32), ChunkBuffer<R>>
+}
+
+impl<R: gfx::Resources> ChunkManager<R> {
+    pub fn new() -> ChunkManager<R> {
+        ChunkManager {
+            chunk_columns: HashMap::new(),
+            buffers: HashMap::new()
+        }
+    }
+
+    pub fn add_chunk_column(&mut self, x: i32, z: i32, c: Rc<ChunkColumn>) {
+        for i in 0..c.chunks.len() {
+            let y = c.min_section + i as i32;
+            self.buffers.insert((x, y, z), RefCell::new(None));
+        }
+        self.chunk_columns.insert((x, z), c);
+    }
+
+    pub fn each_chunk_and_neighbors<'a, F>(&'a self, mut f: F)
+        where F: FnMut(/*coords:*/ [i32; 3],
+                       /*buffer:*/ &'a ChunkBuffer<R>,
+                       /*chunks:*/ [[[&'a Chunk; 3]; 3]; 3],
+                       /*biomes:*/ [[Option<&'a [[BiomeId; SIZE]; SIZE]>; 3]; 3])
+
+    {
+        for &(x, z) in self.chunk_columns.keys() {
+            let columns = [-1, 0, 1].map(
+                    |dz| [-1, 0, 1].map(
+                        |dx| self.chunk_columns.get(&(x + dx, z + dz)).map(|c| &**c)
+                    )
+                );
+            let central = columns[1][1].unwrap();
+            for i in 0..central.chunks.len() {
+                let y = central.min_section + i as i32;
+                let chunks = [-1, 0, 1].map(|dy| {
+                    columns.map(
+                        |cz| cz.map(
+                            |cx| cx.and_then(|c| c.chunk(y + dy)).unwrap_or(EMPTY_CHUNK)
+                        )
+                    )
+                });
+                f([x, y, z], &self.buffers[&(x, y, z)], chunks,
+                  columns.map(|cz| cz.map(|cx| cx.map(|c| &c.biomes))))
+            }
+        }
+    }
+
+    pub fn each_chunk<F>(&self, mut f: F)
+        where F: FnMut(/*x:*/ i32, /*y:*/ i32, /*z:*/ i32, /*c:*/ &Chunk,
+            /*b:*/ &ChunkBuffer<R>)
+    {
+        for (&(x, z), c) in self.chunk_columns.iter() {
+            for (i, chunk) in c.chunks.iter().enumerate() {
+                let y = c.min_section + i as i32;
+                f(x, y, z, chunk, &self.buffers[&(x, y, z)])
+            }
+        }
+    }
+}
@@ -11,6 +11,8 @@ extern crate glutin_window;
 extern crate libc;
 extern crate memmap;
 extern crate rustc_serialize;
+#[macro_use] extern crate serde;
+#[macro_use] extern crate serde_derive;
 extern crate shader_version;
 extern crate vecmath;
 extern crate zip;
@@ -19,17 +21,15 @@ extern crate zip;
 // from Hematite to the library.
 pub use gfx_voxel::{ array, cube };
 
-use std::cmp::max;
 use std::f32::consts::PI;
 use std::f32::INFINITY;
-use std::fs::File;
 use std::path::{ Path, PathBuf };
+use std::process;
 use std::time::Instant;
 
 use array::*;
 use docopt::Docopt;
 use piston::event_loop::{ Events, EventLoop };
-use flate2::read::GzDecoder;
 use glutin_window::*;
 use gfx::traits::Device;
 use shader::Renderer;
@@ -39,20 +39,43 @@ use piston::window::{ Size, Window, AdvancedWindow, OpenGLWindow,
 
 pub mod minecraft;
 pub mod chunk;
+pub mod chunk_manager;
 pub mod shader;
 
 use minecraft::biome::Biomes;
 use minecraft::block_state::BlockStates;
+use minecraft::dimension::Dimension;
+use minecraft::level::LevelData;
+use minecraft::player::{ DimensionId, Player, PlayerData };
 
 static USAGE: &'static str = "
 hematite, Minecraft made in Rust!
 
 Usage:
     hematite [options] <world>
+    hematite region check [--repair] [--out=<dir>] <region>...
+    hematite region compact [--recompress=<type>] [--level=<level>] <region>...
+    hematite region trim [--dry-run] [--min-inhabited=<ticks>] [--keep=<area>...] <region>...
 
 Options:
     -p, --path               Fully qualified path for world folder.
     --mcversion=<version>    Minecraft version [default: 1.8.8].
+    --player=<player>        Start where a player of a server world is, given
+                             their UUID or, for old worlds, their name.
+    --list-players           List the players of the world and exit.
+    --dimension=<dim>        View a dimension other than the player's: nether,
+                             the_end, a namespaced name or a number.
+    --repair                 Copy each damaged region without its broken
+                             chunks, moving overlapping ones apart.
+    --out=<dir>              Where --repair puts the copies [default: repaired].
+    --recompress=<type>      Compress every chunk with gzip, zlib, lz4 or none.
+    --level=<level>          How hard gzip and zlib try: fast, default or best
+                             [default: default].
+    --dry-run                List the chunks trim would delete, and keep them.
+    --min-inhabited=<ticks>  Trim chunks players spent less time in than this.
+    --keep=<area>            Never trim chunks in the box x1,z1,x2,z2 or the
+                             circle x,z,radius, in blocks. Without
+                             --min-inhabited, trim everything else.
 ";
 
 #[derive(RustcDecodable)]
@@ -60,6 +83,20 @@ struct Args {
     arg_world: String,
     flag_path: bool,
     flag_mcversion: String,
+    flag_player: Option<String>,
+    flag_list_players: bool,
+    flag_dimension: Option<String>,
+    cmd_region: bool,
+    cmd_compact: bool,
+    cmd_trim: bool,
+    arg_region: Vec<String>,
+    flag_repair: bool,
+    flag_out: String,
+    flag_recompress: Option<String>,
+    flag_level: String,
+    flag_dry_run: bool,
+    flag_min_inhabited: Option<i64>,
+    flag_keep: Vec<String>,
 }
 
 fn create_main_targets(dim: gfx::tex::Dimensions) ->
@@ -81,13 +118,189 @@ fn create_main_targets(dim: gfx::tex::Dimensions) ->
     (output_color, output_stencil)
 }
 
+/// Exits after printing a bad option's problem, the way docopt does.
+fn usage_error(msg: String) -> ! {
+    docopt::Error::Usage(msg).exit()
+}
+
+/// Reports the problems of each region file, and with `--repair` writes
+/// copies of the damaged ones.
+fn check_regions(args: &Args) {
+    use minecraft::region::{ check, Region };
+
+    for path in &args.arg_region {
+        let path = Path::new(path);
+        let region = match Region::open(path) {
+            Ok(region) => region,
+            Err(err) => {
+                println!("{}: {}", path.display(), err);
+                continue;
+            }
+        };
+        let problems = match check::check(&region) {
+            Ok(problems) => problems,
+            Err(err) => {
+                println!("{}: {:?}", path.display(), err);
+                continue;
+            }
+        };
+        for p in &problems {
+            println!("{}: chunk {}, {}: {:?}", path.display(), p.x, p.z, p.problem);
+        }
+        if problems.is_empty() {
+            println!("{}: ok", path.display());
+        } else if args.flag_repair {
+            let out = match path.file_name() {
+                Some(name) => Path::new(&args.flag_out).join(name),
+                None => {
+                    println!("{}: not a file name", path.display());
+                    continue;
+                }
+            };
+            if let Err(err) = std::fs::create_dir_all(&args.flag_out) {
+                println!("{}: {}", args.flag_out, err);
+                continue;
+            }
+            match check::repair(&region, &problems, &out) {
+                Ok(dropped) => {
+                    println!("{}: wrote {} without {} chunks", path.display(), out.display(),
+                             dropped);
+                }
+                Err(err) => println!("{}: {:?}", out.display(), err)
+            }
+        }
+    }
+}
+
+/// Packs the chunks of each region file together, in place.
+fn compact_regions(args: &Args) {
+    use minecraft::region::{ compact, Compression };
+
+    let compression = args.flag_recompress.as_ref().map(|name| match &name[..] {
+        "gzip" => Compression::Gzip,
+        "zlib" => Compression::Zlib,
+        "lz4" => Compression::Lz4,
+        "none" => Compression::None,
+        _ => usage_error(format!("unknown compression {}: use gzip, zlib, lz4 or none", name))
+    });
+    let level = match &args.flag_level[..] {
+        "fast" => flate2::Compression::Fast,
+        "default" => flate2::Compression::Default,
+        "best" => flate2::Compression::Best,
+        level => usage_error(format!("unknown level {}: use fast, default or best", level))
+    };
+    for path in &args.arg_region {
+        let path = Path::new(path);
+        match compact::compact(path, path, compression.map(|c| (c, level))) {
+            Ok(done) => {
+                println!("{}: {} chunks, {} -> {} bytes, saved {}", path.display(),
+                         done.chunks, done.before, done.after, done.saved());
+            }
+            Err(err) => println!("{}: {:?}", path.display(), err)
+        }
+    }
+}
+
+/// Deletes chunks of each region file as `--min-inhabited` and `--keep`
+/// say, or only lists them with `--dry-run`.
+fn trim_regions(args: &Args) {
+    use minecraft::region::{ region_position, Region };
+    use minecraft::region::trim::{ self, Area, Trim };
+
+    let keep = args.flag_keep.iter().map(|area| {
+        let bad_area = || format!("bad area {}: use x1,z1,x2,z2 or x,z,radius", area);
+        let numbers: Vec<i32> = area.split(',').map(|n| {
+            n.trim().parse().unwrap_or_else(|_| usage_error(bad_area()))
+        }).collect();
+        match numbers.len() {
+            3 => Area::Radius([numbers[0], numbers[1]], numbers[2]),
+            4 => Area::Box([numbers[0], numbers[1]], [numbers[2], numbers[3]]),
+            _ => usage_error(bad_area())
+        }
+    }).collect();
+    let plan = Trim {
+        min_inhabited: args.flag_min_inhabited,
+        keep: keep
+    };
+    for path in &args.arg_region {
+        let path = Path::new(path);
+        let (rx, rz) = match region_position(path) {
+            Some(position) => position,
+            None => {
+                println!("{}: not named like r.X.Z.mca", path.display());
+                continue;
+            }
+        };
+        let trimmed = match Region::open(path).map_err(minecraft::region::RegionError::Io)
+                                              .and_then(|region| plan.plan(&region, rx, rz)) {
+            Ok(trimmed) => trimmed,
+            Err(err) => {
+                println!("{}: {:?}", path.display(), err);
+                continue;
+            }
+        };
+        for chunk in &trimmed {
+            let inhabited = chunk.inhabited.map_or(String::new(), |t| format!(", inhabited {}", t));
+            println!("{}: chunk {}, {}{}", path.display(), chunk.x, chunk.z, inhabited);
+        }
+        if args.flag_dry_run {
+            println!("{}: would trim {} chunks", path.display(), trimmed.len());
+        } else if trimmed.is_empty() {
+            println!("{}: nothing to trim", path.display());
+        } else {
+            match trim::trim(path, &trimmed) {
+                Ok(()) => println!("{}: trimmed {} chunks", path.display(), trimmed.len()),
+                Err(err) => println!("{}: {:?}", path.display(), err)
+            }
+        }
+    }
+}
+
+/// Prints the players of a world, as `--player` takes them.
+fn list_players(level: &LevelData, players: &PlayerData) {
+    if level.player.is_some() {
+        println!("(singleplayer, in level.dat)");
+    }
+    match players.players() {
+        Ok(ids) => for id in ids {
+            println!("{}", id);
+        },
+        Err(err) => println!("Can't list the players: {}", err)
+    }
+}
+
+/// Loads the player given with `--player`, or exits with the players
+/// there are to choose from.
+fn load_player(level: &LevelData, players: &PlayerData, who: &str) -> Player {
+    match players.find(who) {
+        Ok(Some(id)) => match players.load(&id) {
+            Ok(player) => return player,
+            Err(err) => println!("Can't read player {}: {:?}", id, err)
+        },
+        Ok(None) => {
+            println!("No player {} in this world. Its players are:", who);
+            list_players(level, players);
+        }
+        Err(err) => println!("Can't list the players: {}", err)
+    }
+    process::exit(1);
+}
+
 fn main() {
     let args: Args = Docopt::new(USAGE)
                             .and_then(|dopt| dopt.decode())
                             .unwrap_or_else(|e| e.exit());
 
-    // Automagically pull MC assets
-    minecraft::fetch_assets(&args.flag_mcversion);
+    if args.cmd_region {
+        if args.cmd_compact {
+            compact_regions(&args);
+        } else if args.cmd_trim {
+            trim_regions(&args);
+        } else {
+            check_regions(&args);
+        }
+        return;
+    }
 
     // Automagically expand path if world is located at
     // $MINECRAFT_ROOT/saves/<world_name>
@@ -100,26 +313,54 @@ fn main() {
         mc_path
     };
 
-    let file_name = PathBuf::from(world.join("level.dat"));
-    let level_reader = GzDecoder::new(File::open(file_name).unwrap()).unwrap();
-    let level = minecraft::nbt::Nbt::from_reader(level_reader).unwrap();
-    println!("{:?}", level);
-    let player_pos: [f32; 3] = Array::from_iter(
-            level["Data"]["Player"]["Pos"]
-            .as_double_list().unwrap().iter().map(|&x| x as f32)
-        );
+    let level = match LevelData::open(&world) {
+        Ok(level) => level,
+        Err(err) => {
+            println!("Can't read the level of {}: {:?}", world.display(), err);
+            process::exit(1);
+        }
+    };
+    let players = PlayerData::new(&world);
+    if args.flag_list_players {
+        list_players(&level, &players);
+        return;
+    }
+
+    // Automagically pull MC assets
+    minecraft::fetch_assets(&args.flag_mcversion);
+
+    println!("Loading \"{}\", last played with {}", level.level_name,
+        level.game_version.as_ref().map_or("an old version", |v| &v.name[..]));
+    let player = match args.flag_player {
+        Some(ref who) => Some(load_player(&level, &players, who)),
+        None => level.player.clone()
+    };
+    let (player_pos, player_yaw, player_pitch, dimension) = match player {
+        Some(player) => (
+            player.pos.map(|x| x as f32),
+            player.rotation[0],
+            player.rotation[1],
+            player.dimension
+        ),
+        None => {
+            println!("No player given, starting at the world spawn");
+            let spawn = level.spawn();
+            let player_pos: [f32; 3] = [
+                spawn[0] as f32 + 0.5, spawn[1] as f32, spawn[2] as f32 + 0.5
+            ];
+            (player_pos, 0.0, 0.0, DimensionId::default())
+        }
+    };
     let player_chunk = [player_pos.x(), player_pos.z()]
         .map(|x| (x / 16.0).floor() as i32);
-    let player_rot = level["Data"]["Player"]["Rotation"]
-        .as_float_list().unwrap();
-    let player_yaw = player_rot[0];
-    let player_pitch = player_rot[1];
-
-    let regions = player_chunk.map(|x| x >> 5);
-    let region_file = world.join(
-            format!("region/r.{}.{}.mca", regions[0], regions[1])
-        );
-    let region = minecraft::region::Region::open(&region_file).unwrap();
+
+    let dimension = match args.flag_dimension {
+        Some(ref name) => Dimension::from_name(&world, name),
+        None => Dimension::from_id(&world, &dimension)
+    };
+    println!("In {}", dimension.name);
+
+    let world_data = minecraft::world::World::new(&dimension.dir);
 
     let loading_title = format!(
             "Hematite loading... - {}",
@@ -156,22 +397,17 @@ fn main() {
 
 	let encoder = factory.create_command_buffer().into();
     let mut renderer = Renderer::new(factory, encoder, target_view, depth_view, block_states.texture.surface.clone());
+    renderer.set_dimension(&dimension);
 
-    let mut chunk_manager = chunk::ChunkManager::new();
+    let mut chunk_manager = chunk_manager::ChunkManager::new();
 
     println!("Started loading chunks...");
-    let c_bases = player_chunk.map(|x| max(0, (x & 0x1f) - 8) as u8);
-    for cz in c_bases[1]..c_bases[1] + 16 {
-        for cx in c_bases[0]..c_bases[0] + 16 {
-            match region.get_chunk_column(cx, cz) {
-                Some(column) => {
-                    let (cx, cz) = (
-                        cx as i32 + regions[0] * 32,
-                        cz as i32 + regions[1] * 32
-                    );
-                    chunk_manager.add_chunk_column(cx, cz, column)
-                }
-                None => {}
+    for cz in player_chunk[1] - 8..player_chunk[1] + 8 {
+        for cx in player_chunk[0] - 8..player_chunk[0] + 8 {
+            match world_data.column(cx, cz) {
+                Ok(Some(column)) => chunk_manager.add_chunk_column(cx, cz, column),
+                Ok(None) => {}
+                Err(err) => println!("Skipping chunk {}, {}: {:?}", cx, cz, err)
             }
         }
     }
@@ -332,7 +568,7 @@ fn main() {
                     Some((coords, buffer, chunks, column_biomes)) => {
                         minecraft::block_state::fill_buffer(
                             &block_states, &biomes, &mut staging_buffer,
-                            coords, chunks, column_biomes
+                            coords, chunks, column_biomes, renderer.sky_light()
                         );
                         *buffer.borrow_mut() = Some(
                             renderer.create_buffer(&staging_buffer[..])
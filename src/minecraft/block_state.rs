@@ -401,7 +401,8 @@ impl<R: gfx::Resources> BlockStates<R> {
 pub fn fill_buffer<R: gfx::Resources>(block_states: &BlockStates<R>,
                    biomes: &Biomes, buffer: &mut Vec<Vertex>,
                    coords: [i32; 3], chunks: [[[&Chunk; 3]; 3]; 3],
-                   column_biomes: [[Option<&[[BiomeId; 16]; 16]>; 3]; 3]) {
+                   column_biomes: [[Option<&[[BiomeId; 16]; 16]>; 3]; 3],
+                   sky_light: Option<u8>) {
     let chunk_xyz = coords.map(|x| x as f32 * 16.0);
     for y in 0..16_usize {
         for z in 0..16_usize {
@@ -508,8 +509,8 @@ pub fn fill_buffer<R: gfx::Resources>(block_states: &BlockStates<R>,
                             for &dz in [dz - 1, dz].iter() {
                                 for &dy in [dy - 1, dy].iter() {
                                     let (neighbor, light_level) = at([dx, dy, dz]);
-                                    let light_level = max(light_level.block_light(),
-                                                          light_level.sky_light());
+                                    let sky = sky_light.unwrap_or(light_level.sky_light());
+                                    let light_level = max(light_level.block_light(), sky);
                                     let mut light_level = light_level as f32;
 
                                     let use_block = match face.ao_face {
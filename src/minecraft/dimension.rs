@@ -0,0 +1,130 @@
+//! Dimensions: where their chunks are kept in a world, and how they look.
+
+use std::path::{ Path, PathBuf };
+
+use minecraft::player::DimensionId;
+
+/// A dimension of a world.
+#[derive(Clone, Debug)]
+pub struct Dimension {
+    /// The namespaced name, like `minecraft:the_nether`.
+    pub name: String,
+    /// The folder with the dimension's `region` folder.
+    pub dir: PathBuf,
+    /// The light that takes the place of the sky's, in a dimension that
+    /// doesn't have one; `None` to use the light saved with the chunks.
+    pub sky_light: Option<u8>,
+    /// The color behind everything, for lack of a sky box.
+    pub clear_color: [f32; 4],
+}
+
+const OVERWORLD_COLOR: [f32; 4] = [0.81, 0.8, 1.0, 1.0];
+const NETHER_COLOR: [f32; 4] = [0.2, 0.03, 0.03, 1.0];
+const END_COLOR: [f32; 4] = [0.09, 0.07, 0.11, 1.0];
+
+impl Dimension {
+    /// Finds a dimension by the id a player was saved with.
+    pub fn from_id(world: &Path, id: &DimensionId) -> Dimension {
+        match *id {
+            DimensionId::Legacy(0) => Dimension::from_name(world, "minecraft:overworld"),
+            DimensionId::Legacy(-1) => Dimension::from_name(world, "minecraft:the_nether"),
+            DimensionId::Legacy(1) => Dimension::from_name(world, "minecraft:the_end"),
+            // Mods numbered their own dimensions the same way.
+            DimensionId::Legacy(n) => Dimension {
+                name: n.to_string(),
+                dir: world.join(format!("DIM{}", n)),
+                sky_light: None,
+                clear_color: OVERWORLD_COLOR
+            },
+            DimensionId::Named(ref name) => Dimension::from_name(world, name)
+        }
+    }
+
+    /// Finds a dimension by name, as given on the command line: a
+    /// namespaced name (the namespace defaults to `minecraft`), `nether`
+    /// or `end`, or a number as used before 1.16.
+    pub fn from_name(world: &Path, name: &str) -> Dimension {
+        if let Ok(n) = name.parse::<i32>() {
+            return Dimension::from_id(world, &DimensionId::Legacy(n));
+        }
+        let name = match name {
+            "nether" => "minecraft:the_nether".to_string(),
+            "end" => "minecraft:the_end".to_string(),
+            _ if name.contains(':') => name.to_string(),
+            _ => format!("minecraft:{}", name)
+        };
+        let (dir, sky_light, clear_color) = match &name[..] {
+            "minecraft:overworld" => (world.to_path_buf(), None, OVERWORLD_COLOR),
+            "minecraft:the_nether" => (world.join("DIM-1"), Some(7), NETHER_COLOR),
+            "minecraft:the_end" => (world.join("DIM1"), Some(10), END_COLOR),
+            // Since 1.16, datapacks can add dimensions, which are kept
+            // in `dimensions/<namespace>/<path>`.
+            _ => {
+                let mut dir = world.join("dimensions");
+                for part in name.split(':').flat_map(|part| part.split('/')) {
+                    dir.push(part);
+                }
+                (dir, None, OVERWORLD_COLOR)
+            }
+        };
+        Dimension {
+            name: name,
+            dir: dir,
+            sky_light: sky_light,
+            clear_color: clear_color
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use minecraft::player::DimensionId;
+    use super::*;
+
+    fn named(name: &str) -> (String, PathBuf, Option<u8>) {
+        let dimension = Dimension::from_name(Path::new("world"), name);
+        (dimension.name, dimension.dir, dimension.sky_light)
+    }
+
+    #[test]
+    fn vanilla_names_and_numbers() {
+        let overworld = ("minecraft:overworld".to_string(), PathBuf::from("world"), None);
+        let nether = ("minecraft:the_nether".to_string(), Path::new("world").join("DIM-1"), Some(7));
+        let end = ("minecraft:the_end".to_string(), Path::new("world").join("DIM1"), Some(10));
+        for name in &["overworld", "minecraft:overworld", "0"] {
+            assert_eq!(named(name), overworld);
+        }
+        for name in &["nether", "the_nether", "minecraft:the_nether", "-1"] {
+            assert_eq!(named(name), nether);
+        }
+        for name in &["end", "the_end", "minecraft:the_end", "1"] {
+            assert_eq!(named(name), end);
+        }
+    }
+
+    #[test]
+    fn datapack_dimensions() {
+        assert_eq!(named("mining"),
+                   ("minecraft:mining".to_string(), Path::new("world").join("dimensions/minecraft/mining"), None));
+        assert_eq!(named("ns:path/sub").1, Path::new("world").join("dimensions/ns/path/sub"));
+        // Only vanilla's own dimensions are in the old folders.
+        assert_eq!(named("other:the_nether").1, Path::new("world").join("dimensions/other/the_nether"));
+    }
+
+    #[test]
+    fn player_dimension_ids() {
+        let world = Path::new("world");
+        assert_eq!(Dimension::from_id(world, &DimensionId::Legacy(-1)).dir, world.join("DIM-1"));
+        assert_eq!(Dimension::from_id(world, &DimensionId::Legacy(0)).dir, world);
+        assert_eq!(Dimension::from_id(world, &DimensionId::Legacy(1)).dir, world.join("DIM1"));
+        let named = DimensionId::Named("minecraft:the_end".to_string());
+        assert_eq!(Dimension::from_id(world, &named).sky_light, Some(10));
+
+        // A mod's dimension keeps its number.
+        let modded = Dimension::from_id(world, &DimensionId::Legacy(7));
+        assert_eq!((&modded.name[..], modded.dir), ("7", world.join("DIM7")));
+        assert_eq!(Dimension::from_name(world, "-42").dir, world.join("DIM-42"));
+    }
+}
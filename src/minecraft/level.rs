@@ -0,0 +1,268 @@
+//! The world-wide settings and state kept in `level.dat`.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+
+use minecraft::nbt::{ de, Nbt, NbtReaderError };
+use minecraft::player::Player;
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Nbt(NbtReaderError),
+    /// The file is NBT, but not a level: a field is missing or has the
+    /// wrong type. The message names the field.
+    Invalid(de::Error),
+}
+
+impl From<io::Error> for LevelError {
+    fn from(err: io::Error) -> LevelError { LevelError::Io(err) }
+}
+
+impl From<NbtReaderError> for LevelError {
+    fn from(err: NbtReaderError) -> LevelError { LevelError::Nbt(err) }
+}
+
+impl From<de::Error> for LevelError {
+    fn from(err: de::Error) -> LevelError { LevelError::Invalid(err) }
+}
+
+/// The `Data` compound of `level.dat`. Fields that older versions don't
+/// write are optional or take the vanilla default.
+#[derive(Clone, Debug, Deserialize)]
+pub struct LevelData {
+    #[serde(rename = "LevelName", default)]
+    pub level_name: String,
+    #[serde(rename = "SpawnX")]
+    pub spawn_x: i32,
+    #[serde(rename = "SpawnY")]
+    pub spawn_y: i32,
+    #[serde(rename = "SpawnZ")]
+    pub spawn_z: i32,
+    /// The singleplayer player. Server worlds keep their players in
+    /// separate files instead.
+    #[serde(rename = "Player")]
+    pub player: Option<Player>,
+    /// Ticks since the world was created.
+    #[serde(rename = "Time")]
+    pub time: i64,
+    /// The time of day in ticks, which can be changed by commands and
+    /// sleeping. Older versions only have `time`.
+    #[serde(rename = "DayTime")]
+    pub day_time: Option<i64>,
+    #[serde(default)]
+    pub raining: bool,
+    #[serde(rename = "rainTime", default)]
+    pub rain_time: i32,
+    #[serde(default)]
+    pub thundering: bool,
+    #[serde(rename = "thunderTime", default)]
+    pub thunder_time: i32,
+    #[serde(rename = "clearWeatherTime", default)]
+    pub clear_weather_time: i32,
+    #[serde(rename = "GameRules", default)]
+    pub game_rules: BTreeMap<String, String>,
+    /// The seed before 1.16, which moved it into `WorldGenSettings`.
+    #[serde(rename = "RandomSeed")]
+    pub random_seed: Option<i64>,
+    #[serde(rename = "WorldGenSettings")]
+    pub world_gen_settings: Option<WorldGenSettings>,
+    /// The generator before 1.16, e.g. `default` or `flat`.
+    #[serde(rename = "generatorName")]
+    pub generator_name: Option<String>,
+    #[serde(flatten)]
+    pub world_border: WorldBorder,
+    /// The save format, 19133 for Anvil.
+    #[serde(default)]
+    pub version: i32,
+    #[serde(rename = "DataVersion")]
+    pub data_version: Option<i32>,
+    #[serde(rename = "Version")]
+    pub game_version: Option<GameVersion>,
+}
+
+impl LevelData {
+    /// Reads `level.dat` from a world directory, falling back to the
+    /// backup in `level.dat_old` if it can't be read. The error is the
+    /// one for `level.dat`.
+    pub fn open(world: &Path) -> Result<LevelData, LevelError> {
+        LevelData::from_file(&world.join("level.dat")).or_else(|err| {
+            LevelData::from_file(&world.join("level.dat_old")).map_err(|_| err)
+        })
+    }
+
+    pub fn from_file(path: &Path) -> Result<LevelData, LevelError> {
+        let reader = try!(GzDecoder::new(try!(File::open(path))));
+        let root = try!(Nbt::from_reader(reader));
+        let level: Level = try!(de::from_nbt(&root));
+        Ok(level.data)
+    }
+
+    pub fn spawn(&self) -> [i32; 3] {
+        [self.spawn_x, self.spawn_y, self.spawn_z]
+    }
+
+    pub fn seed(&self) -> Option<i64> {
+        self.random_seed.or(self.world_gen_settings.as_ref().map(|s| s.seed))
+    }
+
+    /// The overworld generator, e.g. `default` before 1.16 and
+    /// `minecraft:noise` after.
+    pub fn generator(&self) -> Option<&str> {
+        if let Some(ref name) = self.generator_name {
+            return Some(name);
+        }
+        self.world_gen_settings.as_ref()
+            .and_then(|s| s.dimensions.get("minecraft:overworld"))
+            .and_then(|d| d.generator.as_ref())
+            .map(|g| &g.kind[..])
+    }
+
+    /// The time of day, from 0 (sunrise) to 23999.
+    pub fn time_of_day(&self) -> i64 {
+        self.day_time.unwrap_or(self.time) % 24000
+    }
+}
+
+#[derive(Deserialize)]
+struct Level {
+    #[serde(rename = "Data")]
+    data: LevelData,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct WorldGenSettings {
+    pub seed: i64,
+    #[serde(default)]
+    pub dimensions: BTreeMap<String, DimensionSettings>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DimensionSettings {
+    pub generator: Option<Generator>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Generator {
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// The world border, stored as loose `Border*` entries of `Data`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+pub struct WorldBorder {
+    #[serde(rename = "BorderCenterX")]
+    pub center_x: f64,
+    #[serde(rename = "BorderCenterZ")]
+    pub center_z: f64,
+    #[serde(rename = "BorderSize")]
+    pub size: f64,
+    #[serde(rename = "BorderSizeLerpTarget")]
+    pub size_lerp_target: f64,
+    #[serde(rename = "BorderSizeLerpTime")]
+    pub size_lerp_time: i64,
+    #[serde(rename = "BorderSafeZone")]
+    pub safe_zone: f64,
+    #[serde(rename = "BorderDamagePerBlock")]
+    pub damage_per_block: f64,
+    #[serde(rename = "BorderWarningBlocks")]
+    pub warning_blocks: f64,
+    #[serde(rename = "BorderWarningTime")]
+    pub warning_time: f64,
+}
+
+impl Default for WorldBorder {
+    /// The border of a new vanilla world.
+    fn default() -> WorldBorder {
+        WorldBorder {
+            center_x: 0.0,
+            center_z: 0.0,
+            size: 60000000.0,
+            size_lerp_target: 60000000.0,
+            size_lerp_time: 0,
+            safe_zone: 5.0,
+            damage_per_block: 0.2,
+            warning_blocks: 5.0,
+            warning_time: 15.0,
+        }
+    }
+}
+
+/// The game version that last saved the world, written since 1.9.
+#[derive(Clone, Debug, Deserialize)]
+pub struct GameVersion {
+    #[serde(rename = "Id")]
+    pub id: i32,
+    #[serde(rename = "Name")]
+    pub name: String,
+    #[serde(rename = "Snapshot", default)]
+    pub snapshot: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use minecraft::nbt::{ de, Nbt };
+    use super::*;
+
+    fn data(snbt: &str) -> LevelData {
+        let root: Nbt = snbt.parse().unwrap();
+        let level: Level = de::from_nbt(&root).unwrap();
+        level.data
+    }
+
+    #[test]
+    fn before_1_16() {
+        let level = data(r#"{Data: {
+            LevelName: "Old", SpawnX: 12, SpawnY: 70, SpawnZ: -40, Time: 30000L,
+            RandomSeed: -1234567890123L, generatorName: "flat", version: 19133,
+            BorderSize: 1000.0d, BorderCenterX: -8.5d
+        }}"#);
+        assert_eq!(level.level_name, "Old");
+        assert_eq!(level.spawn(), [12, 70, -40]);
+        assert_eq!(level.seed(), Some(-1234567890123));
+        assert_eq!(level.generator(), Some("flat"));
+        assert!(level.world_gen_settings.is_none());
+        assert_eq!(level.version, 19133);
+        assert_eq!(level.data_version, None);
+        // Without `DayTime` the time of day follows the world's age.
+        assert_eq!(level.time_of_day(), 6000);
+        assert_eq!(level.world_border.size, 1000.0);
+        assert_eq!(level.world_border.center_x, -8.5);
+        assert_eq!(level.world_border.center_z, 0.0);
+        assert_eq!(level.world_border.damage_per_block, 0.2);
+        assert!(!level.raining);
+        assert!(level.player.is_none());
+    }
+
+    #[test]
+    fn since_1_16() {
+        let level = data(r#"{Data: {
+            LevelName: "New", SpawnX: 0, SpawnY: 64, SpawnZ: 0, Time: 30000L, DayTime: 1000L,
+            DataVersion: 2975, Version: {Id: 2975, Name: "1.18.2", Snapshot: 0b},
+            raining: 1b, rainTime: 200, GameRules: {doDaylightCycle: "false"},
+            WorldGenSettings: {seed: 42L, dimensions: {
+                "minecraft:overworld": {generator: {type: "minecraft:noise"}},
+                "minecraft:the_end": {}
+            }}
+        }}"#);
+        assert_eq!(level.seed(), Some(42));
+        assert_eq!(level.random_seed, None);
+        assert_eq!(level.generator(), Some("minecraft:noise"));
+        assert_eq!(level.data_version, Some(2975));
+        let version = level.game_version.as_ref().unwrap();
+        assert_eq!((version.id, &version.name[..], version.snapshot), (2975, "1.18.2", false));
+        assert_eq!(level.time_of_day(), 1000);
+        assert!(level.raining);
+        assert_eq!(level.rain_time, 200);
+        assert_eq!(level.game_rules.get("doDaylightCycle").map(|r| &r[..]), Some("false"));
+        // None of the border is written, so all of it is the default.
+        assert_eq!(level.world_border.size, 60000000.0);
+        assert_eq!(level.world_border.warning_time, 15.0);
+        assert_eq!(level.world_border.size_lerp_time, 0);
+    }
+}
@@ -1,9 +1,13 @@
 pub mod data_1_8_pre2;
 pub mod biome;
 pub mod block_state;
+pub mod dimension;
+pub mod level;
 pub mod model;
 pub mod nbt;
+pub mod player;
 pub mod region;
+pub mod world;
 
 pub use minecraft::data_1_8_pre2 as data;
 use std::env;
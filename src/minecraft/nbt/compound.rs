@@ -0,0 +1,342 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::Index;
+use std::slice;
+use std::vec;
+
+use minecraft::nbt::Nbt;
+
+/// A list of named NBT values which remembers the order its entries were
+/// inserted in, so that values read from disk are written back unchanged.
+///
+/// Lookups go through a hash index, like they would with a `HashMap`.
+/// Equality ignores the order of the entries. Names are unique: when a
+/// compound read from disk repeats one, the last value is kept, in the
+/// place of the first.
+#[derive(Clone, Default)]
+pub struct Compound {
+    /// The entries in order, with `None` where one was removed.
+    entries: Vec<Option<(String, Nbt)>>,
+    index: HashMap<String, usize>
+}
+
+impl Compound {
+    pub fn new() -> Compound {
+        Compound {
+            entries: Vec::new(),
+            index: HashMap::new()
+        }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Compound {
+        Compound {
+            entries: Vec::with_capacity(capacity),
+            index: HashMap::with_capacity(capacity)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn entry(&self, i: usize) -> &(String, Nbt) {
+        self.entries[i].as_ref().expect("compound index points at a removed entry")
+    }
+
+    pub fn get<Q: ?Sized>(&self, k: &Q) -> Option<&Nbt>
+        where String: Borrow<Q>, Q: Hash + Eq
+    {
+        self.index.get(k).map(|&i| &self.entry(i).1)
+    }
+
+    pub fn get_mut<Q: ?Sized>(&mut self, k: &Q) -> Option<&mut Nbt>
+        where String: Borrow<Q>, Q: Hash + Eq
+    {
+        match self.index.get(k) {
+            Some(&i) => self.entries[i].as_mut().map(|&mut (_, ref mut v)| v),
+            None => None
+        }
+    }
+
+    pub fn contains_key<Q: ?Sized>(&self, k: &Q) -> bool
+        where String: Borrow<Q>, Q: Hash + Eq
+    {
+        self.index.contains_key(k)
+    }
+
+    /// Inserts a value, returning the one it replaced. A replaced entry
+    /// keeps its position, a new one goes at the end.
+    pub fn insert(&mut self, k: String, v: Nbt) -> Option<Nbt> {
+        if let Some(&i) = self.index.get(&k) {
+            return self.entries[i].as_mut().map(|&mut (_, ref mut old)| mem::replace(old, v));
+        }
+        self.index.insert(k.clone(), self.entries.len());
+        self.entries.push(Some((k, v)));
+        None
+    }
+
+    /// Removes a value, keeping the order of the remaining entries.
+    ///
+    /// The entry is only marked as removed, until removed entries make up
+    /// half of the compound and the rest are moved together.
+    pub fn remove<Q: ?Sized>(&mut self, k: &Q) -> Option<Nbt>
+        where String: Borrow<Q>, Q: Hash + Eq
+    {
+        let i = match self.index.remove(k) {
+            Some(i) => i,
+            None => return None
+        };
+        let v = self.entries[i].take().map(|(_, v)| v);
+        if self.index.len() * 2 < self.entries.len() {
+            self.entries.retain(Option::is_some);
+            for (i, entry) in self.entries.iter().enumerate() {
+                if let Some((ref name, _)) = *entry {
+                    *self.index.get_mut::<str>(name).unwrap() = i;
+                }
+            }
+        }
+        v
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.index.clear();
+    }
+
+    pub fn iter(&self) -> Iter {
+        Iter { inner: self.entries.iter(), len: self.len() }
+    }
+
+    pub fn iter_mut(&mut self) -> IterMut {
+        let len = self.len();
+        IterMut { inner: self.entries.iter_mut(), len: len }
+    }
+
+    pub fn keys(&self) -> Keys {
+        Keys { inner: self.iter() }
+    }
+
+    pub fn values(&self) -> Values {
+        Values { inner: self.iter() }
+    }
+}
+
+impl PartialEq for Compound {
+    fn eq(&self, other: &Compound) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(k, v)| other.get(k) == Some(v))
+    }
+}
+
+impl fmt::Debug for Compound {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> Index<&'a str> for Compound {
+    type Output = Nbt;
+
+    fn index(&self, k: &'a str) -> &Nbt {
+        match self.get(k) {
+            Some(v) => v,
+            None => panic!("no entry '{}' in compound", k)
+        }
+    }
+}
+
+impl FromIterator<(String, Nbt)> for Compound {
+    fn from_iter<I: IntoIterator<Item = (String, Nbt)>>(iter: I) -> Compound {
+        let mut c = Compound::new();
+        c.extend(iter);
+        c
+    }
+}
+
+impl Extend<(String, Nbt)> for Compound {
+    fn extend<I: IntoIterator<Item = (String, Nbt)>>(&mut self, iter: I) {
+        for (k, v) in iter {
+            self.insert(k, v);
+        }
+    }
+}
+
+impl IntoIterator for Compound {
+    type Item = (String, Nbt);
+    type IntoIter = IntoIter;
+
+    fn into_iter(self) -> IntoIter {
+        let len = self.len();
+        IntoIter { inner: self.entries.into_iter(), len: len }
+    }
+}
+
+impl<'a> IntoIterator for &'a Compound {
+    type Item = (&'a String, &'a Nbt);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Compound {
+    type Item = (&'a String, &'a mut Nbt);
+    type IntoIter = IterMut<'a>;
+
+    fn into_iter(self) -> IterMut<'a> {
+        self.iter_mut()
+    }
+}
+
+pub struct Iter<'a> {
+    inner: slice::Iter<'a, Option<(String, Nbt)>>,
+    len: usize
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = (&'a String, &'a Nbt);
+
+    fn next(&mut self) -> Option<(&'a String, &'a Nbt)> {
+        for entry in &mut self.inner {
+            if let Some((ref k, ref v)) = *entry {
+                self.len -= 1;
+                return Some((k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+pub struct IterMut<'a> {
+    inner: slice::IterMut<'a, Option<(String, Nbt)>>,
+    len: usize
+}
+
+impl<'a> Iterator for IterMut<'a> {
+    type Item = (&'a String, &'a mut Nbt);
+
+    fn next(&mut self) -> Option<(&'a String, &'a mut Nbt)> {
+        for entry in &mut self.inner {
+            if let Some((ref k, ref mut v)) = *entry {
+                self.len -= 1;
+                return Some((k, v));
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+pub struct IntoIter {
+    inner: vec::IntoIter<Option<(String, Nbt)>>,
+    len: usize
+}
+
+impl Iterator for IntoIter {
+    type Item = (String, Nbt);
+
+    fn next(&mut self) -> Option<(String, Nbt)> {
+        for entry in &mut self.inner {
+            if entry.is_some() {
+                self.len -= 1;
+                return entry;
+            }
+        }
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+pub struct Keys<'a> {
+    inner: Iter<'a>
+}
+
+impl<'a> Iterator for Keys<'a> {
+    type Item = &'a String;
+
+    fn next(&mut self) -> Option<&'a String> {
+        self.inner.next().map(|(k, _)| k)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+pub struct Values<'a> {
+    inner: Iter<'a>
+}
+
+impl<'a> Iterator for Values<'a> {
+    type Item = &'a Nbt;
+
+    fn next(&mut self) -> Option<&'a Nbt> {
+        self.inner.next().map(|(_, v)| v)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minecraft::nbt::Nbt;
+    use super::Compound;
+
+    fn keys(c: &Compound) -> Vec<&str> {
+        c.keys().map(|k| &k[..]).collect()
+    }
+
+    #[test]
+    fn removing_keeps_the_order() {
+        let mut c: Compound = (0..10).map(|i| (i.to_string(), Nbt::Int(i))).collect();
+        assert_eq!(c.remove("3"), Some(Nbt::Int(3)));
+        assert_eq!(c.remove("3"), None);
+        for i in 5..9 {
+            c.remove(&i.to_string()[..]);
+        }
+        assert_eq!(keys(&c), ["0", "1", "2", "4", "9"]);
+        assert_eq!(c.len(), 5);
+        assert_eq!(c.iter().size_hint(), (5, Some(5)));
+        assert_eq!(c["9"], Nbt::Int(9));
+
+        // Past half removed, the entries are moved together.
+        c.remove("0");
+        c.insert("10".to_string(), Nbt::Int(10));
+        c.insert("4".to_string(), Nbt::Int(-4));
+        assert_eq!(keys(&c), ["1", "2", "4", "9", "10"]);
+        assert_eq!(c.get("4"), Some(&Nbt::Int(-4)));
+        *c.get_mut("10").unwrap() = Nbt::Int(-10);
+        let values: Vec<_> = c.into_iter().map(|(_, v)| v).collect();
+        assert_eq!(values, [Nbt::Int(1), Nbt::Int(2), Nbt::Int(-4), Nbt::Int(9), Nbt::Int(-10)]);
+    }
+
+    #[test]
+    fn a_repeated_name_keeps_the_last_value_in_the_first_place() {
+        let c: Compound = vec![("a".to_string(), Nbt::Int(1)),
+                               ("b".to_string(), Nbt::Int(2)),
+                               ("a".to_string(), Nbt::Int(3))].into_iter().collect();
+        assert_eq!(keys(&c), ["a", "b"]);
+        assert_eq!(c["a"], Nbt::Int(3));
+    }
+}
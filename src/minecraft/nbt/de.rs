@@ -0,0 +1,414 @@
+//! Deserializing Rust values from NBT with serde.
+//!
+//! This is the inverse of `ser`: compounds deserialize as structs or maps,
+//! lists and the array tags as sequences, a missing entry as `None` and a
+//! byte as a `bool` and an empty compound as a unit. Strings borrow from
+//! the `Nbt` they come from.
+
+use std::error;
+use std::fmt;
+use std::io::Read;
+use std::slice;
+
+use serde::de::{ self, Deserialize, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor };
+use serde::de::value::{ BorrowedStrDeserializer, SeqDeserializer, StrDeserializer };
+
+use minecraft::nbt::{ compound, ListIter, Nbt, NbtReaderError, NbtRef };
+
+#[derive(Debug)]
+pub enum Error {
+    Message(String),
+    Reader(NbtReaderError)
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Message(ref msg) => f.write_str(msg),
+            Error::Reader(ref err) => write!(f, "{:?}", err)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "NBT deserialization error"
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Message(msg.to_string())
+    }
+}
+
+/// Deserializes a value out of NBT, borrowing strings from it.
+pub fn from_nbt<'a, T: Deserialize<'a>>(nbt: &'a Nbt) -> Result<T> {
+    T::deserialize(Deserializer::new(nbt.as_nbt_ref()))
+}
+
+/// Reads an uncompressed NBT stream and deserializes its root tag. The
+/// whole stream is read into an `Nbt` first, so it has to fit in memory
+/// next to the value.
+pub fn from_reader<R: Read, T: DeserializeOwned>(r: R) -> Result<T> {
+    let nbt = try!(Nbt::from_reader(r).map_err(Error::Reader));
+    from_nbt(&nbt)
+}
+
+pub struct Deserializer<'a> {
+    value: NbtRef<'a>
+}
+
+impl<'a> Deserializer<'a> {
+    pub fn new(value: NbtRef<'a>) -> Deserializer<'a> {
+        Deserializer { value: value }
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Byte(x) => visitor.visit_i8(x),
+            NbtRef::Short(x) => visitor.visit_i16(x),
+            NbtRef::Int(x) => visitor.visit_i32(x),
+            NbtRef::Long(x) => visitor.visit_i64(x),
+            NbtRef::Float(x) => visitor.visit_f32(x),
+            NbtRef::Double(x) => visitor.visit_f64(x),
+            NbtRef::ByteArray(a) => visitor.visit_seq(ByteArrayAccess { iter: a.iter() }),
+            NbtRef::IntArray(a) => {
+                visitor.visit_seq(SeqDeserializer::<_, Error>::new(a.iter().cloned()))
+            }
+            NbtRef::LongArray(a) => {
+                visitor.visit_seq(SeqDeserializer::<_, Error>::new(a.iter().cloned()))
+            }
+            NbtRef::String(s) => visitor.visit_borrowed_str(s),
+            NbtRef::List(l) => visitor.visit_seq(ListAccess { iter: l.iter() }),
+            NbtRef::Compound(c) => {
+                visitor.visit_map(CompoundAccess { iter: c.iter(), value: None })
+            }
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Byte(x) => visitor.visit_bool(x != 0),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    // Unsigned integers are stored in the signed tag of the same width.
+
+    fn deserialize_u8<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Byte(x) => visitor.visit_u8(x as u8),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u16<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Short(x) => visitor.visit_u16(x as u16),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Int(x) => visitor.visit_u32(x as u32),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_u64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Long(x) => visitor.visit_u64(x as u64),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::ByteArray(a) => visitor.visit_borrowed_bytes(a),
+            _ => self.deserialize_any(visitor)
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// Missing compound entries are `None`, so anything that is present
+    /// is `Some`.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        match self.value {
+            NbtRef::Compound(c) if c.len() == 0 => visitor.visit_unit(),
+            _ => Err(de::Error::custom("expected an empty compound"))
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+                                                  -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(self, _name: &'static str,
+                                         _variants: &'static [&'static str], visitor: V)
+                                         -> Result<V::Value> {
+        match self.value {
+            NbtRef::String(s) => {
+                let variant: StrDeserializer<Error> = s.into_deserializer();
+                visitor.visit_enum(variant)
+            }
+            NbtRef::Compound(c) if c.len() == 1 => {
+                let (name, value) = c.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess { name: name, value: value.as_nbt_ref() })
+            }
+            _ => Err(de::Error::custom("expected a string or a compound with one entry"))
+        }
+    }
+
+    /// Written as an empty compound.
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V)
+                                               -> Result<V::Value> {
+        de::Deserializer::deserialize_unit(self, visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+/// The bytes of a byte array, which are signed like those of a byte tag
+/// unless read as `u8`.
+struct ByteArrayAccess<'a> {
+    iter: slice::Iter<'a, u8>
+}
+
+impl<'de> de::SeqAccess<'de> for ByteArrayAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T)
+                                                  -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some(&x) => seed.deserialize(Deserializer::new(NbtRef::Byte(x as i8))).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct ListAccess<'a> {
+    iter: ListIter<'a>
+}
+
+impl<'de> de::SeqAccess<'de> for ListAccess<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T)
+                                                  -> Result<Option<T::Value>> {
+        match self.iter.next() {
+            Some(value) => seed.deserialize(Deserializer::new(value)).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.size_hint().0)
+    }
+}
+
+struct CompoundAccess<'a> {
+    iter: compound::Iter<'a>,
+    value: Option<&'a Nbt>
+}
+
+impl<'de> de::MapAccess<'de> for CompoundAccess<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        match self.iter.next() {
+            Some((name, value)) => {
+                self.value = Some(value);
+                seed.deserialize(BorrowedStrDeserializer::new(&name[..])).map(Some)
+            }
+            None => Ok(None)
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value> {
+        let value = self.value.take().expect("next_value_seed called before next_key_seed");
+        seed.deserialize(Deserializer::new(value.as_nbt_ref()))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.size_hint().0)
+    }
+}
+
+/// An enum variant stored as a compound with a single entry.
+struct EnumAccess<'a> {
+    name: &'a str,
+    value: NbtRef<'a>
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V)
+                                             -> Result<(V::Value, Deserializer<'de>)> {
+        let name = BorrowedStrDeserializer::<Error>::new(self.name);
+        let variant = try!(seed.deserialize(name));
+        Ok((variant, Deserializer::new(self.value)))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(self, _fields: &'static [&'static str], visitor: V)
+                                       -> Result<V::Value> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+
+    use serde::Serialize;
+    use serde::de::DeserializeOwned;
+
+    use minecraft::nbt::{ Compound, Nbt };
+    use minecraft::nbt::ser::{ self, byte_array, int_array, long_array };
+    use super::from_nbt;
+
+    fn assert_round_trip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: T) {
+        let nbt = ser::to_nbt(&value).unwrap();
+        assert_eq!(from_nbt::<T>(&nbt).unwrap(), value);
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Unit;
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    enum Shape {
+        Empty,
+        Circle(f32),
+        Rect { w: u16, h: u16 }
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Everything {
+        flag: bool,
+        byte: i8,
+        ubyte: u8,
+        short: i16,
+        int: u32,
+        long: i64,
+        float: f32,
+        double: f64,
+        name: String,
+        missing: Option<String>,
+        present: Option<i32>,
+        list: Vec<Vec<i16>>,
+        map: HashMap<String, Shape>,
+        unit: Unit,
+        #[serde(serialize_with = "byte_array")]
+        bytes: Vec<u8>,
+        #[serde(serialize_with = "byte_array")]
+        signed_bytes: Vec<u8>,
+        #[serde(serialize_with = "int_array")]
+        ints: Vec<i32>,
+        #[serde(serialize_with = "long_array")]
+        longs: Vec<i64>
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let mut map = HashMap::new();
+        map.insert("empty".to_string(), Shape::Empty);
+        map.insert("circle".to_string(), Shape::Circle(0.5));
+        map.insert("rect".to_string(), Shape::Rect { w: 3, h: 65535 });
+        assert_round_trip(Everything {
+            flag: true,
+            byte: -128,
+            ubyte: 255,
+            short: -2,
+            int: 4000000000,
+            long: i64::min_value(),
+            float: 1.5,
+            double: -0.25,
+            name: "Ünïcode\0".to_string(),
+            missing: None,
+            present: Some(7),
+            list: vec![vec![1, 2], vec![], vec![-3]],
+            map: map,
+            unit: Unit,
+            bytes: vec![0, 1, 127],
+            signed_bytes: vec![128, 255],
+            ints: vec![-1, 0, 1],
+            longs: vec![]
+        });
+        assert_round_trip(Unit);
+        assert_round_trip(vec![Shape::Circle(-1.0), Shape::Rect { w: 0, h: 1 }]);
+    }
+
+    #[test]
+    fn byte_arrays_are_signed() {
+        let nbt = Nbt::ByteArray(vec![0, 127, 128, 255]);
+        assert_eq!(from_nbt::<Vec<i8>>(&nbt).unwrap(), vec![0, 127, -128, -1]);
+        assert_eq!(from_nbt::<Vec<u8>>(&nbt).unwrap(), vec![0, 127, 128, 255]);
+    }
+
+    #[derive(Serialize, Deserialize, PartialEq, Debug)]
+    struct Units {
+        unit: (),
+        units: Vec<()>,
+        pair: ((), Unit)
+    }
+
+    #[test]
+    fn units_are_empty_compounds() {
+        let empty = Nbt::Compound(Compound::new());
+        assert_eq!(ser::to_nbt(&Unit).unwrap(), empty);
+        assert_eq!(ser::to_nbt(&()).unwrap(), empty);
+        assert!(from_nbt::<Unit>(&Nbt::Int(0)).is_err());
+        assert!(from_nbt::<()>(&Nbt::Int(0)).is_err());
+        assert_round_trip(());
+        assert_round_trip(Units { unit: (), units: vec![(), ()], pair: ((), Unit) });
+
+        // A missing unit is an error, like any other missing field.
+        let nbt: Nbt = "{units: [], pair: [{}, {}]}".parse().unwrap();
+        assert!(from_nbt::<Units>(&nbt).is_err());
+    }
+}
@@ -0,0 +1,1864 @@
+pub mod compound;
+pub mod de;
+pub mod mutf8;
+pub mod path;
+pub mod ser;
+pub mod slice;
+pub mod snbt;
+
+use std::cmp;
+use std::fmt;
+use std::io::{ Read, Write };
+use std::io;
+use std::mem;
+use std::ops::Index;
+use std::string::ToString;
+
+use byteorder::{ BigEndian, LittleEndian, ReadBytesExt, WriteBytesExt };
+use flate2::Compression;
+use flate2::read::{ GzDecoder, ZlibDecoder };
+use flate2::write::{ GzEncoder, ZlibEncoder };
+use rustc_serialize;
+use rustc_serialize::hex::ToHex;
+
+pub use self::compound::Compound;
+
+use self::DecoderError::*;
+use self::EncoderError::*;
+
+/// Represents a NBT value
+#[derive(Clone, PartialEq)]
+pub enum Nbt {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<u8>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+    String(String),
+    List(List),
+    Compound(Compound)
+}
+
+impl fmt::Debug for Nbt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nbt::Byte(x) => write!(f, "{}b", x),
+            Nbt::Short(x) => write!(f, "{}s", x),
+            Nbt::Int(x) => write!(f, "{}i", x),
+            Nbt::Long(x) => write!(f, "{}L", x),
+            Nbt::Float(x) => write!(f, "{:.1}f", x),
+            Nbt::Double(x) => write!(f, "{:.1}", x),
+            Nbt::ByteArray(ref x) => write!(f, "b<{}>", x[..].to_hex()),
+            Nbt::IntArray(ref x) => write!(f, "{:?}", *x),
+            Nbt::LongArray(ref x) => write!(f, "{:?}", *x),
+            Nbt::String(ref x) => write!(f, "\"{}\"", *x),
+            Nbt::List(ref x) => write!(f, "{:?}", *x),
+            Nbt::Compound(ref x) => write!(f, "{:?}", *x)
+        }
+    }
+}
+
+impl fmt::Display for Nbt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Nbt::Byte(x) => write!(f, "{}", x),
+            Nbt::Short(x) => write!(f, "{}", x),
+            Nbt::Int(x) => write!(f, "{}", x),
+            Nbt::Long(x) => write!(f, "{}", x),
+            Nbt::Float(x) => write!(f, "{:.1}", x),
+            Nbt::Double(x) => write!(f, "{:.1}", x),
+            Nbt::ByteArray(ref x) => write!(f, "<{}>", x[..].to_hex()),
+            Nbt::IntArray(ref x) => write!(f, "{:?}", *x),
+            Nbt::LongArray(ref x) => write!(f, "{:?}", *x),
+            Nbt::String(ref x) => write!(f, "\"{}\"", *x),
+            Nbt::List(ref x) => write!(f, "{:?}", *x),
+            Nbt::Compound(ref x) => write!(f, "{:?}", *x)
+        }
+    }
+}
+
+/// An ordered list of NBT values.
+#[derive(Clone, PartialEq, Debug)]
+pub enum List {
+    /// An empty list that never had an element type, as the game writes
+    /// lists nothing was ever added to. An empty list of any other variant
+    /// keeps its element type.
+    End,
+    Byte(Vec<i8>),
+    Short(Vec<i16>),
+    Int(Vec<i32>),
+    Long(Vec<i64>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+    ByteArray(Vec<Vec<u8>>),
+    IntArray(Vec<Vec<i32>>),
+    LongArray(Vec<Vec<i64>>),
+    String(Vec<String>),
+    List(Vec<List>),
+    Compound(Vec<Compound>)
+}
+
+impl List {
+    /// Builds a list out of loose values, which must all have the same type.
+    /// An empty list becomes `List::End`. On a type mismatch the first
+    /// offending value is returned.
+    pub fn from_values(values: Vec<Nbt>) -> Result<List, Nbt> {
+        macro_rules! collect(
+            ($values:expr, $t:ident) => ({
+                let mut list = Vec::with_capacity($values.len());
+                for value in $values.into_iter() {
+                    match value {
+                        Nbt::$t(v) => list.push(v),
+                        other => return Err(other)
+                    }
+                }
+                Ok(List::$t(list))
+            })
+        );
+
+        let tag_type = match values.first() {
+            Some(first) => first.tag_type(),
+            None => return Ok(List::End)
+        };
+        match tag_type {
+            TAG_BYTE => collect!(values, Byte),
+            TAG_SHORT => collect!(values, Short),
+            TAG_INT => collect!(values, Int),
+            TAG_LONG => collect!(values, Long),
+            TAG_FLOAT => collect!(values, Float),
+            TAG_DOUBLE => collect!(values, Double),
+            TAG_BYTE_ARRAY => collect!(values, ByteArray),
+            TAG_INT_ARRAY => collect!(values, IntArray),
+            TAG_LONG_ARRAY => collect!(values, LongArray),
+            TAG_STRING => collect!(values, String),
+            TAG_LIST => collect!(values, List),
+            _ => collect!(values, Compound)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match *self {
+            List::End => 0,
+            List::Byte(ref v) => v.len(),
+            List::Short(ref v) => v.len(),
+            List::Int(ref v) => v.len(),
+            List::Long(ref v) => v.len(),
+            List::Float(ref v) => v.len(),
+            List::Double(ref v) => v.len(),
+            List::ByteArray(ref v) => v.len(),
+            List::IntArray(ref v) => v.len(),
+            List::LongArray(ref v) => v.len(),
+            List::String(ref v) => v.len(),
+            List::List(ref v) => v.len(),
+            List::Compound(ref v) => v.len()
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn get(&self, i: usize) -> Option<NbtRef> {
+        if i >= self.len() {
+            return None;
+        }
+        Some(match *self {
+            List::End => return None,
+            List::Byte(ref v) => NbtRef::Byte(v[i]),
+            List::Short(ref v) => NbtRef::Short(v[i]),
+            List::Int(ref v) => NbtRef::Int(v[i]),
+            List::Long(ref v) => NbtRef::Long(v[i]),
+            List::Float(ref v) => NbtRef::Float(v[i]),
+            List::Double(ref v) => NbtRef::Double(v[i]),
+            List::ByteArray(ref v) => NbtRef::ByteArray(&v[i][..]),
+            List::IntArray(ref v) => NbtRef::IntArray(&v[i][..]),
+            List::LongArray(ref v) => NbtRef::LongArray(&v[i][..]),
+            List::String(ref v) => NbtRef::String(&v[i][..]),
+            List::List(ref v) => NbtRef::List(&v[i]),
+            List::Compound(ref v) => NbtRef::Compound(&v[i])
+        })
+    }
+
+    pub fn iter(&self) -> ListIter {
+        ListIter { list: self, next: 0 }
+    }
+
+    /// The tag type of the list elements.
+    fn tag_type(&self) -> i8 {
+        match *self {
+            List::End => TAG_END,
+            List::Byte(_) => TAG_BYTE,
+            List::Short(_) => TAG_SHORT,
+            List::Int(_) => TAG_INT,
+            List::Long(_) => TAG_LONG,
+            List::Float(_) => TAG_FLOAT,
+            List::Double(_) => TAG_DOUBLE,
+            List::ByteArray(_) => TAG_BYTE_ARRAY,
+            List::IntArray(_) => TAG_INT_ARRAY,
+            List::LongArray(_) => TAG_LONG_ARRAY,
+            List::String(_) => TAG_STRING,
+            List::List(_) => TAG_LIST,
+            List::Compound(_) => TAG_COMPOUND
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a List {
+    type Item = NbtRef<'a>;
+    type IntoIter = ListIter<'a>;
+
+    fn into_iter(self) -> ListIter<'a> {
+        self.iter()
+    }
+}
+
+/// Iterates over the elements of a `List`, whatever their type.
+pub struct ListIter<'a> {
+    list: &'a List,
+    next: usize
+}
+
+impl<'a> Iterator for ListIter<'a> {
+    type Item = NbtRef<'a>;
+
+    fn next(&mut self) -> Option<NbtRef<'a>> {
+        let item = self.list.get(self.next);
+        if item.is_some() {
+            self.next += 1;
+        }
+        item
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let n = self.list.len() - self.next;
+        (n, Some(n))
+    }
+}
+
+impl Nbt {
+    pub fn from_reader<R: Read>(r: R) -> NbtReaderResult<Nbt> {
+        Nbt::from_reader_as(r, Dialect::Java)
+    }
+
+    /// Reads the root tag of a stream in the given dialect.
+    pub fn from_reader_as<R: Read>(r: R, dialect: Dialect) -> NbtReaderResult<Nbt> {
+        let mut reader = NbtReader::with_dialect(r, dialect, NbtLimits::default());
+        reader.root().map(|(nbt, _)| nbt)
+    }
+
+    pub fn from_gzip(data: &[u8]) -> NbtReaderResult<Nbt> {
+        let reader = try!(GzDecoder::new(data));
+        Nbt::from_reader(reader)
+    }
+
+    pub fn from_zlib(data: &[u8]) -> NbtReaderResult<Nbt> {
+        let reader = ZlibDecoder::new(data);
+        Nbt::from_reader(reader)
+    }
+
+    /// Writes the value as an unnamed root tag.
+    pub fn to_writer<W: Write>(&self, w: W) -> NbtWriterResult<()> {
+        self.to_writer_as(w, Dialect::Java)
+    }
+
+    /// Writes the value as an unnamed root tag in the given dialect.
+    pub fn to_writer_as<W: Write>(&self, w: W, dialect: Dialect) -> NbtWriterResult<()> {
+        NbtWriter::with_dialect(w, dialect).root(self, "")
+    }
+
+    pub fn to_gzip(&self) -> NbtWriterResult<Vec<u8>> {
+        let mut writer = GzEncoder::new(Vec::new(), Compression::Default);
+        try!(self.to_writer(&mut writer));
+        writer.finish().map_err(NbtWriterError::from)
+    }
+
+    pub fn to_zlib(&self) -> NbtWriterResult<Vec<u8>> {
+        let mut writer = ZlibEncoder::new(Vec::new(), Compression::Default);
+        try!(self.to_writer(&mut writer));
+        writer.finish().map_err(NbtWriterError::from)
+    }
+
+    fn tag_type(&self) -> i8 {
+        match *self {
+            Nbt::Byte(_) => TAG_BYTE,
+            Nbt::Short(_) => TAG_SHORT,
+            Nbt::Int(_) => TAG_INT,
+            Nbt::Long(_) => TAG_LONG,
+            Nbt::Float(_) => TAG_FLOAT,
+            Nbt::Double(_) => TAG_DOUBLE,
+            Nbt::ByteArray(_) => TAG_BYTE_ARRAY,
+            Nbt::IntArray(_) => TAG_INT_ARRAY,
+            Nbt::LongArray(_) => TAG_LONG_ARRAY,
+            Nbt::String(_) => TAG_STRING,
+            Nbt::List(_) => TAG_LIST,
+            Nbt::Compound(_) => TAG_COMPOUND
+        }
+    }
+
+    /// Borrows the value, for use with the `NbtRef` accessors.
+    pub fn as_nbt_ref(&self) -> NbtRef {
+        match *self {
+            Nbt::Byte(x) => NbtRef::Byte(x),
+            Nbt::Short(x) => NbtRef::Short(x),
+            Nbt::Int(x) => NbtRef::Int(x),
+            Nbt::Long(x) => NbtRef::Long(x),
+            Nbt::Float(x) => NbtRef::Float(x),
+            Nbt::Double(x) => NbtRef::Double(x),
+            Nbt::ByteArray(ref x) => NbtRef::ByteArray(&x[..]),
+            Nbt::IntArray(ref x) => NbtRef::IntArray(&x[..]),
+            Nbt::LongArray(ref x) => NbtRef::LongArray(&x[..]),
+            Nbt::String(ref x) => NbtRef::String(&x[..]),
+            Nbt::List(ref x) => NbtRef::List(x),
+            Nbt::Compound(ref x) => NbtRef::Compound(x)
+        }
+    }
+
+    /// Looks up an entry of a compound. Returns `None` for other values.
+    pub fn get(&self, key: &str) -> Option<&Nbt> {
+        match *self { Nbt::Compound(ref c) => c.get(key), _ => None }
+    }
+
+    pub fn as_byte(&self) -> Option<i8> { self.as_nbt_ref().as_byte() }
+    pub fn as_short(&self) -> Option<i16> { self.as_nbt_ref().as_short() }
+    pub fn as_int(&self) -> Option<i32> { self.as_nbt_ref().as_int() }
+    pub fn as_long(&self) -> Option<i64> { self.as_nbt_ref().as_long() }
+    pub fn as_float(&self) -> Option<f32> { self.as_nbt_ref().as_float() }
+    pub fn as_double(&self) -> Option<f64> { self.as_nbt_ref().as_double() }
+    pub fn as_bytearray(&self) -> Option<&[u8]> { self.as_nbt_ref().as_bytearray() }
+    pub fn as_int_array(&self) -> Option<&[i32]> { self.as_nbt_ref().as_int_array() }
+    pub fn as_long_array(&self) -> Option<&[i64]> { self.as_nbt_ref().as_long_array() }
+    pub fn as_string(&self) -> Option<&str> { self.as_nbt_ref().as_string() }
+    pub fn as_list(&self) -> Option<&List> { self.as_nbt_ref().as_list() }
+    pub fn as_compound(&self) -> Option<&Compound> { self.as_nbt_ref().as_compound() }
+
+    pub fn as_byte_list(&self) -> Option<&[i8]> { self.as_nbt_ref().as_byte_list() }
+    pub fn as_short_list(&self) -> Option<&[i16]> { self.as_nbt_ref().as_short_list() }
+    pub fn as_int_list(&self) -> Option<&[i32]> { self.as_nbt_ref().as_int_list() }
+    pub fn as_long_list(&self) -> Option<&[i64]> { self.as_nbt_ref().as_long_list() }
+    pub fn as_float_list(&self) -> Option<&[f32]> { self.as_nbt_ref().as_float_list() }
+    pub fn as_double_list(&self) -> Option<&[f64]> { self.as_nbt_ref().as_double_list() }
+    pub fn as_bytearray_list(&self) -> Option<&[Vec<u8>]> { self.as_nbt_ref().as_bytearray_list() }
+    pub fn as_int_array_list(&self) -> Option<&[Vec<i32>]> { self.as_nbt_ref().as_int_array_list() }
+    pub fn as_long_array_list(&self) -> Option<&[Vec<i64>]> { self.as_nbt_ref().as_long_array_list() }
+    pub fn as_string_list(&self) -> Option<&[String]> { self.as_nbt_ref().as_string_list() }
+    pub fn as_list_list(&self) -> Option<&[List]> { self.as_nbt_ref().as_list_list() }
+    pub fn as_compound_list(&self) -> Option<&[Compound]> { self.as_nbt_ref().as_compound_list() }
+
+    pub fn into_compound(self) -> Result<Compound, Nbt> {
+        match self { Nbt::Compound(c) => Ok(c), x => Err(x) }
+    }
+
+    pub fn into_compound_list(self) -> Result<Vec<Compound>, Nbt> {
+        match self {
+            Nbt::List(List::Compound(c)) => Ok(c),
+            Nbt::List(List::End) => Ok(vec![]),
+            x => Err(x)
+        }
+    }
+
+    pub fn into_bytearray(self) -> Result<Vec<u8>, Nbt> {
+        match self { Nbt::ByteArray(b) => Ok(b), x => Err(x) }
+    }
+}
+
+/// A borrowed NBT value. Numbers are copied out, everything else points
+/// into the `Nbt` it was taken from.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum NbtRef<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    IntArray(&'a [i32]),
+    LongArray(&'a [i64]),
+    String(&'a str),
+    List(&'a List),
+    Compound(&'a Compound)
+}
+
+macro_rules! list_accessor(
+    ($name:ident, $t:ident, $elem:ty) => (
+        pub fn $name(self) -> Option<&'a [$elem]> {
+            match self {
+                NbtRef::List(&List::$t(ref v)) => Some(&v[..]),
+                NbtRef::List(&List::End) => Some(&[]),
+                _ => None
+            }
+        }
+    )
+);
+
+impl<'a> NbtRef<'a> {
+    pub fn to_nbt(self) -> Nbt {
+        match self {
+            NbtRef::Byte(x) => Nbt::Byte(x),
+            NbtRef::Short(x) => Nbt::Short(x),
+            NbtRef::Int(x) => Nbt::Int(x),
+            NbtRef::Long(x) => Nbt::Long(x),
+            NbtRef::Float(x) => Nbt::Float(x),
+            NbtRef::Double(x) => Nbt::Double(x),
+            NbtRef::ByteArray(x) => Nbt::ByteArray(x.to_vec()),
+            NbtRef::IntArray(x) => Nbt::IntArray(x.to_vec()),
+            NbtRef::LongArray(x) => Nbt::LongArray(x.to_vec()),
+            NbtRef::String(x) => Nbt::String(x.to_string()),
+            NbtRef::List(x) => Nbt::List(x.clone()),
+            NbtRef::Compound(x) => Nbt::Compound(x.clone())
+        }
+    }
+
+    /// Looks up an entry of a compound. Returns `None` for other values.
+    pub fn get(self, key: &str) -> Option<NbtRef<'a>> {
+        match self {
+            NbtRef::Compound(c) => c.get(key).map(Nbt::as_nbt_ref),
+            _ => None
+        }
+    }
+
+    pub fn as_byte(self) -> Option<i8> {
+        match self { NbtRef::Byte(x) => Some(x), _ => None }
+    }
+
+    pub fn as_short(self) -> Option<i16> {
+        match self { NbtRef::Short(x) => Some(x), _ => None }
+    }
+
+    pub fn as_int(self) -> Option<i32> {
+        match self { NbtRef::Int(x) => Some(x), _ => None }
+    }
+
+    pub fn as_long(self) -> Option<i64> {
+        match self { NbtRef::Long(x) => Some(x), _ => None }
+    }
+
+    pub fn as_float(self) -> Option<f32> {
+        match self { NbtRef::Float(x) => Some(x), _ => None }
+    }
+
+    pub fn as_double(self) -> Option<f64> {
+        match self { NbtRef::Double(x) => Some(x), _ => None }
+    }
+
+    pub fn as_bytearray(self) -> Option<&'a [u8]> {
+        match self { NbtRef::ByteArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_int_array(self) -> Option<&'a [i32]> {
+        match self { NbtRef::IntArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_long_array(self) -> Option<&'a [i64]> {
+        match self { NbtRef::LongArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_string(self) -> Option<&'a str> {
+        match self { NbtRef::String(x) => Some(x), _ => None }
+    }
+
+    pub fn as_list(self) -> Option<&'a List> {
+        match self { NbtRef::List(x) => Some(x), _ => None }
+    }
+
+    pub fn as_compound(self) -> Option<&'a Compound> {
+        match self { NbtRef::Compound(x) => Some(x), _ => None }
+    }
+
+    list_accessor!(as_byte_list, Byte, i8);
+    list_accessor!(as_short_list, Short, i16);
+    list_accessor!(as_int_list, Int, i32);
+    list_accessor!(as_long_list, Long, i64);
+    list_accessor!(as_float_list, Float, f32);
+    list_accessor!(as_double_list, Double, f64);
+    list_accessor!(as_bytearray_list, ByteArray, Vec<u8>);
+    list_accessor!(as_int_array_list, IntArray, Vec<i32>);
+    list_accessor!(as_long_array_list, LongArray, Vec<i64>);
+    list_accessor!(as_string_list, String, String);
+    list_accessor!(as_list_list, List, List);
+    list_accessor!(as_compound_list, Compound, Compound);
+}
+
+impl<'a> From<&'a Nbt> for NbtRef<'a> {
+    fn from(nbt: &'a Nbt) -> NbtRef<'a> {
+        nbt.as_nbt_ref()
+    }
+}
+
+impl<'a> Index<&'a str> for Nbt {
+    type Output = Nbt;
+
+    fn index<'b>(&'b self, s: &'a str) -> &'b Nbt {
+        match *self {
+            Nbt::Compound(ref c) => &c[s],
+            _ => panic!("cannot index non-compound Nbt ({:?}) with '{}'", self, s)
+        }
+    }
+}
+
+const TAG_END: i8 = 0;
+const TAG_BYTE: i8 = 1;
+const TAG_SHORT: i8 = 2;
+const TAG_INT: i8 = 3;
+const TAG_LONG: i8 = 4;
+const TAG_FLOAT: i8 = 5;
+const TAG_DOUBLE: i8 = 6;
+const TAG_BYTE_ARRAY: i8 = 7;
+const TAG_STRING: i8 = 8;
+const TAG_LIST: i8 = 9;
+const TAG_COMPOUND: i8 = 10;
+const TAG_INT_ARRAY: i8 = 11;
+const TAG_LONG_ARRAY: i8 = 12;
+
+/// The binary encodings of NBT. They share the tag layout but differ in
+/// byte order, in how integers and lengths are written, and in whether
+/// the root tag has a name.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Dialect {
+    /// Big-endian, as in Java Edition files.
+    Java,
+    /// Java with a nameless root tag, as sent over the network since
+    /// Java Edition 1.20.2.
+    JavaNetwork,
+    /// Little-endian, as in Bedrock Edition files.
+    Bedrock,
+    /// Bedrock's network encoding: little-endian, except that ints, longs
+    /// and array lengths are zigzag varints and string lengths are
+    /// unsigned varints.
+    BedrockNetwork,
+}
+
+impl Dialect {
+    fn named_root(self) -> bool {
+        self != Dialect::JavaNetwork
+    }
+
+    fn varints(self) -> bool {
+        self == Dialect::BedrockNetwork
+    }
+
+    fn little_endian(self) -> bool {
+        self == Dialect::Bedrock || self == Dialect::BedrockNetwork
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Dialect {
+        Dialect::Java
+    }
+}
+
+pub type NbtReaderResult<T> = Result<T, NbtReaderError>;
+
+#[derive(Debug)]
+pub enum NbtReaderError {
+    Io(io::Error),
+    /// A string which isn't valid modified UTF-8.
+    Mutf8(mutf8::Mutf8Error),
+    /// The input ended in the middle of a tag.
+    UnexpectedEof,
+    /// A tag type outside of `TAG_END`..`TAG_LONG_ARRAY`.
+    UnknownTag(i8),
+    /// A list of `TAG_END` elements with a non-zero length.
+    InvalidEndList(i32),
+    /// The root tag was a `TAG_END`.
+    EmptyRoot,
+    /// An array, list or string with a negative length prefix.
+    NegativeLength(i32),
+    /// An array or list longer than `NbtLimits::max_array_len`.
+    ArrayTooLong(usize),
+    /// Compounds and lists nested deeper than `NbtLimits::max_depth`.
+    DepthLimit(usize),
+    /// More data than `NbtLimits::max_bytes` in a single value.
+    SizeLimit(u64),
+    /// A varint with more bytes than its type can hold.
+    VarIntTooLong,
+}
+
+impl From<io::Error> for NbtReaderError {
+    fn from(err: io::Error) -> NbtReaderError {
+        match err.kind() {
+            io::ErrorKind::UnexpectedEof => NbtReaderError::UnexpectedEof,
+            _ => NbtReaderError::Io(err)
+        }
+    }
+}
+
+impl From<mutf8::Mutf8Error> for NbtReaderError {
+    fn from(err: mutf8::Mutf8Error) -> NbtReaderError { NbtReaderError::Mutf8(err) }
+}
+
+/// Bounds on the resources `NbtReader` spends on a single value, so that
+/// corrupt or hostile input fails with an error instead of exhausting
+/// memory or the stack.
+#[derive(Copy, Clone, Debug)]
+pub struct NbtLimits {
+    /// How deeply compounds and lists may be nested.
+    pub max_depth: usize,
+    /// The total number of bytes that may be read.
+    pub max_bytes: u64,
+    /// The largest number of elements in any array or list.
+    pub max_array_len: usize,
+}
+
+impl NbtLimits {
+    pub fn unlimited() -> NbtLimits {
+        NbtLimits {
+            max_depth: usize::max_value(),
+            max_bytes: u64::max_value(),
+            max_array_len: usize::max_value()
+        }
+    }
+}
+
+impl Default for NbtLimits {
+    /// The same nesting depth as vanilla, and sizes comfortably above
+    /// anything found in a real chunk or level.dat.
+    fn default() -> NbtLimits {
+        NbtLimits {
+            max_depth: 512,
+            max_bytes: 64 << 20,
+            max_array_len: 1 << 24
+        }
+    }
+}
+
+/// Elements preallocated for an array before any of them are read.
+const MAX_PREALLOCATED: usize = 1 << 16;
+
+pub struct NbtReader<R> {
+    reader: R,
+    dialect: Dialect,
+    limits: NbtLimits,
+    depth: usize,
+    bytes: u64
+}
+
+macro_rules! read_ordered(
+    ($r:expr, $read:ident) => (
+        if $r.dialect.little_endian() {
+            $r.reader.$read::<LittleEndian>()
+        } else {
+            $r.reader.$read::<BigEndian>()
+        }
+    )
+);
+
+impl<R: Read> NbtReader<R> {
+    pub fn new(reader: R) -> NbtReader<R> {
+        NbtReader::with_limits(reader, NbtLimits::default())
+    }
+
+    pub fn with_limits(reader: R, limits: NbtLimits) -> NbtReader<R> {
+        NbtReader::with_dialect(reader, Dialect::Java, limits)
+    }
+
+    pub fn with_dialect(reader: R, dialect: Dialect, limits: NbtLimits) -> NbtReader<R> {
+        NbtReader {
+            reader: reader,
+            dialect: dialect,
+            limits: limits,
+            depth: 0,
+            bytes: 0
+        }
+    }
+
+    /// Accounts for `n` more bytes, failing if that exceeds the limit.
+    fn claim(&mut self, n: u64) -> NbtReaderResult<()> {
+        self.bytes = self.bytes.saturating_add(n);
+        if self.bytes > self.limits.max_bytes {
+            return Err(NbtReaderError::SizeLimit(self.limits.max_bytes));
+        }
+        Ok(())
+    }
+
+    /// Reads an unsigned LEB128 varint of at most `bits` bits.
+    fn varint(&mut self, bits: u32) -> NbtReaderResult<u64> {
+        let mut x = 0u64;
+        let mut shift = 0;
+        loop {
+            try!(self.claim(1));
+            let b = try!(self.reader.read_u8());
+            x |= ((b & 0x7f) as u64) << shift;
+            if b & 0x80 == 0 {
+                return Ok(x);
+            }
+            shift += 7;
+            if shift >= bits {
+                return Err(NbtReaderError::VarIntTooLong);
+            }
+        }
+    }
+
+    fn i8(&mut self) -> NbtReaderResult<i8> { try!(self.claim(1)); self.reader.read_i8().map_err(NbtReaderError::from) }
+    fn i16(&mut self) -> NbtReaderResult<i16> { try!(self.claim(2)); read_ordered!(self, read_i16).map_err(NbtReaderError::from) }
+    fn f32(&mut self) -> NbtReaderResult<f32> { try!(self.claim(4)); read_ordered!(self, read_f32).map_err(NbtReaderError::from) }
+    fn f64(&mut self) -> NbtReaderResult<f64> { try!(self.claim(8)); read_ordered!(self, read_f64).map_err(NbtReaderError::from) }
+
+    fn i32(&mut self) -> NbtReaderResult<i32> {
+        if self.dialect.varints() {
+            let x = try!(self.varint(35)) as u32;
+            return Ok((x >> 1) as i32 ^ -((x & 1) as i32));
+        }
+        try!(self.claim(4));
+        read_ordered!(self, read_i32).map_err(NbtReaderError::from)
+    }
+
+    fn i64(&mut self) -> NbtReaderResult<i64> {
+        if self.dialect.varints() {
+            let x = try!(self.varint(70));
+            return Ok((x >> 1) as i64 ^ -((x & 1) as i64));
+        }
+        try!(self.claim(8));
+        read_ordered!(self, read_i64).map_err(NbtReaderError::from)
+    }
+
+    /// The fewest bytes an int, and so an array or list length, takes.
+    fn int_size(&self) -> u64 {
+        if self.dialect.varints() { 1 } else { 4 }
+    }
+
+    /// The fewest bytes a long takes.
+    fn long_size(&self) -> u64 {
+        if self.dialect.varints() { 1 } else { 8 }
+    }
+
+    /// The fewest bytes a string length takes.
+    fn string_len_size(&self) -> u64 {
+        if self.dialect.varints() { 1 } else { 2 }
+    }
+
+    /// Reads an array length and checks it against the limits, assuming
+    /// each element takes at least `size` bytes.
+    fn len(&mut self, size: u64) -> NbtReaderResult<usize> {
+        let len = try!(self.i32());
+        if len < 0 {
+            return Err(NbtReaderError::NegativeLength(len));
+        }
+        let len = len as usize;
+        if len > self.limits.max_array_len {
+            return Err(NbtReaderError::ArrayTooLong(len));
+        }
+        try!(self.claim(len as u64 * size));
+        Ok(len)
+    }
+
+    fn string(&mut self) -> NbtReaderResult<String> {
+        let len = if self.dialect.varints() {
+            try!(self.varint(35)) as u32 as usize
+        } else {
+            try!(self.claim(2));
+            try!(read_ordered!(self, read_u16)) as usize
+        };
+        try!(self.claim(len as u64));
+        let bytes = try!(self.read_bytes(len));
+        mutf8::decode_vec(bytes).map_err(NbtReaderError::from)
+    }
+
+    fn array_u8(&mut self) -> NbtReaderResult<Vec<u8>> {
+        let len = try!(self.len(1));
+        self.read_bytes(len)
+    }
+
+    /// Reads `len` bytes which have already been claimed. Memory is only
+    /// allocated as the data arrives, so a bogus length can't exhaust it.
+    fn read_bytes(&mut self, len: usize) -> NbtReaderResult<Vec<u8>> {
+        let mut v = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED));
+        try!((&mut self.reader).take(len as u64).read_to_end(&mut v));
+        if v.len() < len {
+            return Err(NbtReaderError::UnexpectedEof);
+        }
+        Ok(v)
+    }
+
+    /// Reads an array of elements taking at least `size` bytes each. The
+    /// elements account for their own bytes, so only the length is
+    /// claimed up front to reject impossible lengths before allocating.
+    fn array<T, F>(&mut self, size: u64, mut read: F) -> NbtReaderResult<Vec<T>>
+        where F: FnMut(&mut NbtReader<R>) -> NbtReaderResult<T>
+    {
+        let len = try!(self.len(0));
+        if self.bytes.saturating_add(len as u64 * size) > self.limits.max_bytes {
+            return Err(NbtReaderError::SizeLimit(self.limits.max_bytes));
+        }
+        let mut v = Vec::with_capacity(cmp::min(len, MAX_PREALLOCATED));
+        for _ in 0..len {
+            v.push(try!(read(self)))
+        }
+        Ok(v)
+    }
+
+    /// Runs `f` one nesting level deeper.
+    fn nested<T, F>(&mut self, f: F) -> NbtReaderResult<T>
+        where F: FnOnce(&mut NbtReader<R>) -> NbtReaderResult<T>
+    {
+        if self.depth >= self.limits.max_depth {
+            return Err(NbtReaderError::DepthLimit(self.limits.max_depth));
+        }
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn compound(&mut self) -> NbtReaderResult<Compound> {
+        self.nested(|r| {
+            let mut map = Compound::new();
+            while let Some((v, name)) = try!(r.tag()) {
+                map.insert(name, v);
+            }
+            Ok(map)
+        })
+    }
+
+    fn list(&mut self) -> NbtReaderResult<List> {
+        self.nested(|r| r.list_elements())
+    }
+
+    fn list_elements(&mut self) -> NbtReaderResult<List> {
+        match try!(self.i8()) {
+            TAG_END => match try!(self.i32()) {
+                0 => Ok(List::End),
+                len => Err(NbtReaderError::InvalidEndList(len))
+            },
+            TAG_BYTE => self.array(1, |r| r.i8()).map(List::Byte),
+            TAG_SHORT => self.array(2, |r| r.i16()).map(List::Short),
+            TAG_INT => {
+                let int = self.int_size();
+                self.array(int, |r| r.i32()).map(List::Int)
+            }
+            TAG_LONG => {
+                let long = self.long_size();
+                self.array(long, |r| r.i64()).map(List::Long)
+            }
+            TAG_FLOAT => self.array(4, |r| r.f32()).map(List::Float),
+            TAG_DOUBLE => self.array(8, |r| r.f64()).map(List::Double),
+            TAG_BYTE_ARRAY => {
+                let int = self.int_size();
+                self.array(int, |r| r.array_u8()).map(List::ByteArray)
+            }
+            TAG_INT_ARRAY => {
+                let int = self.int_size();
+                self.array(int, |r| r.array(int, |r| r.i32())).map(List::IntArray)
+            }
+            TAG_LONG_ARRAY => {
+                let (int, long) = (self.int_size(), self.long_size());
+                self.array(int, |r| r.array(long, |r| r.i64())).map(List::LongArray)
+            }
+            TAG_STRING => {
+                let len = self.string_len_size();
+                self.array(len, |r| r.string()).map(List::String)
+            }
+            TAG_LIST => {
+                let int = self.int_size();
+                self.array(1 + int, |r| r.list()).map(List::List)
+            }
+            TAG_COMPOUND => self.array(1, |r| r.compound()).map(List::Compound),
+            tag_type => Err(NbtReaderError::UnknownTag(tag_type))
+        }
+    }
+
+    /// Reads the root tag of a stream, which is never a `TAG_END`. In
+    /// `Dialect::JavaNetwork` the root has no name and an empty one is
+    /// returned.
+    pub fn root(&mut self) -> NbtReaderResult<(Nbt, String)> {
+        let tag_type = try!(self.tag_type());
+        if tag_type == TAG_END {
+            return Err(NbtReaderError::EmptyRoot);
+        }
+        let name = if self.dialect.named_root() {
+            try!(self.string())
+        } else {
+            String::new()
+        };
+        self.payload(tag_type).map(|nbt| (nbt, name))
+    }
+
+    /// Reads a named tag, or `None` at the `TAG_END` closing a compound.
+    pub fn tag(&mut self) -> NbtReaderResult<Option<(Nbt, String)>> {
+        Ok(match try!(self.tag_type()) {
+            TAG_END => None,
+            tag_type => {
+                let name = try!(self.string());
+                Some((try!(self.payload(tag_type)), name))
+            }
+        })
+    }
+
+    fn tag_type(&mut self) -> NbtReaderResult<i8> {
+        match try!(self.i8()) {
+            tag_type if tag_type < TAG_END || tag_type > TAG_LONG_ARRAY => {
+                Err(NbtReaderError::UnknownTag(tag_type))
+            }
+            tag_type => Ok(tag_type)
+        }
+    }
+
+    fn payload(&mut self, tag_type: i8) -> NbtReaderResult<Nbt> {
+        match tag_type {
+            TAG_BYTE => self.i8().map(Nbt::Byte),
+            TAG_SHORT => self.i16().map(Nbt::Short),
+            TAG_INT => self.i32().map(Nbt::Int),
+            TAG_LONG => self.i64().map(Nbt::Long),
+            TAG_FLOAT => self.f32().map(Nbt::Float),
+            TAG_DOUBLE => self.f64().map(Nbt::Double),
+            TAG_BYTE_ARRAY => self.array_u8().map(Nbt::ByteArray),
+            TAG_INT_ARRAY => {
+                let int = self.int_size();
+                self.array(int, |r| r.i32()).map(Nbt::IntArray)
+            }
+            TAG_LONG_ARRAY => {
+                let long = self.long_size();
+                self.array(long, |r| r.i64()).map(Nbt::LongArray)
+            }
+            TAG_STRING => self.string().map(Nbt::String),
+            TAG_LIST => self.list().map(Nbt::List),
+            TAG_COMPOUND => self.compound().map(Nbt::Compound),
+            tag_type => Err(NbtReaderError::UnknownTag(tag_type))
+        }
+    }
+}
+
+pub type NbtWriterResult<T> = Result<T, NbtWriterError>;
+
+#[derive(Debug)]
+pub enum NbtWriterError {
+    Io(io::Error),
+    /// A string longer than the 65535 bytes its length prefix can hold.
+    StringTooLong(usize),
+    /// An array or list longer than its `i32` length prefix can hold.
+    ArrayTooLong(usize),
+}
+
+impl From<io::Error> for NbtWriterError {
+    fn from(err: io::Error) -> NbtWriterError { NbtWriterError::Io(err) }
+}
+
+pub struct NbtWriter<W> {
+    writer: W,
+    dialect: Dialect
+}
+
+macro_rules! write_ordered(
+    ($w:expr, $write:ident, $x:expr) => (
+        if $w.dialect.little_endian() {
+            $w.writer.$write::<LittleEndian>($x)
+        } else {
+            $w.writer.$write::<BigEndian>($x)
+        }
+    )
+);
+
+impl<W: Write> NbtWriter<W> {
+    pub fn new(writer: W) -> NbtWriter<W> {
+        NbtWriter::with_dialect(writer, Dialect::Java)
+    }
+
+    pub fn with_dialect(writer: W, dialect: Dialect) -> NbtWriter<W> {
+        NbtWriter {
+            writer: writer,
+            dialect: dialect
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Writes an unsigned LEB128 varint.
+    fn varint(&mut self, mut x: u64) -> NbtWriterResult<()> {
+        while x >= 0x80 {
+            try!(self.writer.write_u8(x as u8 | 0x80));
+            x >>= 7;
+        }
+        self.writer.write_u8(x as u8).map_err(NbtWriterError::from)
+    }
+
+    fn i8(&mut self, x: i8) -> NbtWriterResult<()> { self.writer.write_i8(x).map_err(NbtWriterError::from) }
+    fn i16(&mut self, x: i16) -> NbtWriterResult<()> { write_ordered!(self, write_i16, x).map_err(NbtWriterError::from) }
+    fn f32(&mut self, x: f32) -> NbtWriterResult<()> { write_ordered!(self, write_f32, x).map_err(NbtWriterError::from) }
+    fn f64(&mut self, x: f64) -> NbtWriterResult<()> { write_ordered!(self, write_f64, x).map_err(NbtWriterError::from) }
+
+    fn i32(&mut self, x: i32) -> NbtWriterResult<()> {
+        if self.dialect.varints() {
+            return self.varint(((x << 1) ^ (x >> 31)) as u32 as u64);
+        }
+        write_ordered!(self, write_i32, x).map_err(NbtWriterError::from)
+    }
+
+    fn i64(&mut self, x: i64) -> NbtWriterResult<()> {
+        if self.dialect.varints() {
+            return self.varint(((x << 1) ^ (x >> 63)) as u64);
+        }
+        write_ordered!(self, write_i64, x).map_err(NbtWriterError::from)
+    }
+
+    fn string(&mut self, s: &str) -> NbtWriterResult<()> {
+        let bytes = mutf8::encode(s);
+        if self.dialect.varints() {
+            if bytes.len() > i32::max_value() as usize {
+                return Err(NbtWriterError::StringTooLong(bytes.len()));
+            }
+            try!(self.varint(bytes.len() as u64));
+        } else {
+            if bytes.len() > u16::max_value() as usize {
+                return Err(NbtWriterError::StringTooLong(bytes.len()));
+            }
+            try!(write_ordered!(self, write_u16, bytes.len() as u16));
+        }
+        self.writer.write_all(&bytes).map_err(NbtWriterError::from)
+    }
+
+    fn len(&mut self, len: usize) -> NbtWriterResult<()> {
+        if len > i32::max_value() as usize {
+            return Err(NbtWriterError::ArrayTooLong(len));
+        }
+        self.i32(len as i32)
+    }
+
+    fn array_u8(&mut self, v: &[u8]) -> NbtWriterResult<()> {
+        try!(self.len(v.len()));
+        self.writer.write_all(v).map_err(NbtWriterError::from)
+    }
+
+    fn array<T, F>(&mut self, v: &[T], mut write: F) -> NbtWriterResult<()>
+        where F: FnMut(&mut NbtWriter<W>, &T) -> NbtWriterResult<()>
+    {
+        try!(self.len(v.len()));
+        for x in v.iter() {
+            try!(write(self, x))
+        }
+        Ok(())
+    }
+
+    fn compound(&mut self, c: &Compound) -> NbtWriterResult<()> {
+        for (name, v) in c.iter() {
+            try!(self.tag(v, name));
+        }
+        self.i8(TAG_END)
+    }
+
+    fn list(&mut self, l: &List) -> NbtWriterResult<()> {
+        try!(self.i8(l.tag_type()));
+        match *l {
+            List::End => self.i32(0),
+            List::Byte(ref v) => self.array(v, |w, &x| w.i8(x)),
+            List::Short(ref v) => self.array(v, |w, &x| w.i16(x)),
+            List::Int(ref v) => self.array(v, |w, &x| w.i32(x)),
+            List::Long(ref v) => self.array(v, |w, &x| w.i64(x)),
+            List::Float(ref v) => self.array(v, |w, &x| w.f32(x)),
+            List::Double(ref v) => self.array(v, |w, &x| w.f64(x)),
+            List::ByteArray(ref v) => self.array(v, |w, x| w.array_u8(x)),
+            List::IntArray(ref v) => self.array(v, |w, x| w.array(x, |w, &x| w.i32(x))),
+            List::LongArray(ref v) => self.array(v, |w, x| w.array(x, |w, &x| w.i64(x))),
+            List::String(ref v) => self.array(v, |w, x| w.string(x)),
+            List::List(ref v) => self.array(v, |w, x| w.list(x)),
+            List::Compound(ref v) => self.array(v, |w, x| w.compound(x))
+        }
+    }
+
+    /// Writes the root tag of a stream, leaving out the name in
+    /// `Dialect::JavaNetwork`. The counterpart of `NbtReader::root`.
+    pub fn root(&mut self, nbt: &Nbt, name: &str) -> NbtWriterResult<()> {
+        try!(self.i8(nbt.tag_type()));
+        if self.dialect.named_root() {
+            try!(self.string(name));
+        }
+        self.payload(nbt)
+    }
+
+    /// Writes a named tag, the counterpart of `NbtReader::tag`.
+    pub fn tag(&mut self, nbt: &Nbt, name: &str) -> NbtWriterResult<()> {
+        try!(self.i8(nbt.tag_type()));
+        try!(self.string(name));
+        self.payload(nbt)
+    }
+
+    fn payload(&mut self, nbt: &Nbt) -> NbtWriterResult<()> {
+        match *nbt {
+            Nbt::Byte(x) => self.i8(x),
+            Nbt::Short(x) => self.i16(x),
+            Nbt::Int(x) => self.i32(x),
+            Nbt::Long(x) => self.i64(x),
+            Nbt::Float(x) => self.f32(x),
+            Nbt::Double(x) => self.f64(x),
+            Nbt::ByteArray(ref v) => self.array_u8(v),
+            Nbt::IntArray(ref v) => self.array(v, |w, &x| w.i32(x)),
+            Nbt::LongArray(ref v) => self.array(v, |w, &x| w.i64(x)),
+            Nbt::String(ref s) => self.string(s),
+            Nbt::List(ref l) => self.list(l),
+            Nbt::Compound(ref c) => self.compound(c)
+        }
+    }
+}
+
+/// A structure to decode NBT to values in rust.
+pub struct Decoder {
+    stack: Vec<DecodeResult<Nbt>>
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum DecoderError {
+    ExpectedError(String, String),
+    MissingFieldError(String),
+    UnknownVariantError(String),
+    ApplicationError(String)
+}
+
+pub type DecodeResult<T> = Result<T, DecoderError>;
+
+impl Decoder {
+    /// Creates a new decoder instance for decoding the specified NBT value.
+    pub fn new(nbt: Nbt) -> Decoder {
+        Decoder {
+            stack: vec![Ok(nbt)]
+        }
+    }
+    fn pop(&mut self) -> DecodeResult<Nbt> {
+        self.stack.pop().unwrap()
+    }
+    fn push(&mut self, nbt: Nbt) {
+        self.stack.push(Ok(nbt))
+    }
+    fn push_all<T, F>(&mut self, list: Vec<T>, f: F) -> usize
+        where F: FnMut(T) -> Nbt
+    {
+        let len = list.len();
+        self.stack.extend(list.into_iter().rev().map(f).map(Ok::<Nbt, DecoderError>));
+        len
+    }
+}
+
+// impl Decodable for Nbt {
+//     fn decode<D: serialize::Decoder>(d: &mut D) -> Result<Self, serialize::Decoder::Error> {
+//         d.pop()
+//     }
+// }
+
+macro_rules! expect(
+    ($s:expr, $t:path) => ({
+        match $s.pop() {
+            Ok($t(v)) => Ok(v),
+            Ok(other) => {
+                Err(ExpectedError(stringify!($t).to_string(), other.to_string()))
+            }
+            Err(e) => Err(e)
+        }
+    });
+    ($s:expr, $t:ident as $to:ty) => (expect!($s, $t).map(|x| x as $to))
+);
+
+impl rustc_serialize::Decoder for Decoder {
+    type Error = DecoderError;
+
+    fn read_nil(&mut self) -> DecodeResult<()> {
+        Err(ExpectedError("()".to_string(), try!(self.pop()).to_string()))
+    }
+
+    fn read_u64(&mut self) -> DecodeResult<u64> { expect!(self, Nbt::Long).map(|x| x as u64) }
+    fn read_u32(&mut self) -> DecodeResult<u32> { expect!(self, Nbt::Int).map(|x| x as u32) }
+    fn read_u16(&mut self) -> DecodeResult<u16> { expect!(self, Nbt::Short).map(|x| x as u16) }
+    fn read_u8 (&mut self) -> DecodeResult<u8>  { expect!(self, Nbt::Byte).map(|x| x as u8) }
+
+    fn read_i64(&mut self) -> DecodeResult<i64> { expect!(self, Nbt::Long) }
+    fn read_i32(&mut self) -> DecodeResult<i32> { expect!(self, Nbt::Int) }
+    fn read_i16(&mut self) -> DecodeResult<i16> { expect!(self, Nbt::Short) }
+    fn read_i8 (&mut self) -> DecodeResult<i8>  { expect!(self, Nbt::Byte) }
+
+    fn read_isize(&mut self) -> DecodeResult<isize> {
+        match try!(self.pop()) {
+            Nbt::Byte(x) => Ok(x as isize),
+            Nbt::Short(x) => Ok(x as isize),
+            Nbt::Int(x) => Ok(x as isize),
+            Nbt::Long(x) => Ok(x as isize),
+            other => Err(ExpectedError("isize".to_string(), other.to_string()))
+        }
+    }
+    fn read_usize(&mut self) -> DecodeResult<usize> {
+        Ok(try!(self.read_isize()) as usize)
+    }
+
+    fn read_bool(&mut self) -> DecodeResult<bool> {
+        Ok(try!(self.read_u8()) != 0)
+    }
+
+    fn read_f64(&mut self) -> DecodeResult<f64> { expect!(self, Nbt::Double) }
+    fn read_f32(&mut self) -> DecodeResult<f32> { expect!(self, Nbt::Float) }
+
+    fn read_char(&mut self) -> DecodeResult<char> {
+        let s = try!(self.read_str());
+        {
+            let mut it = s.chars();
+            match (it.next(), it.next()) {
+                // exactly one character
+                (Some(c), None) => return Ok(c),
+                _ => ()
+            }
+        }
+        Err(ExpectedError("single character string".to_string(), s))
+    }
+
+    fn read_str(&mut self) -> DecodeResult<String> {
+        expect!(self, Nbt::String)
+    }
+
+    fn read_enum<T, F>(&mut self, _name: &str, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        f(self)
+    }
+
+    fn read_enum_variant<T, F>(&mut self, names: &[&str], mut f: F) -> DecodeResult<T>
+        where F: FnMut(&mut Self, usize) -> DecodeResult<T>
+    {
+        let name = match try!(self.pop()) {
+            Nbt::String(s) => s,
+            Nbt::Compound(mut o) => {
+                let name = match o.remove("variant") {
+                    Some(Nbt::String(s)) => s,
+                    Some(val) => return Err(ExpectedError("String".to_string(), val.to_string())),
+                    None => return Err(MissingFieldError("variant".to_string()))
+                };
+                match o.remove("fields") {
+                    Some(v) => {
+                        self.push(v);
+                        try!(self.read_seq(|_, _| Ok(())));
+                    },
+                    None => return Err(MissingFieldError("fields".to_string()))
+                }
+                name
+            }
+            nbt => {
+                return Err(ExpectedError("String or Compound".to_string(), nbt.to_string()))
+            }
+        };
+        let idx = match names.iter().position(|n| n == &name) {
+            Some(idx) => idx,
+            None => return Err(UnknownVariantError(name))
+        };
+        f(self, idx)
+    }
+
+    fn read_enum_variant_arg<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        f(self)
+    }
+
+    fn read_enum_struct_variant<T, F>(&mut self, names: &[&str], f: F) -> DecodeResult<T>
+        where F: FnMut(&mut Self, usize) -> DecodeResult<T>
+    {
+        self.read_enum_variant(names, f)
+    }
+
+
+    fn read_enum_struct_variant_field<T, F>(&mut self, _name: &str, idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        self.read_enum_variant_arg(idx, f)
+    }
+
+    fn read_struct<T, F>(&mut self, _name: &str, _len: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        let value = try!(f(self));
+        let _ = self.pop();
+        Ok(value)
+    }
+
+    fn read_struct_field<T, F>(&mut self, name: &str, _idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        let mut obj = try!(expect!(self, Nbt::Compound));
+
+        // A missing field is pushed as an error so `read_option` can turn
+        // it into `None`.
+        self.stack.push(obj.remove(name).ok_or(MissingFieldError(name.to_string())));
+        let value = try!(f(self));
+        self.push(Nbt::Compound(obj));
+        Ok(value)
+    }
+
+    fn read_tuple<T, F>(&mut self, tuple_len: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        self.read_seq(move |d, len| {
+            if len == tuple_len {
+                f(d)
+            } else {
+                Err(ExpectedError(format!("Tuple{}", tuple_len), format!("Tuple{}", len)))
+            }
+        })
+    }
+
+    fn read_tuple_arg<T, F>(&mut self, idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        self.read_seq_elt(idx, f)
+    }
+
+    fn read_tuple_struct<T, F>(&mut self, _name: &str, len: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        self.read_tuple(len, f)
+    }
+
+    fn read_tuple_struct_arg<T, F>(&mut self, idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        self.read_tuple_arg(idx, f)
+    }
+
+    fn read_option<T, F>(&mut self, mut f: F) -> DecodeResult<T>
+        where F: FnMut(&mut Self, bool) -> DecodeResult<T>
+    {
+        match self.pop() {
+            Ok(value) => { self.push(value); f(self, true) }
+            Err(MissingFieldError(_)) => f(self, false),
+            Err(e) => Err(e)
+        }
+    }
+
+    fn read_seq<T, F>(&mut self, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self, usize) -> DecodeResult<T>
+    {
+        let len = match try!(expect!(self, Nbt::List)) {
+            List::End => 0,
+            List::Byte(list) => self.push_all(list, Nbt::Byte),
+            List::Short(list) => self.push_all(list, Nbt::Short),
+            List::Int(list) => self.push_all(list, Nbt::Int),
+            List::Long(list) => self.push_all(list, Nbt::Long),
+            List::Float(list) => self.push_all(list, Nbt::Float),
+            List::Double(list) => self.push_all(list, Nbt::Double),
+            List::ByteArray(list) => self.push_all(list, Nbt::ByteArray),
+            List::IntArray(list) => self.push_all(list, Nbt::IntArray),
+            List::LongArray(list) => self.push_all(list, Nbt::LongArray),
+            List::String(list) => self.push_all(list, Nbt::String),
+            List::List(list) => self.push_all(list, Nbt::List),
+            List::Compound(list) => self.push_all(list, Nbt::Compound)
+        };
+        f(self, len)
+    }
+
+    fn read_seq_elt<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        f(self)
+    }
+
+    fn read_map<T, F>(&mut self, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self, usize) -> DecodeResult<T>
+    {
+        let obj = try!(expect!(self, Nbt::Compound));
+        let len = obj.len();
+        for (key, value) in obj.into_iter() {
+            self.push(value);
+            self.push(Nbt::String(key));
+        }
+        f(self, len)
+    }
+
+    fn read_map_elt_key<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        f(self)
+    }
+
+    fn read_map_elt_val<T, F>(&mut self, _idx: usize, f: F) -> DecodeResult<T>
+        where F: FnOnce(&mut Self) -> DecodeResult<T>
+    {
+        f(self)
+    }
+
+    fn error(&mut self, err: &str) -> DecoderError {
+        ApplicationError(err.to_string())
+    }
+}
+
+/// A structure to encode values in rust to NBT.
+///
+/// Structs and maps become compounds, sequences become lists and `None`
+/// values are left out entirely, mirroring what `Decoder` accepts.
+pub struct Encoder {
+    stack: Vec<Nbt>,
+    keys: Vec<String>,
+    /// Whether the value being encoded is that of a struct field, which
+    /// is left out of its compound when it is `None`.
+    in_field: bool
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum EncoderError {
+    MixedListError(String, String),
+    KeyError(String),
+    UnsupportedError(String),
+    EncoderApplicationError(String)
+}
+
+pub type EncodeResult<T> = Result<T, EncoderError>;
+
+impl Encoder {
+    pub fn new() -> Encoder {
+        Encoder {
+            stack: vec![],
+            keys: vec![],
+            in_field: false
+        }
+    }
+
+    /// Encodes a value into a single NBT value.
+    pub fn encode<T: rustc_serialize::Encodable>(value: &T) -> EncodeResult<Nbt> {
+        let mut encoder = Encoder::new();
+        try!(value.encode(&mut encoder));
+        match encoder.stack.pop() {
+            Some(nbt) => Ok(nbt),
+            None => Err(UnsupportedError("None".to_string()))
+        }
+    }
+
+    fn push(&mut self, nbt: Nbt) -> EncodeResult<()> {
+        self.stack.push(nbt);
+        Ok(())
+    }
+
+    /// Runs `f` and returns the values it left on the stack.
+    fn collect<F>(&mut self, f: F) -> EncodeResult<Vec<Nbt>>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        let base = self.stack.len();
+        try!(f(self));
+        Ok(self.stack.split_off(base))
+    }
+
+    fn list(values: Vec<Nbt>) -> EncodeResult<List> {
+        let tag_type = values.first().map_or(TAG_END, |first| first.tag_type());
+        List::from_values(values).map_err(|other| {
+            MixedListError(format!("tag type {}", tag_type), other.to_string())
+        })
+    }
+
+    /// Runs `f` and stores the value it produced, if any, under `name` in
+    /// the compound at the top of the stack.
+    fn insert<F>(&mut self, name: String, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        let value = try!(self.collect(f)).pop();
+        match (self.stack.last_mut(), value) {
+            (Some(&mut Nbt::Compound(ref mut c)), Some(value)) => {
+                c.insert(name, value);
+            }
+            (_, None) => {}
+            (_, Some(value)) => {
+                return Err(UnsupportedError(format!("{} outside of a compound", value)));
+            }
+        }
+        Ok(())
+    }
+
+    fn variant<F>(&mut self, name: &str, len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        if len == 0 {
+            return self.push(Nbt::String(name.to_string()));
+        }
+        self.in_field = false;
+        let fields = try!(self.collect(f));
+        let mut c = Compound::new();
+        c.insert("variant".to_string(), Nbt::String(name.to_string()));
+        c.insert("fields".to_string(), Nbt::List(try!(Encoder::list(fields))));
+        self.push(Nbt::Compound(c))
+    }
+}
+
+impl rustc_serialize::Encoder for Encoder {
+    type Error = EncoderError;
+
+    fn emit_nil(&mut self) -> EncodeResult<()> {
+        Err(UnsupportedError("()".to_string()))
+    }
+
+    fn emit_usize(&mut self, v: usize) -> EncodeResult<()> { self.push(Nbt::Long(v as i64)) }
+    fn emit_u64(&mut self, v: u64) -> EncodeResult<()> { self.push(Nbt::Long(v as i64)) }
+    fn emit_u32(&mut self, v: u32) -> EncodeResult<()> { self.push(Nbt::Int(v as i32)) }
+    fn emit_u16(&mut self, v: u16) -> EncodeResult<()> { self.push(Nbt::Short(v as i16)) }
+    fn emit_u8 (&mut self, v: u8)  -> EncodeResult<()> { self.push(Nbt::Byte(v as i8)) }
+
+    fn emit_isize(&mut self, v: isize) -> EncodeResult<()> { self.push(Nbt::Long(v as i64)) }
+    fn emit_i64(&mut self, v: i64) -> EncodeResult<()> { self.push(Nbt::Long(v)) }
+    fn emit_i32(&mut self, v: i32) -> EncodeResult<()> { self.push(Nbt::Int(v)) }
+    fn emit_i16(&mut self, v: i16) -> EncodeResult<()> { self.push(Nbt::Short(v)) }
+    fn emit_i8 (&mut self, v: i8)  -> EncodeResult<()> { self.push(Nbt::Byte(v)) }
+
+    fn emit_bool(&mut self, v: bool) -> EncodeResult<()> { self.push(Nbt::Byte(v as i8)) }
+
+    fn emit_f64(&mut self, v: f64) -> EncodeResult<()> { self.push(Nbt::Double(v)) }
+    fn emit_f32(&mut self, v: f32) -> EncodeResult<()> { self.push(Nbt::Float(v)) }
+
+    fn emit_char(&mut self, v: char) -> EncodeResult<()> {
+        self.push(Nbt::String(v.to_string()))
+    }
+
+    fn emit_str(&mut self, v: &str) -> EncodeResult<()> {
+        self.push(Nbt::String(v.to_string()))
+    }
+
+    fn emit_enum<F>(&mut self, _name: &str, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        f(self)
+    }
+
+    fn emit_enum_variant<F>(&mut self, v_name: &str, _v_id: usize, len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.variant(v_name, len, f)
+    }
+
+    fn emit_enum_variant_arg<F>(&mut self, _a_idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        f(self)
+    }
+
+    fn emit_enum_struct_variant<F>(&mut self, v_name: &str, _v_id: usize, len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.variant(v_name, len, f)
+    }
+
+    fn emit_enum_struct_variant_field<F>(&mut self, _f_name: &str, f_idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.emit_enum_variant_arg(f_idx, f)
+    }
+
+    fn emit_struct<F>(&mut self, _name: &str, _len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.in_field = false;
+        self.stack.push(Nbt::Compound(Compound::new()));
+        f(self)
+    }
+
+    fn emit_struct_field<F>(&mut self, f_name: &str, _f_idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        let in_field = mem::replace(&mut self.in_field, true);
+        let result = self.insert(f_name.to_string(), f);
+        self.in_field = in_field;
+        result
+    }
+
+    fn emit_tuple<F>(&mut self, len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.emit_seq(len, f)
+    }
+
+    fn emit_tuple_arg<F>(&mut self, idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.emit_seq_elt(idx, f)
+    }
+
+    fn emit_tuple_struct<F>(&mut self, _name: &str, len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.emit_tuple(len, f)
+    }
+
+    fn emit_tuple_struct_arg<F>(&mut self, f_idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.emit_tuple_arg(f_idx, f)
+    }
+
+    fn emit_option<F>(&mut self, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        f(self)
+    }
+
+    /// A struct field that is `None` is left out of its compound, but
+    /// there is no way to store `None` anywhere else.
+    fn emit_option_none(&mut self) -> EncodeResult<()> {
+        if self.in_field {
+            Ok(())
+        } else {
+            Err(UnsupportedError("None".to_string()))
+        }
+    }
+
+    fn emit_option_some<F>(&mut self, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        f(self)
+    }
+
+    fn emit_seq<F>(&mut self, _len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.in_field = false;
+        let values = try!(self.collect(f));
+        let list = try!(Encoder::list(values));
+        self.push(Nbt::List(list))
+    }
+
+    fn emit_seq_elt<F>(&mut self, _idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        f(self)
+    }
+
+    fn emit_map<F>(&mut self, _len: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        self.in_field = false;
+        self.stack.push(Nbt::Compound(Compound::new()));
+        f(self)
+    }
+
+    fn emit_map_elt_key<F>(&mut self, _idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        match try!(self.collect(f)).pop() {
+            Some(Nbt::String(key)) => {
+                self.keys.push(key);
+                Ok(())
+            }
+            Some(other) => Err(KeyError(other.to_string())),
+            None => Err(KeyError("None".to_string()))
+        }
+    }
+
+    fn emit_map_elt_val<F>(&mut self, _idx: usize, f: F) -> EncodeResult<()>
+        where F: FnOnce(&mut Self) -> EncodeResult<()>
+    {
+        match self.keys.pop() {
+            Some(key) => self.insert(key, f),
+            None => Err(KeyError("map value without a key".to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::io::Read;
+
+    use flate2::read::{ GzDecoder, ZlibDecoder };
+    use rustc_serialize::{ Encodable, Encoder as RustcEncoder };
+
+    use super::*;
+
+    // Built by `fixtures/make_fixtures.py` in the layout of each version.
+    const LEVEL_DAT: &'static [u8] = include_bytes!("../../../fixtures/level.dat");
+    const CHUNK_1_12: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_12.zlib");
+    const CHUNK_1_13: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_13.zlib");
+    const CHUNK_1_18: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_18.zlib");
+
+    fn gunzip(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        GzDecoder::new(data).unwrap().read_to_end(&mut out).unwrap();
+        out
+    }
+
+    fn inflate(data: &[u8]) -> Vec<u8> {
+        let mut out = vec![];
+        ZlibDecoder::new(data).read_to_end(&mut out).unwrap();
+        out
+    }
+
+    /// Reads `data` and writes it back, which must give the same bytes.
+    fn assert_round_trip(data: &[u8]) -> Nbt {
+        let (nbt, name) = NbtReader::new(data).root().unwrap();
+        let mut writer = NbtWriter::new(Vec::new());
+        writer.root(&nbt, &name).unwrap();
+        assert!(writer.into_inner() == data, "written back differently");
+        nbt
+    }
+
+    #[test]
+    fn level_dat_round_trips() {
+        let data = gunzip(LEVEL_DAT);
+        let nbt = assert_round_trip(&data);
+        let player = nbt["Data"]["Player"].as_compound().unwrap();
+        assert_eq!(player["EnderItems"], Nbt::List(List::End));
+        assert_eq!(player["Inventory"].as_compound_list().unwrap().len(), 2);
+        assert_eq!(nbt["Data"]["LevelName"], Nbt::String("Fixture é\u{0}".to_string()));
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        for chunk in &[CHUNK_1_12, CHUNK_1_13, CHUNK_1_18] {
+            assert_round_trip(&inflate(chunk));
+        }
+    }
+
+    #[test]
+    fn gzip_and_zlib_round_trip() {
+        let data = gunzip(LEVEL_DAT);
+        let nbt = Nbt::from_gzip(LEVEL_DAT).unwrap();
+        let gzip = nbt.to_gzip().unwrap();
+        assert!(gunzip(&gzip) == data);
+        assert_eq!(Nbt::from_gzip(&gzip).unwrap(), nbt);
+
+        let data = inflate(CHUNK_1_18);
+        let nbt = Nbt::from_zlib(CHUNK_1_18).unwrap();
+        let zlib = nbt.to_zlib().unwrap();
+        assert!(inflate(&zlib) == data);
+        assert_eq!(Nbt::from_zlib(&zlib).unwrap(), nbt);
+    }
+
+    #[test]
+    fn empty_lists_keep_their_type() {
+        // {"": {untyped: [], compounds: [] of TAG_COMPOUND, ints: [] of TAG_INT}}
+        let data = b"\x0a\x00\x00\
+                     \x09\x00\x07untyped\x00\x00\x00\x00\x00\
+                     \x09\x00\x09compounds\x0a\x00\x00\x00\x00\
+                     \x09\x00\x04ints\x03\x00\x00\x00\x00\
+                     \x00";
+        let nbt = assert_round_trip(data);
+        assert_eq!(nbt["untyped"], Nbt::List(List::End));
+        assert_eq!(nbt["compounds"], Nbt::List(List::Compound(vec![])));
+        assert_eq!(nbt["ints"], Nbt::List(List::Int(vec![])));
+        assert_eq!(List::from_values(vec![]), Ok(List::End));
+    }
+
+    macro_rules! assert_error(
+        ($result:expr, $error:pat) => (
+            match $result {
+                Err($error) => {}
+                other => panic!("expected {}, got {:?}", stringify!($error), other)
+            }
+        )
+    );
+
+    fn read(data: &[u8], limits: NbtLimits) -> NbtReaderResult<(Nbt, String)> {
+        NbtReader::with_limits(data, limits).root()
+    }
+
+    #[test]
+    fn negative_lengths_are_rejected() {
+        let limits = NbtLimits::unlimited();
+        // A list of ints, a byte array, an int array and a long array.
+        assert_error!(read(b"\x09\x00\x00\x03\xff\xff\xff\xff", limits),
+                      NbtReaderError::NegativeLength(-1));
+        assert_error!(read(b"\x07\x00\x00\xff\xff\xff\xfe", limits),
+                      NbtReaderError::NegativeLength(-2));
+        assert_error!(read(b"\x0b\x00\x00\x80\x00\x00\x00", limits),
+                      NbtReaderError::NegativeLength(-2147483648));
+        assert_error!(read(b"\x0c\x00\x00\xff\xff\xff\xff", limits),
+                      NbtReaderError::NegativeLength(-1));
+        // String lengths are unsigned, so this one is 65535 bytes long and
+        // the input ends before them.
+        assert_error!(read(b"\x08\x00\x00\xff\xffab", limits), NbtReaderError::UnexpectedEof);
+    }
+
+    #[test]
+    fn huge_lengths_are_rejected_before_allocating() {
+        assert_error!(read(b"\x07\x00\x00\x7f\xff\xff\xff", NbtLimits::default()),
+                      NbtReaderError::ArrayTooLong(0x7fffffff));
+        // Short enough for the array limit, but not for the size limit.
+        let limits = NbtLimits { max_array_len: usize::max_value(), ..NbtLimits::default() };
+        assert_error!(read(b"\x0c\x00\x00\x7f\xff\xff\xff", limits),
+                      NbtReaderError::SizeLimit(_));
+
+        // Without limits the data runs out first. Allocating for the length
+        // up front would ask for gigabytes and abort the test.
+        let limits = NbtLimits::unlimited();
+        assert_error!(read(b"\x07\x00\x00\x7f\xff\xff\xff\x01\x02", limits),
+                      NbtReaderError::UnexpectedEof);
+        assert_error!(read(b"\x0c\x00\x00\x7f\xff\xff\xff\x00\x00\x00\x00\x00\x00\x00\x01",
+                           limits),
+                      NbtReaderError::UnexpectedEof);
+        assert_error!(read(b"\x09\x00\x00\x0a\x7f\xff\xff\xff\x00\x00", limits),
+                      NbtReaderError::UnexpectedEof);
+    }
+
+    /// A root list with `depth` levels of lists in it.
+    fn nested_lists(depth: usize) -> Vec<u8> {
+        let mut data = b"\x09\x00\x00".to_vec();
+        for _ in 0..depth {
+            data.extend_from_slice(b"\x09\x00\x00\x00\x01");
+        }
+        data.extend_from_slice(b"\x00\x00\x00\x00\x00");
+        data
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let limits = NbtLimits { max_depth: 8, ..NbtLimits::default() };
+        assert!(read(&nested_lists(7), limits).is_ok());
+        assert_error!(read(&nested_lists(8), limits), NbtReaderError::DepthLimit(8));
+        // Far deeper than the default, which must fail before the stack
+        // runs out.
+        assert_error!(read(&nested_lists(100000), NbtLimits::default()),
+                      NbtReaderError::DepthLimit(512));
+    }
+
+    #[test]
+    fn input_size_is_limited() {
+        let limits = NbtLimits { max_bytes: 100, ..NbtLimits::default() };
+        let mut array = b"\x07\x00\x00\x00\x00\x00\xc8".to_vec();
+        array.extend_from_slice(&[0; 200]);
+        assert_error!(read(&array, limits), NbtReaderError::SizeLimit(100));
+        let mut string = b"\x08\x00\x00\x00\xc8".to_vec();
+        string.extend_from_slice(&[b'a'; 200]);
+        assert_error!(read(&string, limits), NbtReaderError::SizeLimit(100));
+        // Many small tags add up as well.
+        let mut list = b"\x09\x00\x00\x01\x00\x00\x00\xc8".to_vec();
+        list.extend_from_slice(&[0; 200]);
+        assert_error!(read(&list, limits), NbtReaderError::SizeLimit(100));
+        assert!(read(&array, NbtLimits::default()).is_ok());
+    }
+
+    struct Named {
+        name: String,
+        nickname: Option<String>,
+        scores: Vec<Option<i32>>
+    }
+
+    impl Encodable for Named {
+        fn encode<S: RustcEncoder>(&self, s: &mut S) -> Result<(), S::Error> {
+            s.emit_struct("Named", 3, |s| {
+                try!(s.emit_struct_field("name", 0, |s| self.name.encode(s)));
+                try!(s.emit_struct_field("nickname", 1, |s| self.nickname.encode(s)));
+                s.emit_struct_field("scores", 2, |s| self.scores.encode(s))
+            })
+        }
+    }
+
+    #[test]
+    fn none_is_only_left_out_of_structs() {
+        let named = Named { name: "Steve".to_string(), nickname: None, scores: vec![Some(3)] };
+        let nbt = Encoder::encode(&named).unwrap();
+        assert!(!nbt.as_compound().unwrap().contains_key("nickname"));
+        assert_eq!(nbt["scores"], Nbt::List(List::Int(vec![3])));
+
+        let none = Err(UnsupportedError("None".to_string()));
+        let named = Named { name: "Alex".to_string(), nickname: None, scores: vec![None] };
+        assert_eq!(Encoder::encode(&named), none);
+        assert_eq!(Encoder::encode(&vec![Some(1), None]), none);
+        let mut map = HashMap::new();
+        map.insert("a".to_string(), None::<i32>);
+        assert_eq!(Encoder::encode(&map), none);
+    }
+
+    struct Unkeyed;
+
+    impl Encodable for Unkeyed {
+        fn encode<S: RustcEncoder>(&self, s: &mut S) -> Result<(), S::Error> {
+            s.emit_map(1, |s| s.emit_map_elt_val(0, |s| 1i32.encode(s)))
+        }
+    }
+
+    #[test]
+    fn malformed_encodables_are_errors() {
+        assert!(Encoder::encode(&Unkeyed).is_err());
+    }
+}
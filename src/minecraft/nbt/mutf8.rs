@@ -0,0 +1,170 @@
+//! Java's "modified UTF-8", which NBT strings are stored in.
+//!
+//! It differs from UTF-8 in two ways: NUL is written as the two bytes
+//! `C0 80`, and characters outside the Basic Multilingual Plane are written
+//! as a UTF-16 surrogate pair, each half taking three bytes. Most strings
+//! use neither, and are passed through without copying.
+//!
+//! Decoding also accepts plain UTF-8 four-byte sequences, which some
+//! third-party tools write. Like Java, it rejects overlong sequences other
+//! than the one for NUL.
+
+use std::borrow::Cow;
+use std::char;
+use std::str;
+
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Mutf8Error {
+    valid_up_to: usize
+}
+
+impl Mutf8Error {
+    /// The offset of the first byte that couldn't be decoded.
+    pub fn valid_up_to(&self) -> usize {
+        self.valid_up_to
+    }
+}
+
+pub fn decode(bytes: &[u8]) -> Result<Cow<str>, Mutf8Error> {
+    match str::from_utf8(bytes) {
+        Ok(s) => Ok(Cow::Borrowed(s)),
+        Err(e) => decode_from(bytes, e.valid_up_to()).map(Cow::Owned)
+    }
+}
+
+/// Decodes an owned buffer, reusing it when it is valid UTF-8.
+pub fn decode_vec(bytes: Vec<u8>) -> Result<String, Mutf8Error> {
+    match String::from_utf8(bytes) {
+        Ok(s) => Ok(s),
+        Err(e) => {
+            let valid_up_to = e.utf8_error().valid_up_to();
+            decode_from(&e.into_bytes(), valid_up_to)
+        }
+    }
+}
+
+pub fn encode(s: &str) -> Cow<[u8]> {
+    if !s.chars().any(|c| c == '\0' || c > '\u{ffff}') {
+        return Cow::Borrowed(s.as_bytes());
+    }
+    let mut out = Vec::with_capacity(s.len() + 2);
+    for (i, c) in s.char_indices() {
+        match c as u32 {
+            0 => out.extend_from_slice(&[0xc0, 0x80]),
+            c if c > 0xffff => {
+                let c = c - 0x10000;
+                push_unit(&mut out, 0xd800 + (c >> 10));
+                push_unit(&mut out, 0xdc00 + (c & 0x3ff));
+            }
+            _ => out.extend_from_slice(&s.as_bytes()[i..i + c.len_utf8()])
+        }
+    }
+    Cow::Owned(out)
+}
+
+/// Writes a UTF-16 code unit as a three-byte sequence.
+fn push_unit(out: &mut Vec<u8>, unit: u32) {
+    out.push(0xe0 | (unit >> 12) as u8);
+    out.push(0x80 | ((unit >> 6) & 0x3f) as u8);
+    out.push(0x80 | (unit & 0x3f) as u8);
+}
+
+/// Decodes `bytes`, the first `valid` of which are known to be UTF-8.
+fn decode_from(bytes: &[u8], valid: usize) -> Result<String, Mutf8Error> {
+    let mut out = String::with_capacity(bytes.len());
+    out.push_str(unsafe { str::from_utf8_unchecked(&bytes[..valid]) });
+    let mut i = valid;
+    while i < bytes.len() {
+        let err = Mutf8Error { valid_up_to: i };
+        let (mut c, mut n) = try!(sequence(&bytes[i..]).ok_or(err));
+        if n == 3 && c >= 0xd800 && c < 0xdc00 {
+            match sequence(&bytes[i + 3..]) {
+                Some((low, 3)) if low >= 0xdc00 && low < 0xe000 => {
+                    c = 0x10000 + ((c - 0xd800) << 10) + (low - 0xdc00);
+                    n = 6;
+                }
+                _ => return Err(err)
+            }
+        }
+        // Lone low surrogates are rejected here.
+        out.push(try!(char::from_u32(c).ok_or(err)));
+        i += n;
+    }
+    Ok(out)
+}
+
+/// Decodes the sequence at the start of `bytes`, returning its code unit
+/// (or, for a four-byte sequence, code point) and length.
+fn sequence(bytes: &[u8]) -> Option<(u32, usize)> {
+    match raw_sequence(bytes) {
+        // `C0 80` is the only way to write NUL.
+        Some((0, 2)) => Some((0, 2)),
+        Some((c, 2)) if c < 0x80 => None,
+        Some((c, 3)) if c < 0x800 => None,
+        Some((c, 4)) if c < 0x10000 => None,
+        result => result
+    }
+}
+
+fn raw_sequence(bytes: &[u8]) -> Option<(u32, usize)> {
+    let cont = |i: usize| match bytes.get(i) {
+        Some(&b) if b & 0xc0 == 0x80 => Some((b & 0x3f) as u32),
+        _ => None
+    };
+    let b = match bytes.first() {
+        Some(&b) => b as u32,
+        None => return None
+    };
+    match b {
+        0x00...0x7f => Some((b, 1)),
+        0xc0...0xdf => cont(1).map(|c1| (((b & 0x1f) << 6) | c1, 2)),
+        0xe0...0xef => match (cont(1), cont(2)) {
+            (Some(c1), Some(c2)) => Some((((b & 0x0f) << 12) | (c1 << 6) | c2, 3)),
+            _ => None
+        },
+        0xf0...0xf4 => match (cont(1), cont(2), cont(3)) {
+            (Some(c1), Some(c2), Some(c3)) => {
+                Some((((b & 0x07) << 18) | (c1 << 12) | (c2 << 6) | c3, 4))
+            }
+            _ => None
+        },
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ decode, encode };
+
+    #[test]
+    fn round_trip() {
+        for s in &["", "plain ascii", "caf\u{e9}", "nul\0in the middle", "\u{1f600} astral"] {
+            assert_eq!(decode(&encode(s)).unwrap(), *s);
+        }
+        assert_eq!(&encode("a\0\u{1f600}")[..],
+                   &[0x61, 0xc0, 0x80, 0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80][..]);
+        // Plain UTF-8 for an astral character.
+        assert_eq!(decode(&[0xf0, 0x9f, 0x98, 0x80]).unwrap(), "\u{1f600}");
+    }
+
+    #[test]
+    fn invalid_sequences_are_rejected() {
+        let invalid: &[&[u8]] = &[
+            // Overlong: '/' in two and three bytes, NUL in three and four.
+            &[0x61, 0xc0, 0xaf],
+            &[0x61, 0xe0, 0x80, 0xaf],
+            &[0x61, 0xe0, 0x80, 0x80],
+            &[0x61, 0xf0, 0x80, 0x80, 0x80],
+            // 'é' in three bytes, U+FFFF in four.
+            &[0x61, 0xe0, 0x83, 0xa9],
+            &[0x61, 0xf0, 0x8f, 0xbf, 0xbf],
+            // Lone surrogates and a truncated sequence.
+            &[0x61, 0xed, 0xa0, 0xbd],
+            &[0x61, 0xed, 0xb8, 0x80],
+            &[0x61, 0xe2, 0x82]
+        ];
+        for bytes in invalid {
+            assert_eq!(decode(bytes).unwrap_err().valid_up_to(), 1, "{:?}", bytes);
+        }
+    }
+}
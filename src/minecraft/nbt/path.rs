@@ -0,0 +1,328 @@
+//! Path queries into NBT values.
+//!
+//! A path is a sequence of compound keys separated by dots, each optionally
+//! followed by list indices, e.g. `Data.Player.Inventory[0].id`. Keys that
+//! contain `.`, `[` or `]` can be quoted: `Data."odd.key"`. The wildcards
+//! `*` (any key) and `[*]` (any index) are accepted by `query_all`.
+//!
+//! Errors carry the path up to and including the segment that failed.
+
+use minecraft::nbt::{ Nbt, NbtRef };
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum QueryError {
+    /// The path itself is malformed at the given byte offset.
+    Syntax(usize),
+    MissingKey(String),
+    IndexOutOfBounds(String),
+    NotACompound(String),
+    NotAList(String),
+    /// `query` was given a path with a wildcard, which can match more
+    /// than one value.
+    Wildcard(String)
+}
+
+pub type QueryResult<T> = Result<T, QueryError>;
+
+impl Nbt {
+    /// Looks up the single value at `path`.
+    pub fn query(&self, path: &str) -> QueryResult<NbtRef> {
+        self.as_nbt_ref().query(path)
+    }
+
+    /// Looks up every value matching `path`, in order. Values under a
+    /// wildcard which lack the rest of the path are skipped.
+    pub fn query_all(&self, path: &str) -> QueryResult<Vec<NbtRef>> {
+        self.as_nbt_ref().query_all(path)
+    }
+}
+
+impl<'a> NbtRef<'a> {
+    pub fn query(self, path: &str) -> QueryResult<NbtRef<'a>> {
+        let steps = try!(parse(path));
+        let mut value = self;
+        for step in steps.iter() {
+            let prefix = || path[..step.end].to_string();
+            value = match step.segment {
+                Segment::Key(ref key) => try!(key_of(value, key, &prefix)),
+                Segment::Index(i) => try!(index_of(value, i, &prefix)),
+                Segment::AnyKey | Segment::AnyIndex => {
+                    return Err(QueryError::Wildcard(prefix()));
+                }
+            };
+        }
+        Ok(value)
+    }
+
+    pub fn query_all(self, path: &str) -> QueryResult<Vec<NbtRef<'a>>> {
+        let steps = try!(parse(path));
+        let mut out = vec![];
+        try!(walk(self, path, &steps, false, &mut out));
+        Ok(out)
+    }
+}
+
+enum Segment {
+    Key(String),
+    AnyKey,
+    Index(usize),
+    AnyIndex
+}
+
+struct Step {
+    segment: Segment,
+    /// Byte offset in the path just past this segment.
+    end: usize
+}
+
+fn key_of<'a, F>(value: NbtRef<'a>, key: &str, prefix: &F) -> QueryResult<NbtRef<'a>>
+    where F: Fn() -> String
+{
+    match value {
+        NbtRef::Compound(c) => match c.get(key) {
+            Some(v) => Ok(v.as_nbt_ref()),
+            None => Err(QueryError::MissingKey(prefix()))
+        },
+        _ => Err(QueryError::NotACompound(prefix()))
+    }
+}
+
+fn index_of<'a, F>(value: NbtRef<'a>, i: usize, prefix: &F) -> QueryResult<NbtRef<'a>>
+    where F: Fn() -> String
+{
+    let item = match value {
+        NbtRef::List(l) => l.get(i),
+        NbtRef::ByteArray(a) => a.get(i).map(|&x| NbtRef::Byte(x as i8)),
+        NbtRef::IntArray(a) => a.get(i).map(|&x| NbtRef::Int(x)),
+        NbtRef::LongArray(a) => a.get(i).map(|&x| NbtRef::Long(x)),
+        _ => return Err(QueryError::NotAList(prefix()))
+    };
+    item.ok_or_else(|| QueryError::IndexOutOfBounds(prefix()))
+}
+
+fn len_of(value: NbtRef) -> Option<usize> {
+    match value {
+        NbtRef::List(l) => Some(l.len()),
+        NbtRef::ByteArray(a) => Some(a.len()),
+        NbtRef::IntArray(a) => Some(a.len()),
+        NbtRef::LongArray(a) => Some(a.len()),
+        _ => None
+    }
+}
+
+/// Follows `steps` from `value`, collecting matches. Once a wildcard has
+/// been expanded, branches that don't match are dropped instead of
+/// failing the whole query.
+fn walk<'a>(value: NbtRef<'a>, path: &str, steps: &[Step], wild: bool,
+            out: &mut Vec<NbtRef<'a>>) -> QueryResult<()> {
+    let (step, rest) = match steps.split_first() {
+        Some(x) => x,
+        None => {
+            out.push(value);
+            return Ok(());
+        }
+    };
+    let prefix = || path[..step.end].to_string();
+    let next = match step.segment {
+        Segment::Key(ref key) => key_of(value, key, &prefix),
+        Segment::Index(i) => index_of(value, i, &prefix),
+        Segment::AnyKey => {
+            return match value {
+                NbtRef::Compound(c) => {
+                    for v in c.values() {
+                        try!(walk(v.as_nbt_ref(), path, rest, true, out));
+                    }
+                    Ok(())
+                }
+                _ if wild => Ok(()),
+                _ => Err(QueryError::NotACompound(prefix()))
+            };
+        }
+        Segment::AnyIndex => {
+            return match len_of(value) {
+                Some(len) => {
+                    for i in 0..len {
+                        let v = try!(index_of(value, i, &prefix));
+                        try!(walk(v, path, rest, true, out));
+                    }
+                    Ok(())
+                }
+                None if wild => Ok(()),
+                None => Err(QueryError::NotAList(prefix()))
+            };
+        }
+    };
+    match next {
+        Ok(v) => walk(v, path, rest, wild, out),
+        Err(_) if wild => Ok(()),
+        Err(e) => Err(e)
+    }
+}
+
+fn parse(path: &str) -> QueryResult<Vec<Step>> {
+    let mut parser = Parser { src: path, pos: 0 };
+    let mut steps = vec![];
+    while let Some(c) = parser.peek() {
+        let segment = match c {
+            '[' => try!(parser.index()),
+            '.' if !steps.is_empty() => {
+                parser.pos += 1;
+                try!(parser.key())
+            }
+            _ if steps.is_empty() => try!(parser.key()),
+            _ => return Err(QueryError::Syntax(parser.pos))
+        };
+        steps.push(Step { segment: segment, end: parser.pos });
+    }
+    Ok(steps)
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn key(&mut self) -> QueryResult<Segment> {
+        match self.peek() {
+            Some('"') => {
+                self.pos += 1;
+                let mut key = String::new();
+                loop {
+                    match self.peek() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            self.pos += 1;
+                            match self.peek() {
+                                Some(c @ '"') | Some(c @ '\\') => key.push(c),
+                                _ => return Err(QueryError::Syntax(self.pos))
+                            }
+                        }
+                        Some(c) => key.push(c),
+                        None => return Err(QueryError::Syntax(self.pos))
+                    }
+                    self.pos += self.peek().unwrap().len_utf8();
+                }
+                self.pos += 1;
+                Ok(Segment::Key(key))
+            }
+            _ => {
+                let start = self.pos;
+                while let Some(c) = self.peek() {
+                    match c {
+                        '.' | '[' | ']' | '"' => break,
+                        _ => self.pos += c.len_utf8()
+                    }
+                }
+                match &self.src[start..self.pos] {
+                    "" => Err(QueryError::Syntax(start)),
+                    "*" => Ok(Segment::AnyKey),
+                    key => Ok(Segment::Key(key.to_string()))
+                }
+            }
+        }
+    }
+
+    fn index(&mut self) -> QueryResult<Segment> {
+        self.pos += 1;
+        let start = self.pos;
+        let end = match self.src[start..].find(']') {
+            Some(n) => start + n,
+            None => return Err(QueryError::Syntax(self.src.len()))
+        };
+        self.pos = end + 1;
+        match &self.src[start..end] {
+            "*" => Ok(Segment::AnyIndex),
+            digits => digits.parse()
+                            .map(Segment::Index)
+                            .map_err(|_| QueryError::Syntax(start))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minecraft::nbt::{ Nbt, NbtRef };
+    use super::QueryError::*;
+
+    fn level() -> Nbt {
+        r#"{
+            Data: {
+                Player: {
+                    Pos: [1.0d, 2.0d, 3.0d],
+                    Inventory: [{id: "stone", Count: 1b}, {id: "dirt", tag: {x: 5}}]
+                },
+                "odd.[key]": [I; 4, 5],
+                "say \"hi\" \\ there": [B; 6b],
+                Sections: [[0L], [], [1L, 2L]]
+            }
+        }"#.parse().unwrap()
+    }
+
+    fn error(path: &str) -> super::QueryError {
+        level().query(path).unwrap_err()
+    }
+
+    #[test]
+    fn keys_and_indices() {
+        let nbt = level();
+        assert_eq!(nbt.query("").unwrap().to_nbt(), nbt);
+        assert_eq!(nbt.query("Data.Player.Inventory[0].id").unwrap(), NbtRef::String("stone"));
+        assert_eq!(nbt.query("Data.Player.Inventory[1].tag.x").unwrap(), NbtRef::Int(5));
+        assert_eq!(nbt.query("Data.Player.Pos[2]").unwrap(), NbtRef::Double(3.0));
+        assert_eq!(nbt.query("Data.Sections[2][1]").unwrap(), NbtRef::Long(2));
+        assert_eq!(nbt.query(r#"Data."odd.[key]"[1]"#).unwrap(), NbtRef::Int(5));
+        assert_eq!(nbt.query(r#"Data."say \"hi\" \\ there"[0]"#).unwrap(), NbtRef::Byte(6));
+    }
+
+    #[test]
+    fn wildcards() {
+        let nbt = level();
+        let ids: Vec<_> = nbt.query_all("Data.Player.Inventory[*].id").unwrap()
+                             .into_iter().map(|id| id.as_string().unwrap()).collect();
+        assert_eq!(ids, ["stone", "dirt"]);
+        // Branches without the rest of the path are left out.
+        assert_eq!(nbt.query_all("Data.Player.Inventory[*].tag.x").unwrap(), [NbtRef::Int(5)]);
+        assert_eq!(nbt.query_all("Data.Sections[*][0]").unwrap(),
+                   [NbtRef::Long(0), NbtRef::Long(1)]);
+        assert_eq!(nbt.query_all("Data.*").unwrap().len(), 4);
+        assert_eq!(nbt.query_all("*.*.Pos[*]").unwrap().len(), 3);
+        assert_eq!(nbt.query_all("Data.Player.Pos").unwrap().len(), 1);
+        assert_eq!(nbt.query_all("Data.Player.Inventory[*].nothing").unwrap(), []);
+    }
+
+    #[test]
+    fn errors_report_the_path_up_to_the_failure() {
+        assert_eq!(error("Data.Nope.x"), MissingKey("Data.Nope".to_string()));
+        assert_eq!(error("Data.Player.Inventory[2]"),
+                   IndexOutOfBounds("Data.Player.Inventory[2]".to_string()));
+        assert_eq!(error(r#"Data."odd.[key]"[9].x"#),
+                   IndexOutOfBounds(r#"Data."odd.[key]"[9]"#.to_string()));
+        assert_eq!(error("Data.Player.Pos.x"), NotACompound("Data.Player.Pos.x".to_string()));
+        assert_eq!(error("Data.Player[0]"), NotAList("Data.Player[0]".to_string()));
+        assert_eq!(error("Data.*"), Wildcard("Data.*".to_string()));
+        assert_eq!(error("Data.Sections[*]"), Wildcard("Data.Sections[*]".to_string()));
+        assert_eq!(level().query_all("Data.Player.Pos[0].*"),
+                   Err(NotACompound("Data.Player.Pos[0].*".to_string())));
+        assert_eq!(level().query_all("Data.Player[*]"),
+                   Err(NotAList("Data.Player[*]".to_string())));
+        assert_eq!(level().query_all("Data.*.Pos[5]"), Ok(vec![]));
+    }
+
+    #[test]
+    fn syntax_errors_report_the_offset() {
+        assert_eq!(error("Data..x"), Syntax(5));
+        assert_eq!(error(".Data"), Syntax(0));
+        assert_eq!(error("Data."), Syntax(5));
+        assert_eq!(error("Data[x]"), Syntax(5));
+        assert_eq!(error("Data[-1]"), Syntax(5));
+        assert_eq!(error("Data[0"), Syntax(6));
+        assert_eq!(error("Data]"), Syntax(4));
+        assert_eq!(error(r#"Data."open"#), Syntax(10));
+        assert_eq!(error(r#"Data."bad \n escape""#), Syntax(11));
+    }
+}
@@ -0,0 +1,414 @@
+//! Serializing Rust values to NBT with serde.
+//!
+//! Structs and maps become compounds, sequences become lists and `None`
+//! fields are left out. Enums are externally tagged: a unit variant is
+//! written as its name, any other variant as a compound holding a single
+//! entry named after it. Unsigned integers are stored in the signed tag
+//! of the same width. Units and unit structs have no data of their own
+//! and are written as empty compounds.
+//!
+//! Sequences are written as lists. Use `byte_array`, `int_array` or
+//! `long_array` with `#[serde(serialize_with = "...")]` to write a field
+//! as one of the array tags instead.
+
+use std::error;
+use std::fmt;
+use std::io::Write;
+
+use serde::ser::{ self, Serialize };
+
+use minecraft::nbt::{ Compound, List, Nbt, NbtWriterError };
+
+const INT_ARRAY: &'static str = "__nbt_int_array";
+const LONG_ARRAY: &'static str = "__nbt_long_array";
+
+#[derive(Debug)]
+pub enum Error {
+    Message(String),
+    /// A map key which isn't a string or an integer.
+    KeyMustBeString,
+    /// A sequence whose elements don't all have the same NBT type.
+    MixedList,
+    /// A value with no NBT representation, like a `None` in a sequence.
+    Unsupported(&'static str),
+    Writer(NbtWriterError)
+}
+
+pub type Result<T> = ::std::result::Result<T, Error>;
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Message(ref msg) => f.write_str(msg),
+            Error::KeyMustBeString => f.write_str("map key must be a string"),
+            Error::MixedList => f.write_str("list elements must all have the same type"),
+            Error::Unsupported(what) => write!(f, "cannot serialize {} as NBT", what),
+            Error::Writer(ref err) => write!(f, "{:?}", err)
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        "NBT serialization error"
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Error {
+        Error::Message(msg.to_string())
+    }
+}
+
+/// Converts a value to NBT.
+pub fn to_nbt<T: ?Sized + Serialize>(value: &T) -> Result<Nbt> {
+    match try!(value.serialize(Serializer)) {
+        Some(nbt) => Ok(nbt),
+        None => Err(Error::Unsupported("None"))
+    }
+}
+
+/// Writes a value as an unnamed root tag. The value is converted to an
+/// `Nbt` first, so all of it is held in memory twice while writing.
+pub fn to_writer<W: Write, T: ?Sized + Serialize>(w: W, value: &T) -> Result<()> {
+    let nbt = try!(to_nbt(value));
+    nbt.to_writer(w).map_err(Error::Writer)
+}
+
+/// Writes a byte sequence as a `TAG_Byte_Array`.
+pub fn byte_array<T, S>(v: &T, s: S) -> ::std::result::Result<S::Ok, S::Error>
+    where T: AsRef<[u8]>, S: ser::Serializer
+{
+    s.serialize_bytes(v.as_ref())
+}
+
+/// Writes an `i32` sequence as a `TAG_Int_Array`.
+pub fn int_array<T, S>(v: &T, s: S) -> ::std::result::Result<S::Ok, S::Error>
+    where T: AsRef<[i32]>, S: ser::Serializer
+{
+    s.serialize_newtype_struct(INT_ARRAY, v.as_ref())
+}
+
+/// Writes an `i64` sequence as a `TAG_Long_Array`.
+pub fn long_array<T, S>(v: &T, s: S) -> ::std::result::Result<S::Ok, S::Error>
+    where T: AsRef<[i64]>, S: ser::Serializer
+{
+    s.serialize_newtype_struct(LONG_ARRAY, v.as_ref())
+}
+
+/// Produces `None` for `None`, which compounds leave out.
+struct Serializer;
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    type SerializeSeq = SeqSerializer;
+    type SerializeTuple = SeqSerializer;
+    type SerializeTupleStruct = SeqSerializer;
+    type SerializeTupleVariant = SeqSerializer;
+    type SerializeMap = CompoundSerializer;
+    type SerializeStruct = CompoundSerializer;
+    type SerializeStructVariant = CompoundSerializer;
+
+    fn serialize_bool(self, v: bool) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Byte(v as i8)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Byte(v as i8)))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Short(v as i16)))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Int(v as i32)))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Long(v as i64)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::String(v.to_string())))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::ByteArray(v.to_vec())))
+    }
+
+    fn serialize_none(self) -> Result<Option<Nbt>> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<Option<Nbt>> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_variant(self, _name: &'static str, _index: u32,
+                              variant: &'static str) -> Result<Option<Nbt>> {
+        Ok(Some(Nbt::String(variant.to_string())))
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(self, name: &'static str, value: &T)
+                                                      -> Result<Option<Nbt>> {
+        let inner = try!(value.serialize(self));
+        match name {
+            INT_ARRAY => match inner {
+                Some(Nbt::List(List::Int(v))) => Ok(Some(Nbt::IntArray(v))),
+                Some(Nbt::List(ref l)) if l.is_empty() => Ok(Some(Nbt::IntArray(vec![]))),
+                _ => Err(Error::Unsupported("non-int sequence as an int array"))
+            },
+            LONG_ARRAY => match inner {
+                Some(Nbt::List(List::Long(v))) => Ok(Some(Nbt::LongArray(v))),
+                Some(Nbt::List(ref l)) if l.is_empty() => Ok(Some(Nbt::LongArray(vec![]))),
+                _ => Err(Error::Unsupported("non-long sequence as a long array"))
+            },
+            _ => Ok(inner)
+        }
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(self, _name: &'static str, _index: u32,
+                                                       variant: &'static str, value: &T)
+                                                       -> Result<Option<Nbt>> {
+        Ok(try!(value.serialize(self)).map(|v| wrap(Some(variant), v)))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::new(len.unwrap_or(0), None))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::new(len, None))
+    }
+
+    fn serialize_tuple_variant(self, _name: &'static str, _index: u32,
+                               variant: &'static str, len: usize) -> Result<SeqSerializer> {
+        Ok(SeqSerializer::new(len, Some(variant)))
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<CompoundSerializer> {
+        Ok(CompoundSerializer::new(len.unwrap_or(0), None))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<CompoundSerializer> {
+        Ok(CompoundSerializer::new(len, None))
+    }
+
+    fn serialize_struct_variant(self, _name: &'static str, _index: u32,
+                                variant: &'static str, len: usize)
+                                -> Result<CompoundSerializer> {
+        Ok(CompoundSerializer::new(len, Some(variant)))
+    }
+}
+
+/// Wraps the value of an enum variant in a compound named after it.
+fn wrap(variant: Option<&'static str>, value: Nbt) -> Nbt {
+    match variant {
+        Some(name) => {
+            let mut c = Compound::with_capacity(1);
+            c.insert(name.to_string(), value);
+            Nbt::Compound(c)
+        }
+        None => value
+    }
+}
+
+struct SeqSerializer {
+    values: Vec<Nbt>,
+    variant: Option<&'static str>
+}
+
+impl SeqSerializer {
+    fn new(len: usize, variant: Option<&'static str>) -> SeqSerializer {
+        SeqSerializer { values: Vec::with_capacity(len), variant: variant }
+    }
+
+    fn push<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        match try!(value.serialize(Serializer)) {
+            Some(nbt) => self.values.push(nbt),
+            None => return Err(Error::Unsupported("a None list element"))
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Nbt>> {
+        match List::from_values(self.values) {
+            Ok(list) => Ok(Some(wrap(self.variant, Nbt::List(list)))),
+            Err(_) => Err(Error::MixedList)
+        }
+    }
+}
+
+impl ser::SerializeSeq for SeqSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SeqSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SeqSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SeqSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+struct CompoundSerializer {
+    compound: Compound,
+    key: Option<String>,
+    variant: Option<&'static str>
+}
+
+impl CompoundSerializer {
+    fn new(len: usize, variant: Option<&'static str>) -> CompoundSerializer {
+        CompoundSerializer {
+            compound: Compound::with_capacity(len),
+            key: None,
+            variant: variant
+        }
+    }
+
+    fn insert<T: ?Sized + Serialize>(&mut self, key: String, value: &T) -> Result<()> {
+        if let Some(nbt) = try!(value.serialize(Serializer)) {
+            self.compound.insert(key, nbt);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Nbt>> {
+        Ok(Some(wrap(self.variant, Nbt::Compound(self.compound))))
+    }
+}
+
+impl ser::SerializeMap for CompoundSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<()> {
+        self.key = Some(match try!(key.serialize(Serializer)) {
+            Some(Nbt::String(s)) => s,
+            Some(n @ Nbt::Byte(_)) | Some(n @ Nbt::Short(_)) |
+            Some(n @ Nbt::Int(_)) | Some(n @ Nbt::Long(_)) => n.to_string(),
+            _ => return Err(Error::KeyMustBeString)
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<()> {
+        let key = self.key.take().expect("serialize_value called before serialize_key");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for CompoundSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for CompoundSerializer {
+    type Ok = Option<Nbt>;
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, key: &'static str, value: &T)
+                                             -> Result<()> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Option<Nbt>> {
+        self.finish()
+    }
+}
@@ -0,0 +1,794 @@
+//! NBT read in place from a byte slice.
+//!
+//! Nothing is copied: strings and byte arrays borrow from the input, int and
+//! long arrays are decoded on access, as are the rare strings whose modified
+//! UTF-8 differs from UTF-8, and compounds and lists are walked
+//! lazily, so subtrees nobody asks for are only skipped over. There is a
+//! tree-like view starting at `root`, and a SAX-style walk with `visit`.
+
+use std::borrow::Cow;
+use std::fmt;
+use std::marker::PhantomData;
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use minecraft::nbt::{ Compound, List, Nbt, NbtLimits, NbtReaderError, NbtReaderResult };
+use minecraft::nbt::mutf8;
+use minecraft::nbt::{ TAG_END, TAG_BYTE, TAG_SHORT, TAG_INT, TAG_LONG, TAG_FLOAT,
+    TAG_DOUBLE, TAG_BYTE_ARRAY, TAG_STRING, TAG_LIST, TAG_COMPOUND,
+    TAG_INT_ARRAY, TAG_LONG_ARRAY };
+
+/// A value borrowed from the input.
+#[derive(Copy, Clone, Debug)]
+pub enum Value<'a> {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(&'a [u8]),
+    IntArray(Array<'a, i32>),
+    LongArray(Array<'a, i64>),
+    String(Str<'a>),
+    List(ListRef<'a>),
+    Compound(CompoundRef<'a>)
+}
+
+/// A string borrowed from the input.
+#[derive(Copy, Clone)]
+pub struct Str<'a>(StrRepr<'a>);
+
+#[derive(Copy, Clone)]
+enum StrRepr<'a> {
+    /// Modified UTF-8 that is also valid UTF-8.
+    Plain(&'a str),
+    /// Modified UTF-8 that still needs decoding, known to be valid.
+    Encoded(&'a [u8])
+}
+
+impl<'a> Str<'a> {
+    /// The string, which only needs copying if it contains NUL or
+    /// characters outside the Basic Multilingual Plane.
+    pub fn to_str(&self) -> Cow<'a, str> {
+        match self.0 {
+            StrRepr::Plain(s) => Cow::Borrowed(s),
+            StrRepr::Encoded(b) => mutf8::decode(b).ok().expect("validated when read")
+        }
+    }
+
+    /// The raw modified UTF-8 bytes.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        match self.0 {
+            StrRepr::Plain(s) => s.as_bytes(),
+            StrRepr::Encoded(b) => b
+        }
+    }
+}
+
+impl<'a, 'b> PartialEq<&'b str> for Str<'a> {
+    fn eq(&self, other: &&'b str) -> bool {
+        match self.0 {
+            StrRepr::Plain(s) => s == *other,
+            StrRepr::Encoded(_) => self.to_str() == *other
+        }
+    }
+}
+
+impl<'a> fmt::Debug for Str<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&*self.to_str(), f)
+    }
+}
+
+impl<'a> fmt::Display for Str<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_str())
+    }
+}
+
+/// A big-endian array of numbers, decoded on access.
+#[derive(Copy, Clone, Debug)]
+pub struct Array<'a, T> {
+    data: &'a [u8],
+    marker: PhantomData<T>
+}
+
+/// A number type stored in an `Array`.
+pub trait Element: Copy {
+    fn size() -> usize;
+    fn read(buf: &[u8]) -> Self;
+}
+
+impl Element for i32 {
+    fn size() -> usize { 4 }
+    fn read(buf: &[u8]) -> i32 { BigEndian::read_i32(buf) }
+}
+
+impl Element for i64 {
+    fn size() -> usize { 8 }
+    fn read(buf: &[u8]) -> i64 { BigEndian::read_i64(buf) }
+}
+
+impl<'a, T: Element> Array<'a, T> {
+    pub fn len(&self) -> usize {
+        self.data.len() / T::size()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn get(&self, i: usize) -> Option<T> {
+        if i < self.len() {
+            Some(T::read(&self.data[i * T::size()..]))
+        } else {
+            None
+        }
+    }
+
+    pub fn iter(&self) -> ArrayIter<'a, T> {
+        ArrayIter { data: self.data, marker: PhantomData }
+    }
+
+    /// The raw big-endian bytes of the array.
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.data
+    }
+
+    pub fn to_vec(&self) -> Vec<T> {
+        self.iter().collect()
+    }
+}
+
+pub struct ArrayIter<'a, T> {
+    data: &'a [u8],
+    marker: PhantomData<T>
+}
+
+impl<'a, T: Element> Iterator for ArrayIter<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.data.len() < T::size() {
+            return None;
+        }
+        let x = T::read(self.data);
+        self.data = &self.data[T::size()..];
+        Some(x)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.data.len() / T::size();
+        (len, Some(len))
+    }
+}
+
+/// A compound whose entries are parsed as they are iterated over.
+#[derive(Copy, Clone, Debug)]
+pub struct CompoundRef<'a> {
+    data: &'a [u8],
+    depth: usize,
+    limits: NbtLimits
+}
+
+/// A list whose elements are parsed as they are iterated over.
+#[derive(Copy, Clone, Debug)]
+pub struct ListRef<'a> {
+    tag_type: i8,
+    len: usize,
+    data: &'a [u8],
+    depth: usize,
+    limits: NbtLimits
+}
+
+/// What a `Visitor` wants done with a compound or list.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Visit {
+    Enter,
+    Skip
+}
+
+/// Receives the tags of a value walked with `visit`.
+pub trait Visitor<'a> {
+    /// Called for the root, for every entry of an entered compound, and for
+    /// every element of an entered list (with an empty name). For compounds
+    /// and lists, `Visit::Enter` walks their contents and then calls `end`,
+    /// while `Visit::Skip` jumps over them. Other values ignore the result.
+    fn tag(&mut self, name: Str<'a>, value: Value<'a>) -> Visit;
+
+    /// Called after the contents of an entered compound or list.
+    fn end(&mut self) {}
+}
+
+/// Reads the root tag, returning its name and value.
+pub fn root<'a>(data: &'a [u8]) -> NbtReaderResult<(Str<'a>, Value<'a>)> {
+    root_with_limits(data, NbtLimits::default())
+}
+
+/// Reads the root tag with the given nesting depth and array length
+/// limits, which also apply to everything read through it. The data is
+/// already in memory, so `max_bytes` doesn't apply.
+pub fn root_with_limits<'a>(data: &'a [u8], limits: NbtLimits)
+                            -> NbtReaderResult<(Str<'a>, Value<'a>)> {
+    let mut cursor = Cursor::new(data, limits);
+    match try!(cursor.i8()) {
+        TAG_END => Err(NbtReaderError::EmptyRoot),
+        tag_type => {
+            let name = try!(cursor.string());
+            let value = try!(cursor.value(tag_type, 0));
+            Ok((name, value))
+        }
+    }
+}
+
+/// Walks the root tag and everything the visitor enters, returning the
+/// number of bytes the root tag took up.
+pub fn visit<'a, V: Visitor<'a>>(data: &'a [u8], visitor: &mut V) -> NbtReaderResult<usize> {
+    visit_with_limits(data, visitor, NbtLimits::default())
+}
+
+/// Walks the root tag like `visit`, with limits like `root_with_limits`.
+/// They also apply to skipped compounds and lists.
+pub fn visit_with_limits<'a, V: Visitor<'a>>(data: &'a [u8], visitor: &mut V, limits: NbtLimits)
+                                             -> NbtReaderResult<usize> {
+    let mut cursor = Cursor::new(data, limits);
+    match try!(cursor.i8()) {
+        TAG_END => Err(NbtReaderError::EmptyRoot),
+        tag_type => {
+            let name = try!(cursor.string());
+            let value = try!(cursor.value(tag_type, 0));
+            try!(cursor.walk(visitor, name, value, 0));
+            Ok(cursor.pos)
+        }
+    }
+}
+
+struct Cursor<'a> {
+    data: &'a [u8],
+    pos: usize,
+    limits: NbtLimits
+}
+
+impl<'a> Cursor<'a> {
+    fn new(data: &'a [u8], limits: NbtLimits) -> Cursor<'a> {
+        Cursor {
+            data: data,
+            pos: 0,
+            limits: limits
+        }
+    }
+
+    fn bytes(&mut self, n: usize) -> NbtReaderResult<&'a [u8]> {
+        if self.data.len() - self.pos < n {
+            return Err(NbtReaderError::UnexpectedEof);
+        }
+        let bytes = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn i8(&mut self) -> NbtReaderResult<i8> { self.bytes(1).map(|b| b[0] as i8) }
+    fn i16(&mut self) -> NbtReaderResult<i16> { self.bytes(2).map(BigEndian::read_i16) }
+    fn i32(&mut self) -> NbtReaderResult<i32> { self.bytes(4).map(BigEndian::read_i32) }
+    fn i64(&mut self) -> NbtReaderResult<i64> { self.bytes(8).map(BigEndian::read_i64) }
+    fn f32(&mut self) -> NbtReaderResult<f32> { self.bytes(4).map(BigEndian::read_f32) }
+    fn f64(&mut self) -> NbtReaderResult<f64> { self.bytes(8).map(BigEndian::read_f64) }
+
+    fn string(&mut self) -> NbtReaderResult<Str<'a>> {
+        let len = try!(self.bytes(2).map(BigEndian::read_u16)) as usize;
+        let bytes = try!(self.bytes(len));
+        match try!(mutf8::decode(bytes)) {
+            Cow::Borrowed(s) => Ok(Str(StrRepr::Plain(s))),
+            Cow::Owned(_) => Ok(Str(StrRepr::Encoded(bytes)))
+        }
+    }
+
+    /// Reads a length for elements of at least `size` bytes each, failing
+    /// early if they cannot possibly fit in the rest of the input.
+    fn len(&mut self, size: usize) -> NbtReaderResult<usize> {
+        let len = try!(self.i32());
+        if len < 0 {
+            return Err(NbtReaderError::NegativeLength(len));
+        }
+        if len as usize > self.limits.max_array_len {
+            return Err(NbtReaderError::ArrayTooLong(len as usize));
+        }
+        if (len as u64) * (size as u64) > (self.data.len() - self.pos) as u64 {
+            return Err(NbtReaderError::UnexpectedEof);
+        }
+        Ok(len as usize)
+    }
+
+    fn array<T: Element>(&mut self) -> NbtReaderResult<Array<'a, T>> {
+        let len = try!(self.len(T::size()));
+        let data = try!(self.bytes(len * T::size()));
+        Ok(Array { data: data, marker: PhantomData })
+    }
+
+    /// Reads the payload of a tag. Compounds and lists are not consumed
+    /// beyond their headers, see `skip_contents`.
+    fn value(&mut self, tag_type: i8, depth: usize) -> NbtReaderResult<Value<'a>> {
+        Ok(match tag_type {
+            TAG_BYTE => Value::Byte(try!(self.i8())),
+            TAG_SHORT => Value::Short(try!(self.i16())),
+            TAG_INT => Value::Int(try!(self.i32())),
+            TAG_LONG => Value::Long(try!(self.i64())),
+            TAG_FLOAT => Value::Float(try!(self.f32())),
+            TAG_DOUBLE => Value::Double(try!(self.f64())),
+            TAG_BYTE_ARRAY => {
+                let len = try!(self.len(1));
+                Value::ByteArray(try!(self.bytes(len)))
+            }
+            TAG_INT_ARRAY => Value::IntArray(try!(self.array())),
+            TAG_LONG_ARRAY => Value::LongArray(try!(self.array())),
+            TAG_STRING => Value::String(try!(self.string())),
+            TAG_LIST | TAG_COMPOUND if depth >= self.limits.max_depth => {
+                return Err(NbtReaderError::DepthLimit(self.limits.max_depth));
+            }
+            TAG_LIST => {
+                let elements = try!(self.i8());
+                let len = try!(self.len(min_size(elements)));
+                if elements == TAG_END && len != 0 {
+                    return Err(NbtReaderError::InvalidEndList(len as i32));
+                }
+                if elements < TAG_END || elements > TAG_LONG_ARRAY {
+                    return Err(NbtReaderError::UnknownTag(elements));
+                }
+                Value::List(ListRef {
+                    tag_type: elements,
+                    len: len,
+                    data: &self.data[self.pos..],
+                    depth: depth + 1,
+                    limits: self.limits
+                })
+            }
+            TAG_COMPOUND => Value::Compound(CompoundRef {
+                data: &self.data[self.pos..],
+                depth: depth + 1,
+                limits: self.limits
+            }),
+            tag_type => return Err(NbtReaderError::UnknownTag(tag_type))
+        })
+    }
+
+    /// Moves past the contents of a compound or list returned by `value`.
+    fn skip_contents(&mut self, value: Value<'a>) -> NbtReaderResult<()> {
+        match value {
+            Value::Compound(c) => {
+                loop {
+                    match try!(self.i8()) {
+                        TAG_END => return Ok(()),
+                        tag_type => {
+                            try!(self.string());
+                            let value = try!(self.value(tag_type, c.depth));
+                            try!(self.skip_contents(value));
+                        }
+                    }
+                }
+            }
+            Value::List(l) => {
+                for _ in 0..l.len {
+                    let value = try!(self.value(l.tag_type, l.depth));
+                    try!(self.skip_contents(value));
+                }
+                Ok(())
+            }
+            _ => Ok(())
+        }
+    }
+
+    fn walk<V: Visitor<'a>>(&mut self, visitor: &mut V, name: Str<'a>, value: Value<'a>,
+                            depth: usize) -> NbtReaderResult<()> {
+        let visit = visitor.tag(name, value);
+        match value {
+            Value::Compound(_) | Value::List(_) if visit == Visit::Skip => {
+                self.skip_contents(value)
+            }
+            Value::Compound(_) => {
+                loop {
+                    match try!(self.i8()) {
+                        TAG_END => break,
+                        tag_type => {
+                            let name = try!(self.string());
+                            let value = try!(self.value(tag_type, depth + 1));
+                            try!(self.walk(visitor, name, value, depth + 1));
+                        }
+                    }
+                }
+                visitor.end();
+                Ok(())
+            }
+            Value::List(l) => {
+                for _ in 0..l.len {
+                    let value = try!(self.value(l.tag_type, depth + 1));
+                    try!(self.walk(visitor, Str(StrRepr::Plain("")), value, depth + 1));
+                }
+                visitor.end();
+                Ok(())
+            }
+            _ => Ok(())
+        }
+    }
+}
+
+/// The fewest bytes an element with the tag type can take up.
+fn min_size(tag_type: i8) -> usize {
+    match tag_type {
+        TAG_END => 0,
+        TAG_BYTE | TAG_COMPOUND => 1,
+        TAG_SHORT | TAG_STRING => 2,
+        TAG_INT | TAG_FLOAT | TAG_BYTE_ARRAY | TAG_INT_ARRAY | TAG_LONG_ARRAY => 4,
+        TAG_LIST => 5,
+        _ => 8
+    }
+}
+
+impl<'a> CompoundRef<'a> {
+    pub fn iter(&self) -> Entries<'a> {
+        Entries {
+            cursor: Cursor::new(self.data, self.limits),
+            pending: None,
+            depth: self.depth,
+            done: false
+        }
+    }
+
+    /// Looks up an entry, skipping over the ones before it.
+    pub fn get(&self, name: &str) -> NbtReaderResult<Option<Value<'a>>> {
+        for entry in self.iter() {
+            let (key, value) = try!(entry);
+            if key == name {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn to_compound(&self) -> NbtReaderResult<Compound> {
+        let mut c = Compound::new();
+        for entry in self.iter() {
+            let (name, value) = try!(entry);
+            c.insert(name.to_str().into_owned(), try!(value.to_nbt()));
+        }
+        Ok(c)
+    }
+}
+
+/// The entries of a compound, in file order.
+pub struct Entries<'a> {
+    cursor: Cursor<'a>,
+    pending: Option<Value<'a>>,
+    depth: usize,
+    done: bool
+}
+
+impl<'a> Entries<'a> {
+    fn entry(&mut self) -> NbtReaderResult<Option<(Str<'a>, Value<'a>)>> {
+        if let Some(value) = self.pending.take() {
+            try!(self.cursor.skip_contents(value));
+        }
+        match try!(self.cursor.i8()) {
+            TAG_END => Ok(None),
+            tag_type => {
+                let name = try!(self.cursor.string());
+                let value = try!(self.cursor.value(tag_type, self.depth));
+                self.pending = Some(value);
+                Ok(Some((name, value)))
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Entries<'a> {
+    type Item = NbtReaderResult<(Str<'a>, Value<'a>)>;
+
+    fn next(&mut self) -> Option<NbtReaderResult<(Str<'a>, Value<'a>)>> {
+        if self.done {
+            return None;
+        }
+        match self.entry() {
+            Ok(Some(entry)) => Some(Ok(entry)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+impl<'a> ListRef<'a> {
+    /// The tag type of the elements, `TAG_END` (0) for some empty lists.
+    pub fn tag_type(&self) -> i8 {
+        self.tag_type
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> Elements<'a> {
+        Elements {
+            cursor: Cursor::new(self.data, self.limits),
+            pending: None,
+            list: *self,
+            index: 0
+        }
+    }
+
+    pub fn to_list(&self) -> NbtReaderResult<List> {
+        let mut values = Vec::with_capacity(self.len);
+        for element in self.iter() {
+            values.push(try!(try!(element).to_nbt()));
+        }
+        if values.is_empty() {
+            return Ok(empty_list(self.tag_type));
+        }
+        // The elements all have the list's tag type, so this cannot fail.
+        Ok(List::from_values(values).ok().unwrap())
+    }
+}
+
+/// The elements of a list.
+pub struct Elements<'a> {
+    cursor: Cursor<'a>,
+    pending: Option<Value<'a>>,
+    list: ListRef<'a>,
+    index: usize
+}
+
+impl<'a> Iterator for Elements<'a> {
+    type Item = NbtReaderResult<Value<'a>>;
+
+    fn next(&mut self) -> Option<NbtReaderResult<Value<'a>>> {
+        if self.index >= self.list.len {
+            return None;
+        }
+        if let Some(value) = self.pending.take() {
+            if let Err(err) = self.cursor.skip_contents(value) {
+                self.index = self.list.len;
+                return Some(Err(err));
+            }
+        }
+        self.index += 1;
+        match self.cursor.value(self.list.tag_type, self.list.depth) {
+            Ok(value) => {
+                self.pending = Some(value);
+                Some(Ok(value))
+            }
+            Err(err) => {
+                self.index = self.list.len;
+                Some(Err(err))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.list.len - self.index;
+        (len, Some(len))
+    }
+}
+
+fn empty_list(tag_type: i8) -> List {
+    match tag_type {
+        TAG_BYTE => List::Byte(vec![]),
+        TAG_SHORT => List::Short(vec![]),
+        TAG_INT => List::Int(vec![]),
+        TAG_LONG => List::Long(vec![]),
+        TAG_FLOAT => List::Float(vec![]),
+        TAG_DOUBLE => List::Double(vec![]),
+        TAG_BYTE_ARRAY => List::ByteArray(vec![]),
+        TAG_INT_ARRAY => List::IntArray(vec![]),
+        TAG_LONG_ARRAY => List::LongArray(vec![]),
+        TAG_STRING => List::String(vec![]),
+        TAG_LIST => List::List(vec![]),
+        TAG_COMPOUND => List::Compound(vec![]),
+        _ => List::End
+    }
+}
+
+impl<'a> Value<'a> {
+    /// Copies the value, and everything in it, into an owned `Nbt`.
+    pub fn to_nbt(&self) -> NbtReaderResult<Nbt> {
+        Ok(match *self {
+            Value::Byte(x) => Nbt::Byte(x),
+            Value::Short(x) => Nbt::Short(x),
+            Value::Int(x) => Nbt::Int(x),
+            Value::Long(x) => Nbt::Long(x),
+            Value::Float(x) => Nbt::Float(x),
+            Value::Double(x) => Nbt::Double(x),
+            Value::ByteArray(x) => Nbt::ByteArray(x.to_vec()),
+            Value::IntArray(x) => Nbt::IntArray(x.to_vec()),
+            Value::LongArray(x) => Nbt::LongArray(x.to_vec()),
+            Value::String(x) => Nbt::String(x.to_str().into_owned()),
+            Value::List(l) => Nbt::List(try!(l.to_list())),
+            Value::Compound(c) => Nbt::Compound(try!(c.to_compound()))
+        })
+    }
+
+    pub fn as_byte(&self) -> Option<i8> {
+        match *self { Value::Byte(x) => Some(x), _ => None }
+    }
+
+    pub fn as_short(&self) -> Option<i16> {
+        match *self { Value::Short(x) => Some(x), _ => None }
+    }
+
+    pub fn as_int(&self) -> Option<i32> {
+        match *self { Value::Int(x) => Some(x), _ => None }
+    }
+
+    pub fn as_long(&self) -> Option<i64> {
+        match *self { Value::Long(x) => Some(x), _ => None }
+    }
+
+    pub fn as_float(&self) -> Option<f32> {
+        match *self { Value::Float(x) => Some(x), _ => None }
+    }
+
+    pub fn as_double(&self) -> Option<f64> {
+        match *self { Value::Double(x) => Some(x), _ => None }
+    }
+
+    pub fn as_bytearray(&self) -> Option<&'a [u8]> {
+        match *self { Value::ByteArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_int_array(&self) -> Option<Array<'a, i32>> {
+        match *self { Value::IntArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_long_array(&self) -> Option<Array<'a, i64>> {
+        match *self { Value::LongArray(x) => Some(x), _ => None }
+    }
+
+    pub fn as_str(&self) -> Option<Str<'a>> {
+        match *self { Value::String(x) => Some(x), _ => None }
+    }
+
+    pub fn as_list(&self) -> Option<ListRef<'a>> {
+        match *self { Value::List(x) => Some(x), _ => None }
+    }
+
+    pub fn as_compound(&self) -> Option<CompoundRef<'a>> {
+        match *self { Value::Compound(x) => Some(x), _ => None }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use minecraft::nbt::{ Compound, List, Nbt, NbtLimits, NbtReaderError };
+    use super::*;
+
+    /// Writes down the tags it is given, skipping those named in `skip`.
+    struct Recorder {
+        skip: &'static [&'static str],
+        events: Vec<String>
+    }
+
+    impl<'a> Visitor<'a> for Recorder {
+        fn tag(&mut self, name: Str<'a>, value: Value<'a>) -> Visit {
+            let name = name.to_str();
+            let value = match value {
+                Value::Int(x) => x.to_string(),
+                Value::String(s) => s.to_str().into_owned(),
+                Value::List(_) => "[".to_string(),
+                Value::Compound(_) => "{".to_string(),
+                _ => "?".to_string()
+            };
+            self.events.push(format!("{}={}", name, value));
+            if self.skip.contains(&&name[..]) { Visit::Skip } else { Visit::Enter }
+        }
+
+        fn end(&mut self) {
+            self.events.push("end".to_string());
+        }
+    }
+
+    /// `{a: {b: 1, c: [I; 2]}, d: [{e: "f"}, {}], g: [[3]], h: 4}` and a
+    /// byte after it.
+    fn tree() -> Vec<u8> {
+        let mut a = Compound::new();
+        a.insert("b".to_string(), Nbt::Int(1));
+        a.insert("c".to_string(), Nbt::IntArray(vec![2]));
+        let mut e = Compound::new();
+        e.insert("e".to_string(), Nbt::String("f".to_string()));
+        let mut root = Compound::new();
+        root.insert("a".to_string(), Nbt::Compound(a));
+        root.insert("d".to_string(), Nbt::List(List::Compound(vec![e, Compound::new()])));
+        root.insert("g".to_string(), Nbt::List(List::List(vec![List::Int(vec![3])])));
+        root.insert("h".to_string(), Nbt::Int(4));
+        let mut data = vec![];
+        Nbt::Compound(root).to_writer(&mut data).unwrap();
+        data.push(0xff);
+        data
+    }
+
+    fn walk(data: &[u8], skip: &'static [&'static str], limits: NbtLimits)
+            -> NbtReaderResult<(usize, Vec<String>)> {
+        let mut recorder = Recorder { skip: skip, events: vec![] };
+        let len = try!(visit_with_limits(data, &mut recorder, limits));
+        Ok((len, recorder.events))
+    }
+
+    #[test]
+    fn visitors_enter_everything_they_ask_to() {
+        let data = tree();
+        let (len, events) = walk(&data, &[], NbtLimits::default()).unwrap();
+        assert_eq!(len, data.len() - 1);
+        assert_eq!(events, ["={", "a={", "b=1", "c=?", "end", "d=[", "={", "e=f", "end", "={",
+                            "end", "end", "g=[", "=[", "=3", "end", "end", "h=4", "end"]);
+    }
+
+    #[test]
+    fn visitors_skip_whole_subtrees() {
+        let data = tree();
+        let (len, events) = walk(&data, &["a", "d", "g"], NbtLimits::default()).unwrap();
+        assert_eq!(len, data.len() - 1);
+        assert_eq!(events, ["={", "a={", "d=[", "g=[", "h=4", "end"]);
+
+        let (len, events) = walk(&data, &[""], NbtLimits::default()).unwrap();
+        assert_eq!(len, data.len() - 1);
+        assert_eq!(events, ["={"]);
+    }
+
+    #[test]
+    fn limits_apply_while_visiting() {
+        let data = tree();
+        assert!(walk(&data, &[], NbtLimits { max_depth: 3, ..NbtLimits::default() }).is_ok());
+        // The compounds in `d` are too deep, even when skipped.
+        let limits = NbtLimits { max_depth: 2, ..NbtLimits::default() };
+        for skip in &[&[][..], &["d"][..], &[""][..]] {
+            match walk(&data, skip, limits) {
+                Err(NbtReaderError::DepthLimit(2)) => {}
+                other => panic!("{:?}: {:?}", skip, other)
+            }
+        }
+
+        let limits = NbtLimits { max_array_len: 1, ..NbtLimits::default() };
+        match walk(&data, &["d"], limits) {
+            Err(NbtReaderError::ArrayTooLong(2)) => {}
+            other => panic!("{:?}", other)
+        }
+        match walk(&data, &[], limits) {
+            Err(NbtReaderError::ArrayTooLong(2)) => {}
+            other => panic!("{:?}", other)
+        }
+
+        assert!(walk(&data[..data.len() - 3], &[""], NbtLimits::default()).is_err());
+    }
+
+    #[test]
+    fn limits_apply_to_everything_read_through_the_root() {
+        // {"": {a: [[[]]]}, b: [I; 1, 2, 3]}
+        let data = b"\x0a\x00\x00\
+                     \x09\x00\x01a\x09\x00\x00\x00\x01\x09\x00\x00\x00\x01\x00\x00\x00\x00\x00\
+                     \x0b\x00\x01b\x00\x00\x00\x03\x00\x00\x00\x01\x00\x00\x00\x02\x00\x00\x00\x03\
+                     \x00";
+        assert!(root(data).unwrap().1.to_nbt().is_ok());
+
+        let limits = NbtLimits { max_depth: 3, ..NbtLimits::default() };
+        let (_, value) = root_with_limits(data, limits).unwrap();
+        match value.to_nbt() {
+            Err(NbtReaderError::DepthLimit(3)) => {}
+            other => panic!("{:?}", other)
+        }
+
+        let limits = NbtLimits { max_array_len: 2, ..NbtLimits::default() };
+        let (_, value) = root_with_limits(data, limits).unwrap();
+        let compound = value.as_compound().unwrap();
+        assert!(compound.get("a").unwrap().is_some());
+        match compound.get("b") {
+            Err(NbtReaderError::ArrayTooLong(3)) => {}
+            other => panic!("{:?}", other)
+        }
+    }
+}
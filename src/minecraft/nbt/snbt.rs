@@ -0,0 +1,499 @@
+//! Stringified NBT, the text format used by Minecraft commands.
+//!
+//! `from_str` parses text like `{Pos: [1.5d, 64.0d, -3.0d], Count: 1b}` and
+//! `to_string`/`to_string_pretty` print values back in a form it accepts.
+
+use std::str::FromStr;
+
+use minecraft::nbt::{ Compound, List, Nbt };
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum SnbtError {
+    UnexpectedEnd,
+    UnexpectedChar(usize, char),
+    InvalidEscape(usize, char),
+    MixedList(usize),
+    UnknownArrayType(usize, char),
+    TrailingData(usize)
+}
+
+pub type SnbtResult<T> = Result<T, SnbtError>;
+
+/// Parses a single SNBT value, which may be surrounded by whitespace.
+pub fn from_str(s: &str) -> SnbtResult<Nbt> {
+    let mut parser = Parser { src: s, pos: 0 };
+    let value = try!(parser.value());
+    parser.skip_whitespace();
+    if parser.pos < s.len() {
+        return Err(SnbtError::TrailingData(parser.pos));
+    }
+    Ok(value)
+}
+
+impl FromStr for Nbt {
+    type Err = SnbtError;
+
+    fn from_str(s: &str) -> SnbtResult<Nbt> {
+        from_str(s)
+    }
+}
+
+fn is_unquoted(c: char) -> bool {
+    match c {
+        '0'...'9' | 'a'...'z' | 'A'...'Z' | '_' | '-' | '.' | '+' => true,
+        _ => false
+    }
+}
+
+struct Parser<'a> {
+    src: &'a str,
+    pos: usize
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> SnbtResult<char> {
+        match self.peek() {
+            Some(c) => {
+                self.pos += c.len_utf8();
+                Ok(c)
+            }
+            None => Err(SnbtError::UnexpectedEnd)
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() { break; }
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// Skips whitespace and consumes `c` if it comes next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += c.len_utf8();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> SnbtResult<()> {
+        self.skip_whitespace();
+        let pos = self.pos;
+        match try!(self.bump()) {
+            x if x == c => Ok(()),
+            x => Err(SnbtError::UnexpectedChar(pos, x))
+        }
+    }
+
+    fn value(&mut self) -> SnbtResult<Nbt> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.compound().map(Nbt::Compound),
+            Some('[') => self.list_or_array(),
+            Some('"') | Some('\'') => self.quoted().map(Nbt::String),
+            Some(c) if is_unquoted(c) => Ok(parse_token(self.unquoted())),
+            Some(c) => Err(SnbtError::UnexpectedChar(self.pos, c)),
+            None => Err(SnbtError::UnexpectedEnd)
+        }
+    }
+
+    fn unquoted(&mut self) -> &'a str {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !is_unquoted(c) { break; }
+            self.pos += 1;
+        }
+        &self.src[start..self.pos]
+    }
+
+    fn quoted(&mut self) -> SnbtResult<String> {
+        let quote = try!(self.bump());
+        let mut s = String::new();
+        loop {
+            match try!(self.bump()) {
+                '\\' => {
+                    let pos = self.pos;
+                    match try!(self.bump()) {
+                        c @ '\\' | c @ '"' | c @ '\'' => s.push(c),
+                        c => return Err(SnbtError::InvalidEscape(pos, c))
+                    }
+                }
+                c if c == quote => return Ok(s),
+                c => s.push(c)
+            }
+        }
+    }
+
+    fn key(&mut self) -> SnbtResult<String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"') | Some('\'') => self.quoted(),
+            Some(c) if is_unquoted(c) => Ok(self.unquoted().to_string()),
+            Some(c) => Err(SnbtError::UnexpectedChar(self.pos, c)),
+            None => Err(SnbtError::UnexpectedEnd)
+        }
+    }
+
+    fn compound(&mut self) -> SnbtResult<Compound> {
+        try!(self.expect('{'));
+        let mut c = Compound::new();
+        if self.eat('}') {
+            return Ok(c);
+        }
+        loop {
+            let key = try!(self.key());
+            try!(self.expect(':'));
+            let value = try!(self.value());
+            c.insert(key, value);
+            if !self.eat(',') {
+                try!(self.expect('}'));
+                return Ok(c);
+            }
+        }
+    }
+
+    /// Parses comma separated values up to the closing `]`.
+    fn elements(&mut self) -> SnbtResult<Vec<Nbt>> {
+        let mut values = vec![];
+        if self.eat(']') {
+            return Ok(values);
+        }
+        loop {
+            values.push(try!(self.value()));
+            if !self.eat(',') {
+                try!(self.expect(']'));
+                return Ok(values);
+            }
+        }
+    }
+
+    fn list_or_array(&mut self) -> SnbtResult<Nbt> {
+        try!(self.expect('['));
+        let start = self.pos;
+        let rest = &self.src[self.pos..];
+        let mut chars = rest.chars();
+        match (chars.next(), chars.next()) {
+            (Some(t), Some(';')) if t.is_alphabetic() => {
+                self.pos += t.len_utf8() + 1;
+                let values = try!(self.elements());
+                match t {
+                    'B' => array(values, start, |v| match v {
+                        Nbt::Byte(x) => Some(x as u8),
+                        _ => None
+                    }).map(Nbt::ByteArray),
+                    'I' => array(values, start, |v| match v {
+                        Nbt::Byte(x) => Some(x as i32),
+                        Nbt::Short(x) => Some(x as i32),
+                        Nbt::Int(x) => Some(x),
+                        _ => None
+                    }).map(Nbt::IntArray),
+                    'L' => array(values, start, |v| match v {
+                        Nbt::Byte(x) => Some(x as i64),
+                        Nbt::Short(x) => Some(x as i64),
+                        Nbt::Int(x) => Some(x as i64),
+                        Nbt::Long(x) => Some(x),
+                        _ => None
+                    }).map(Nbt::LongArray),
+                    t => Err(SnbtError::UnknownArrayType(start, t))
+                }
+            }
+            _ => {
+                let values = try!(self.elements());
+                List::from_values(values)
+                    .map(Nbt::List)
+                    .map_err(|_| SnbtError::MixedList(start))
+            }
+        }
+    }
+}
+
+fn array<T, F>(values: Vec<Nbt>, pos: usize, mut f: F) -> SnbtResult<Vec<T>>
+    where F: FnMut(Nbt) -> Option<T>
+{
+    let mut v = Vec::with_capacity(values.len());
+    for value in values.into_iter() {
+        match f(value) {
+            Some(x) => v.push(x),
+            None => return Err(SnbtError::MixedList(pos))
+        }
+    }
+    Ok(v)
+}
+
+fn is_integer(s: &str) -> bool {
+    let digits = s.trim_left_matches(|c| c == '-' || c == '+');
+    s.len() - digits.len() <= 1
+        && !digits.is_empty()
+        && digits.chars().all(|c| c.is_digit(10))
+}
+
+fn is_decimal(s: &str) -> bool {
+    s.chars().any(|c| c.is_digit(10))
+        && s.chars().all(|c| match c {
+            '0'...'9' | '.' | 'e' | 'E' | '+' | '-' => true,
+            _ => false
+        })
+}
+
+/// A decimal number, or one of the values without digits the way they
+/// are printed, which Minecraft itself can't read back.
+fn is_float(s: &str) -> bool {
+    match s {
+        "NaN" | "inf" | "-inf" => true,
+        _ => is_decimal(s)
+    }
+}
+
+/// Interprets an unquoted token as a number or boolean, falling back to a
+/// plain string like Minecraft does. That includes numbers out of the
+/// range of their type, like `3000000000` or `200b`.
+fn parse_token(token: &str) -> Nbt {
+    macro_rules! number(
+        ($s:expr, $t:ty, $v:path) => (
+            $s.parse::<$t>().map($v).unwrap_or_else(|_| Nbt::String(token.to_string()))
+        )
+    );
+
+    match token {
+        "true" => return Nbt::Byte(1),
+        "false" => return Nbt::Byte(0),
+        _ => {}
+    }
+    if is_integer(token) {
+        return number!(token, i32, Nbt::Int);
+    }
+    let (body, suffix) = token.split_at(token.len() - 1);
+    match suffix {
+        "b" | "B" if is_integer(body) => number!(body, i8, Nbt::Byte),
+        "s" | "S" if is_integer(body) => number!(body, i16, Nbt::Short),
+        "l" | "L" if is_integer(body) => number!(body, i64, Nbt::Long),
+        "f" | "F" if is_float(body) => number!(body, f32, Nbt::Float),
+        "d" | "D" if is_float(body) => number!(body, f64, Nbt::Double),
+        _ if token.contains('.') && is_decimal(token) => number!(token, f64, Nbt::Double),
+        _ => Nbt::String(token.to_string())
+    }
+}
+
+/// Prints a value on a single line.
+pub fn to_string(nbt: &Nbt) -> String {
+    let mut printer = Printer { out: String::new(), indent: None, level: 0 };
+    printer.value(nbt);
+    printer.out
+}
+
+/// Prints a value with compounds and nested lists spread over indented
+/// lines.
+pub fn to_string_pretty(nbt: &Nbt) -> String {
+    let mut printer = Printer { out: String::new(), indent: Some("    "), level: 0 };
+    printer.value(nbt);
+    printer.out
+}
+
+fn quote(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out.push('"');
+}
+
+struct Printer {
+    out: String,
+    indent: Option<&'static str>,
+    level: usize
+}
+
+impl Printer {
+    fn newline(&mut self) {
+        if let Some(indent) = self.indent {
+            self.out.push('\n');
+            for _ in 0..self.level {
+                self.out.push_str(indent);
+            }
+        }
+    }
+
+    /// Prints `items` between `open` and `close`, one per line if `nested`.
+    fn seq<T, F>(&mut self, open: &str, close: char, items: &[T], nested: bool, mut f: F)
+        where F: FnMut(&mut Printer, &T)
+    {
+        self.out.push_str(open);
+        if items.is_empty() {
+            self.out.push(close);
+            return;
+        }
+        let nested = nested && self.indent.is_some();
+        if open.ends_with(';') && !nested && self.indent.is_some() {
+            self.out.push(' ');
+        }
+        self.level += 1;
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                self.out.push(',');
+                if !nested && self.indent.is_some() {
+                    self.out.push(' ');
+                }
+            }
+            if nested {
+                self.newline();
+            }
+            f(self, item);
+        }
+        self.level -= 1;
+        if nested {
+            self.newline();
+        }
+        self.out.push(close);
+    }
+
+    fn key(&mut self, key: &str) {
+        if !key.is_empty() && key.chars().all(is_unquoted) {
+            self.out.push_str(key);
+        } else {
+            quote(&mut self.out, key);
+        }
+        self.out.push(':');
+        if self.indent.is_some() {
+            self.out.push(' ');
+        }
+    }
+
+    fn compound(&mut self, c: &Compound) {
+        let entries: Vec<_> = c.iter().collect();
+        self.seq("{", '}', &entries, true, |p, &(k, v)| {
+            p.key(k);
+            p.value(v);
+        });
+    }
+
+    fn list(&mut self, l: &List) {
+        match *l {
+            List::End => self.out.push_str("[]"),
+            List::Byte(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&format!("{}b", x))),
+            List::Short(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&format!("{}s", x))),
+            List::Int(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&x.to_string())),
+            List::Long(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&format!("{}L", x))),
+            List::Float(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&format!("{}f", x))),
+            List::Double(ref v) => self.seq("[", ']', v, false, |p, x| p.out.push_str(&format!("{}d", x))),
+            List::ByteArray(ref v) => self.seq("[", ']', v, true, |p, x| p.byte_array(x)),
+            List::IntArray(ref v) => self.seq("[", ']', v, true, |p, x| p.int_array(x)),
+            List::LongArray(ref v) => self.seq("[", ']', v, true, |p, x| p.long_array(x)),
+            List::String(ref v) => self.seq("[", ']', v, false, |p, x| quote(&mut p.out, x)),
+            List::List(ref v) => self.seq("[", ']', v, true, |p, x| p.list(x)),
+            List::Compound(ref v) => self.seq("[", ']', v, true, |p, x| p.compound(x))
+        }
+    }
+
+    fn byte_array(&mut self, v: &[u8]) {
+        self.seq("[B;", ']', v, false, |p, &x| p.out.push_str(&format!("{}b", x as i8)));
+    }
+
+    fn int_array(&mut self, v: &[i32]) {
+        self.seq("[I;", ']', v, false, |p, x| p.out.push_str(&x.to_string()));
+    }
+
+    fn long_array(&mut self, v: &[i64]) {
+        self.seq("[L;", ']', v, false, |p, x| p.out.push_str(&format!("{}L", x)));
+    }
+
+    fn value(&mut self, nbt: &Nbt) {
+        match *nbt {
+            Nbt::Byte(x) => self.out.push_str(&format!("{}b", x)),
+            Nbt::Short(x) => self.out.push_str(&format!("{}s", x)),
+            Nbt::Int(x) => self.out.push_str(&x.to_string()),
+            Nbt::Long(x) => self.out.push_str(&format!("{}L", x)),
+            Nbt::Float(x) => self.out.push_str(&format!("{}f", x)),
+            Nbt::Double(x) => self.out.push_str(&format!("{}d", x)),
+            Nbt::ByteArray(ref v) => self.byte_array(v),
+            Nbt::IntArray(ref v) => self.int_array(v),
+            Nbt::LongArray(ref v) => self.long_array(v),
+            Nbt::String(ref s) => quote(&mut self.out, s),
+            Nbt::List(ref l) => self.list(l),
+            Nbt::Compound(ref c) => self.compound(c)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{ f32, f64 };
+
+    use minecraft::nbt::{ Compound, List, Nbt };
+    use super::{ from_str, to_string, to_string_pretty, SnbtError };
+
+    fn assert_round_trip(nbt: Nbt) {
+        assert_eq!(from_str(&to_string(&nbt)), Ok(nbt.clone()));
+        assert_eq!(from_str(&to_string_pretty(&nbt)), Ok(nbt));
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let mut inner = Compound::new();
+        inner.insert("".to_string(), Nbt::String("quote \" and 'apostrophe'".to_string()));
+        inner.insert("key with spaces".to_string(), Nbt::List(List::End));
+        let mut c = Compound::new();
+        c.insert("byte".to_string(), Nbt::Byte(-128));
+        c.insert("short".to_string(), Nbt::Short(32767));
+        c.insert("int".to_string(), Nbt::Int(-2147483648));
+        c.insert("long".to_string(), Nbt::Long(9223372036854775807));
+        c.insert("float".to_string(), Nbt::Float(0.1));
+        c.insert("double".to_string(), Nbt::Double(-1e300));
+        c.insert("bytes".to_string(), Nbt::ByteArray(vec![0, 127, 128, 255]));
+        c.insert("ints".to_string(), Nbt::IntArray(vec![]));
+        c.insert("longs".to_string(), Nbt::LongArray(vec![-1, 1]));
+        c.insert("string".to_string(), Nbt::String("true".to_string()));
+        c.insert("lists".to_string(), Nbt::List(List::List(vec![List::Short(vec![1, 2]), List::End])));
+        c.insert("arrays".to_string(), Nbt::List(List::IntArray(vec![vec![1], vec![]])));
+        c.insert("compounds".to_string(), Nbt::List(List::Compound(vec![inner.clone(), Compound::new()])));
+        c.insert("compound".to_string(), Nbt::Compound(inner));
+        assert_round_trip(Nbt::Compound(c));
+        for &x in &[f32::INFINITY, f32::NEG_INFINITY, f32::MIN_POSITIVE, -0.0] {
+            assert_round_trip(Nbt::Float(x));
+        }
+        for &x in &[f64::INFINITY, f64::NEG_INFINITY, f64::MAX] {
+            assert_round_trip(Nbt::Double(x));
+        }
+        assert_round_trip(Nbt::List(List::Double(vec![f64::INFINITY, 1.0])));
+    }
+
+    #[test]
+    fn nan_round_trips() {
+        match from_str(&to_string(&Nbt::Float(f32::NAN))) {
+            Ok(Nbt::Float(x)) => assert!(x.is_nan()),
+            other => panic!("{:?}", other)
+        }
+        match from_str(&to_string(&Nbt::Double(f64::NAN))) {
+            Ok(Nbt::Double(x)) => assert!(x.is_nan()),
+            other => panic!("{:?}", other)
+        }
+    }
+
+    #[test]
+    fn unquoted_tokens() {
+        assert_eq!(from_str("12"), Ok(Nbt::Int(12)));
+        assert_eq!(from_str("-2147483648"), Ok(Nbt::Int(-2147483648)));
+        assert_eq!(from_str("3000000000"), Ok(Nbt::String("3000000000".to_string())));
+        assert_eq!(from_str("3000000000L"), Ok(Nbt::Long(3000000000)));
+        assert_eq!(from_str("1.5"), Ok(Nbt::Double(1.5)));
+        assert_eq!(from_str("true"), Ok(Nbt::Byte(1)));
+        assert_eq!(from_str("minecraft"), Ok(Nbt::String("minecraft".to_string())));
+        assert_eq!(from_str("inf"), Ok(Nbt::String("inf".to_string())));
+        assert_eq!(from_str("200b"), Ok(Nbt::String("200b".to_string())));
+        assert_eq!(from_str("-129B"), Ok(Nbt::String("-129B".to_string())));
+        assert_eq!(from_str("40000s"), Ok(Nbt::String("40000s".to_string())));
+        assert_eq!(from_str("9223372036854775808L"),
+                   Ok(Nbt::String("9223372036854775808L".to_string())));
+        assert_eq!(from_str("1.2.3"), Ok(Nbt::String("1.2.3".to_string())));
+        assert_eq!(from_str("[127b, 128b]"), Err(SnbtError::MixedList(1)));
+    }
+}
@@ -0,0 +1,133 @@
+//! Players, which singleplayer worlds keep in `level.dat` and server
+//! worlds keep in a file each.
+
+use std::fmt;
+use std::fs::{ self, File };
+use std::io;
+use std::path::{ Path, PathBuf };
+
+use flate2::read::GzDecoder;
+
+use minecraft::level::LevelError;
+use minecraft::nbt::{ de, Nbt };
+
+/// The parts of a player needed to place the camera.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Player {
+    #[serde(rename = "Pos")]
+    pub pos: [f64; 3],
+    /// Yaw and pitch in degrees.
+    #[serde(rename = "Rotation")]
+    pub rotation: [f32; 2],
+    #[serde(rename = "Dimension", default)]
+    pub dimension: DimensionId,
+}
+
+impl Player {
+    pub fn from_file(path: &Path) -> Result<Player, LevelError> {
+        let reader = try!(GzDecoder::new(try!(File::open(path))));
+        let root = try!(Nbt::from_reader(reader));
+        Ok(try!(de::from_nbt(&root)))
+    }
+}
+
+/// A dimension as saved with a player: a number before 1.16, a namespaced
+/// name like `minecraft:the_nether` after.
+#[derive(Clone, PartialEq, Eq, Debug, Deserialize)]
+#[serde(untagged)]
+pub enum DimensionId {
+    Legacy(i32),
+    Named(String),
+}
+
+impl Default for DimensionId {
+    fn default() -> DimensionId {
+        DimensionId::Legacy(0)
+    }
+}
+
+/// A player with a file of their own.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum PlayerId {
+    /// `playerdata/<uuid>.dat`, used since 1.7.6.
+    Uuid(String),
+    /// `players/<name>.dat`, used before that.
+    Name(String),
+}
+
+impl PlayerId {
+    pub fn path(&self, world: &Path) -> PathBuf {
+        match *self {
+            PlayerId::Uuid(ref uuid) => world.join("playerdata").join(format!("{}.dat", uuid)),
+            PlayerId::Name(ref name) => world.join("players").join(format!("{}.dat", name))
+        }
+    }
+}
+
+impl fmt::Display for PlayerId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlayerId::Uuid(ref uuid) => f.write_str(uuid),
+            PlayerId::Name(ref name) => f.write_str(name)
+        }
+    }
+}
+
+/// The player files of a world.
+pub struct PlayerData {
+    world: PathBuf
+}
+
+impl PlayerData {
+    pub fn new(world: &Path) -> PlayerData {
+        PlayerData { world: world.to_path_buf() }
+    }
+
+    /// Every player with a file, UUIDs before names.
+    pub fn players(&self) -> io::Result<Vec<PlayerId>> {
+        let mut players = vec![];
+        for uuid in try!(dat_files(&self.world.join("playerdata"))) {
+            players.push(PlayerId::Uuid(uuid));
+        }
+        for name in try!(dat_files(&self.world.join("players"))) {
+            players.push(PlayerId::Name(name));
+        }
+        players.sort();
+        Ok(players)
+    }
+
+    /// Looks up a player by UUID, with or without dashes, or by name.
+    /// Both are compared ignoring case. Names are only known for the old
+    /// `players` files.
+    pub fn find(&self, who: &str) -> io::Result<Option<PlayerId>> {
+        let wanted = who.replace("-", "").to_lowercase();
+        Ok(try!(self.players()).into_iter().find(|id| match *id {
+            PlayerId::Uuid(ref uuid) => uuid.replace("-", "").to_lowercase() == wanted,
+            PlayerId::Name(ref name) => name.to_lowercase() == who.to_lowercase()
+        }))
+    }
+
+    pub fn load(&self, id: &PlayerId) -> Result<Player, LevelError> {
+        Player::from_file(&id.path(&self.world))
+    }
+}
+
+/// The names of the `.dat` files in a directory, without the extension.
+/// A missing directory has none.
+fn dat_files(dir: &Path) -> io::Result<Vec<String>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(vec![]),
+        Err(err) => return Err(err)
+    };
+    let mut names = vec![];
+    for entry in entries {
+        let path = try!(entry).path();
+        if path.extension().map_or(false, |ext| ext == "dat") {
+            if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                names.push(name.to_string());
+            }
+        }
+    }
+    Ok(names)
+}
@@ -0,0 +1,198 @@
+//! Finding damaged chunks in a region file, and writing a copy without
+//! them.
+
+use std::cmp::min;
+use std::path::Path;
+
+use minecraft::nbt::Nbt;
+use minecraft::region::{ Region, RegionError, RegionWriteError, RegionWriter, SECTOR };
+
+#[derive(Debug)]
+pub enum Problem {
+    /// The chunk's sectors are also used by the chunk at this position,
+    /// which comes earlier in the tables. At most one of them is intact.
+    Overlap(u8, u8),
+    /// The chunk can't be read: its location is outside the file, its
+    /// length doesn't fit its sectors, or its data doesn't decompress to
+    /// NBT.
+    Unreadable(RegionError),
+}
+
+impl Problem {
+    /// Whether the chunk is lost. An overlapping chunk that still reads
+    /// fine can be moved to sectors of its own.
+    pub fn is_fatal(&self) -> bool {
+        match *self {
+            Problem::Overlap(..) => false,
+            Problem::Unreadable(_) => true
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ChunkProblem {
+    /// The position of the chunk in the region.
+    pub x: u8,
+    pub z: u8,
+    pub problem: Problem
+}
+
+/// Checks every chunk of a region, returning all the problems found, in
+/// the order of the tables. A chunk can have more than one. Fails only if
+/// the file is too short to have tables.
+pub fn check(region: &Region) -> Result<Vec<ChunkProblem>, RegionError> {
+    let len = region.as_slice().len();
+    if len < 2 * SECTOR {
+        return Err(RegionError::Truncated);
+    }
+    // The chunk using each sector, to find overlaps.
+    let mut owners = vec![None; (len + SECTOR - 1) / SECTOR];
+    let mut problems = vec![];
+    for i in 0..1024 {
+        let (x, z) = ((i % 32) as u8, (i / 32) as u8);
+        let mut report = |problem| problems.push(ChunkProblem { x: x, z: z, problem: problem });
+        let info = match region.chunk_info(x, z) {
+            Ok(Some(info)) => info,
+            Ok(None) => continue,
+            Err(err) => {
+                report(Problem::Unreadable(err));
+                continue;
+            }
+        };
+
+        let mut overlap = None;
+        for s in info.offset..min(info.offset + info.sectors, owners.len()) {
+            match owners[s] {
+                Some(owner) => overlap = overlap.or(Some(owner)),
+                None => owners[s] = Some((x, z))
+            }
+        }
+        if let Some((ox, oz)) = overlap {
+            report(Problem::Overlap(ox, oz));
+        }
+
+        let data = region.read_data(&info).and_then(|data| {
+            Nbt::from_reader(&data[..]).map_err(RegionError::from)
+        });
+        if let Err(err) = data {
+            report(Problem::Unreadable(err));
+        }
+    }
+    Ok(problems)
+}
+
+/// Writes a copy of a region to `output` with only the chunks that have
+/// no fatal problem, each in sectors of its own. Their data is copied as
+/// it is, timestamps included. Returns how many chunks were dropped.
+pub fn repair(region: &Region, problems: &[ChunkProblem], output: &Path)
+              -> Result<usize, RegionWriteError> {
+    let mut writer = RegionWriter::create(output);
+    let mut dropped = 0;
+    for i in 0..1024 {
+        let (x, z) = ((i % 32) as u8, (i / 32) as u8);
+        let fatal = problems.iter().any(|p| p.x == x && p.z == z && p.problem.is_fatal());
+        let info = match region.chunk_info(x, z) {
+            Ok(Some(ref info)) if !fatal => *info,
+            Ok(None) => continue,
+            _ => {
+                dropped += 1;
+                continue;
+            }
+        };
+        match region.read_raw(&info) {
+            Ok((compression, data)) => {
+                try!(writer.write_raw(x, z, compression, &data));
+                writer.set_timestamp(x, z, info.timestamp);
+            }
+            _ => dropped += 1
+        }
+    }
+    try!(writer.save());
+    Ok(dropped)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{ self, File };
+    use std::io::{ Read, Write };
+    use std::path::Path;
+
+    use minecraft::nbt::Nbt;
+    use minecraft::region::{ Compression, Region, RegionWriter, SECTOR };
+    use minecraft::region::tests::temp_dir;
+    use super::*;
+
+    fn problems(region: &Region) -> Vec<(u8, u8, String)> {
+        check(region).unwrap().into_iter().map(|p| (p.x, p.z, format!("{:?}", p.problem))).collect()
+    }
+
+    /// Changes the bytes of a file.
+    fn corrupt<F: FnOnce(&mut Vec<u8>)>(path: &Path, f: F) {
+        let mut data = vec![];
+        File::open(path).unwrap().read_to_end(&mut data).unwrap();
+        f(&mut data);
+        File::create(path).unwrap().write_all(&data).unwrap();
+    }
+
+    #[test]
+    fn damaged_chunks_are_found_and_left_out() {
+        let dir = temp_dir("check");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        for x in 0..7 {
+            let nbt: Nbt = format!("{{x: {}}}", x).parse().unwrap();
+            writer.write_chunk(x, 0, &nbt, Compression::Zlib).unwrap();
+            writer.set_timestamp(x, 0, 1000 + x as u32);
+        }
+        writer.save().unwrap();
+        let (first, last) = {
+            let region = Region::open(&path).unwrap();
+            assert_eq!(problems(&region), []);
+            (region.chunk_raw(0, 0).unwrap().unwrap().1, region.chunk_raw(6, 0).unwrap().unwrap().1)
+        };
+
+        corrupt(&path, |data| {
+            // Chunk 1 uses chunk 0's sector.
+            let location = data[0..4].to_vec();
+            data[4..8].copy_from_slice(&location);
+            // Chunk 2 doesn't decompress.
+            let offset = data[10] as usize * SECTOR;
+            for b in &mut data[offset + 5..offset + 20] {
+                *b = 0xaa;
+            }
+            // Chunk 3 is past the end of the file.
+            data[12..16].copy_from_slice(&[0, 0, 90, 1]);
+            // Chunk 4 is longer than its one sector.
+            let offset = data[18] as usize * SECTOR;
+            data[offset..offset + 4].copy_from_slice(&[0, 0, 0x13, 0x88]);
+            // Chunk 5 starts in the tables.
+            data[20..24].copy_from_slice(&[0, 0, 1, 1]);
+        });
+
+        let region = Region::open(&path).unwrap();
+        let found = problems(&region);
+        assert_eq!(found.len(), 5);
+        assert_eq!(found[0], (1, 0, "Overlap(0, 0)".to_string()));
+        assert_eq!((found[1].0, found[1].1), (2, 0));
+        assert!(found[1].2.starts_with("Unreadable(Decompression("), "{}", found[1].2);
+        assert_eq!(found[2], (3, 0, "Unreadable(Truncated)".to_string()));
+        assert_eq!(found[3], (4, 0, "Unreadable(BadLength(5000))".to_string()));
+        assert_eq!(found[4], (5, 0, "Unreadable(BadOffset(1))".to_string()));
+
+        let out = dir.join("repaired.mca");
+        let problems_found = check(&region).unwrap();
+        assert_eq!(repair(&region, &problems_found, &out).unwrap(), 4);
+        let repaired = Region::open(&out).unwrap();
+        assert_eq!(problems(&repaired), []);
+        let kept: Vec<_> = repaired.chunks().map(|info| {
+            let info = info.unwrap();
+            (info.x, info.timestamp)
+        }).collect();
+        assert_eq!(kept, [(0, 1000), (1, 1001), (6, 1006)]);
+        // The overlapping chunk keeps the data it had, in sectors of its own.
+        assert_eq!(repaired.chunk_raw(0, 0).unwrap().unwrap().1, first);
+        assert_eq!(repaired.chunk_raw(1, 0).unwrap().unwrap().1, first);
+        assert_eq!(repaired.chunk_raw(6, 0).unwrap().unwrap().1, last);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
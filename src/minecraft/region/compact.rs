@@ -0,0 +1,193 @@
+//! Rewriting a region with its chunks packed together, optionally in a
+//! different compression.
+//!
+//! Minecraft only ever appends to a region or reuses a gap that fits, so
+//! the sectors of moved and deleted chunks add up over time.
+
+use std::fs;
+use std::path::Path;
+
+use flate2;
+
+use minecraft::region::{ Compression, Region, RegionError, RegionWriteError, RegionWriter };
+
+#[derive(Debug)]
+pub enum CompactError {
+    /// The region itself can't be read.
+    Region(RegionError),
+    /// A chunk that can't be read, at this position in the region. The
+    /// checker can drop it first.
+    Chunk(u8, u8, RegionError),
+    Write(RegionWriteError),
+}
+
+impl From<RegionWriteError> for CompactError {
+    fn from(err: RegionWriteError) -> CompactError { CompactError::Write(err) }
+}
+
+/// What compacting a region did.
+#[derive(Copy, Clone, Debug)]
+pub struct Compacted {
+    pub chunks: usize,
+    /// The bytes of the region and its external chunk files, before and
+    /// after.
+    pub before: u64,
+    pub after: u64
+}
+
+impl Compacted {
+    /// How many bytes were saved, which is negative if the new
+    /// compression does worse.
+    pub fn saved(&self) -> i64 {
+        self.before as i64 - self.after as i64
+    }
+}
+
+/// The size of a region file and its external chunks.
+fn stored_len(region: &Region, path: &Path) -> Result<u64, RegionError> {
+    let mut len = try!(fs::metadata(path).map_err(RegionError::Io)).len();
+    for info in region.chunks() {
+        let info = try!(info);
+        if info.external {
+            len += try!(region.external_len(&info));
+        }
+    }
+    Ok(len)
+}
+
+/// Writes the chunks of the region at `path` to `output` one after the
+/// other, replacing whatever was there. `output` may be `path` itself.
+/// With `recompress`, every chunk is decompressed and compressed again in
+/// that compression and at that level; otherwise its data is copied as it
+/// is. Timestamps are kept.
+pub fn compact(path: &Path, output: &Path, recompress: Option<(Compression, flate2::Compression)>)
+               -> Result<Compacted, CompactError> {
+    let region = try!(Region::open(path).map_err(|err| CompactError::Region(RegionError::Io(err))));
+    let before = try!(stored_len(&region, path).map_err(CompactError::Region));
+    let mut writer = try!(RegionWriter::open(output).map_err(RegionWriteError::Io));
+    // Start from nothing, so the chunks get packed from the first sector
+    // and stale external files are removed.
+    for i in 0..1024 {
+        writer.delete_chunk((i % 32) as u8, (i / 32) as u8);
+    }
+
+    let mut chunks = 0;
+    for i in 0..1024 {
+        let (x, z) = ((i % 32) as u8, (i / 32) as u8);
+        let chunk_err = |err| CompactError::Chunk(x, z, err);
+        let info = match try!(region.chunk_info(x, z).map_err(&chunk_err)) {
+            Some(info) => info,
+            None => continue
+        };
+        let (compression, data) = try!(region.read_raw(&info).map_err(&chunk_err));
+        match recompress {
+            Some((new, level)) => {
+                let data = try!(compression.decompress(&data)
+                                           .map_err(|err| chunk_err(RegionError::Decompression(err))));
+                let data = try!(new.compress_with(&data, level).map_err(RegionWriteError::Io));
+                try!(writer.write_raw(x, z, new, &data));
+            }
+            None => try!(writer.write_raw(x, z, compression, &data))
+        }
+        writer.set_timestamp(x, z, info.timestamp);
+        chunks += 1;
+    }
+    // A file that is still mapped can't be replaced on Windows.
+    drop(region);
+    try!(writer.save().map_err(RegionWriteError::Io));
+
+    let after = try!(Region::open(output).map_err(RegionError::Io)
+                                          .and_then(|region| stored_len(&region, output))
+                                          .map_err(CompactError::Region));
+    Ok(Compacted {
+        chunks: chunks,
+        before: before,
+        after: after
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    use flate2;
+
+    use minecraft::region::{ Compression, Region, RegionWriter, SECTOR };
+    use minecraft::region::tests::temp_dir;
+    use super::*;
+
+    fn filler(len: usize, seed: usize) -> Vec<u8> {
+        (0..len).map(|i| ((i + seed) * 7919 % 251) as u8).collect()
+    }
+
+    /// The chunks of a region with their data and timestamps, in order.
+    fn contents(path: &Path) -> Vec<(u8, Compression, Vec<u8>, u32)> {
+        let region = Region::open(path).unwrap();
+        region.chunks().map(|info| {
+            let info = info.unwrap();
+            (info.x, info.compression.unwrap(), region.read_data(&info).unwrap(), info.timestamp)
+        }).collect()
+    }
+
+    #[test]
+    fn holes_are_closed_in_place() {
+        let dir = temp_dir("compact-holes");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        for x in 0..8 {
+            writer.write_raw(x, 0, Compression::None, &filler(3 * SECTOR, x as usize)).unwrap();
+            writer.set_timestamp(x, 0, 500 + x as u32);
+        }
+        writer.save().unwrap();
+        let mut writer = RegionWriter::open(&path).unwrap();
+        for x in 0..4 {
+            writer.delete_chunk(x * 2, 0);
+        }
+        writer.save().unwrap();
+        let kept = contents(&path);
+        assert_eq!(kept.len(), 4);
+        let len = fs::metadata(&path).unwrap().len();
+
+        let compacted = compact(&path, &path, None).unwrap();
+        assert_eq!(compacted.chunks, 4);
+        assert_eq!(compacted.before, len);
+        assert_eq!(compacted.after, fs::metadata(&path).unwrap().len());
+        assert!(compacted.after < len);
+        assert_eq!(compacted.after, (2 + 4 * 4) as u64 * SECTOR as u64);
+        assert_eq!(compacted.saved(), (len - compacted.after) as i64);
+        assert_eq!(contents(&path), kept);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn recompressing_keeps_the_data() {
+        let dir = temp_dir("compact-recompress");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        for x in 0..4 {
+            let data = Compression::Gzip.compress(&filler(10000, x as usize)).unwrap();
+            writer.write_raw(x, 1, Compression::Gzip, &data).unwrap();
+            writer.set_timestamp(x, 1, 900 + x as u32);
+        }
+        writer.save().unwrap();
+        let original = contents(&path);
+
+        let raw = dir.join("raw.mca");
+        compact(&path, &raw, Some((Compression::None, flate2::Compression::Default))).unwrap();
+        let zlib = dir.join("zlib.mca");
+        let compacted = compact(&raw, &zlib, Some((Compression::Zlib, flate2::Compression::Best))).unwrap();
+        assert_eq!(compacted.chunks, 4);
+        assert!(compacted.saved() > 0);
+
+        for &(ref out, compression) in &[(raw, Compression::None), (zlib, Compression::Zlib)] {
+            let chunks = contents(out);
+            assert_eq!(chunks.len(), original.len());
+            for (chunk, original) in chunks.iter().zip(&original) {
+                assert_eq!(chunk.1, compression);
+                assert_eq!((chunk.0, &chunk.2, chunk.3), (original.0, &original.2, original.3));
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
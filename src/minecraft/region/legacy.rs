@@ -0,0 +1,139 @@
+//! Chunks from before Anvil: McRegion region files, and the separate chunk
+//! files of Alpha worlds. Both store a chunk as one 128 block tall column
+//! in XZY order, without biomes.
+
+use std::fs::File;
+use std::io::{ self, Read };
+use std::path::{ Path, PathBuf };
+
+use flate2::read::GzDecoder;
+
+use chunk::{ BiomeId, BlockState, Chunk, ChunkColumn, LightLevel, SIZE };
+use minecraft::nbt::slice;
+use minecraft::region::{ array_16x16x16, byte_array, nibble, ChunkSource, RegionError,
+    DEFAULT_BIOME };
+
+const HEIGHT: usize = 128;
+
+/// Builds a chunk column from the NBT of a McRegion or Alpha chunk.
+pub fn read_chunk_column(data: &[u8]) -> Result<ChunkColumn, RegionError> {
+    let (_, root) = try!(slice::root(data));
+    let root = try!(root.as_compound().ok_or(RegionError::Schema("root")));
+    let level = try!(try!(root.get("Level")).and_then(|level| level.as_compound())
+                                            .ok_or(RegionError::Schema("Level")));
+    let volume = SIZE * SIZE * HEIGHT;
+    let mut blocks = None;
+    let mut blocks_data = None;
+    let mut block_light = None;
+    let mut sky_light = None;
+    for entry in level.iter() {
+        let (name, value) = try!(entry);
+        match &*name.to_str() {
+            "Blocks" => blocks = Some(try!(byte_array(value, volume, "Blocks"))),
+            "Data" => blocks_data = Some(try!(byte_array(value, volume / 2, "Data"))),
+            "BlockLight" => block_light = Some(try!(byte_array(value, volume / 2, "BlockLight"))),
+            "SkyLight" => sky_light = Some(try!(byte_array(value, volume / 2, "SkyLight"))),
+            _ => {}
+        }
+    }
+    let blocks = try!(blocks.ok_or(RegionError::Schema("Blocks")));
+
+    let sections = (HEIGHT / SIZE) as i32;
+    let mut column = ChunkColumn::new(0, sections - 1,
+                                      [[BiomeId { value: DEFAULT_BIOME }; SIZE]; SIZE]);
+    for section in 0..sections {
+        let index = |x: usize, y: usize, z: usize| {
+            (x * SIZE + z) * HEIGHT + section as usize * SIZE + y
+        };
+        // Leave out sections of only air, as Anvil does.
+        let empty = (0..SIZE * SIZE).all(|xz| {
+            let start = xz * HEIGHT + section as usize * SIZE;
+            blocks[start..start + SIZE].iter().all(|&block| block == 0)
+        });
+        if empty {
+            continue;
+        }
+        column.set_chunk(section, Chunk {
+            blocks: array_16x16x16(|x, y, z| {
+                let i = index(x, y, z);
+                let data = blocks_data.map_or(0, |data| nibble(data, i));
+                BlockState {
+                    value: ((blocks[i] as u16) << 4) | (data as u16)
+                }
+            }),
+            light_levels: array_16x16x16(|x, y, z| {
+                let i = index(x, y, z);
+                let block = block_light.map_or(0, |light| nibble(light, i));
+                let sky = sky_light.map_or(0x0f, |light| nibble(light, i));
+                LightLevel {
+                    value: block | (sky << 4)
+                }
+            }),
+        });
+    }
+    Ok(column)
+}
+
+/// The chunk files of an Alpha world that make up one region, so they can
+/// be read like a `Region`. Each chunk is a gzipped file of its own, in
+/// folders named after its coordinates modulo 64, all in base 36.
+pub struct AlphaRegion {
+    dimension: PathBuf,
+    x: i32,
+    z: i32
+}
+
+impl AlphaRegion {
+    pub fn new(dimension: &Path, x: i32, z: i32) -> AlphaRegion {
+        AlphaRegion {
+            dimension: dimension.to_path_buf(),
+            x: x,
+            z: z
+        }
+    }
+
+    /// The file of chunk `x`, `z` in the world.
+    pub fn chunk_path(&self, x: i32, z: i32) -> PathBuf {
+        self.dimension.join(base36(x & 63)).join(base36(z & 63))
+                      .join(format!("c.{}.{}.dat", base36(x), base36(z)))
+    }
+
+    /// Reads a chunk column, or `None` if it hasn't been generated.
+    pub fn get_chunk_column(&self, x: u8, z: u8) -> Result<Option<ChunkColumn>, RegionError> {
+        let path = self.chunk_path(self.x * 32 + (x % 32) as i32, self.z * 32 + (z % 32) as i32);
+        let file = match File::open(path) {
+            Ok(file) => file,
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(RegionError::Io(err))
+        };
+        let mut data = vec![];
+        try!(GzDecoder::new(file).and_then(|mut r| r.read_to_end(&mut data))
+                                 .map_err(RegionError::Decompression));
+        read_chunk_column(&data).map(Some)
+    }
+}
+
+impl ChunkSource for AlphaRegion {
+    fn get_chunk_column(&self, x: u8, z: u8) -> Result<Option<ChunkColumn>, RegionError> {
+        AlphaRegion::get_chunk_column(self, x, z)
+    }
+}
+
+/// Formats a number like Java's `Integer.toString(n, 36)`.
+fn base36(n: i32) -> String {
+    const DIGITS: &'static [u8] = b"0123456789abcdefghijklmnopqrstuvwxyz";
+    let mut digits = vec![];
+    let mut rest = (n as i64).abs();
+    loop {
+        digits.push(DIGITS[(rest % 36) as usize]);
+        rest /= 36;
+        if rest == 0 {
+            break;
+        }
+    }
+    if n < 0 {
+        digits.push(b'-');
+    }
+    digits.reverse();
+    String::from_utf8(digits).unwrap()
+}
@@ -0,0 +1,358 @@
+//! The LZ4 streams of region compression type 4, as written by lz4-java's
+//! `LZ4BlockOutputStream`: a sequence of blocks, each with a header giving
+//! its method, sizes and checksum, ending with an empty block.
+
+use std::io;
+
+use byteorder::{ ByteOrder, LittleEndian };
+
+const MAGIC: &'static [u8] = b"LZ4Block";
+const HEADER_LEN: usize = 8 + 1 + 4 + 4 + 4;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+/// The block size lz4-java uses by default, 64 KiB.
+const BLOCK_SIZE: usize = 1 << 16;
+/// The low bits of the header's method byte, log2(BLOCK_SIZE) - 10.
+const BLOCK_SIZE_LEVEL: u8 = 6;
+/// The seed of the xxHash32 checksum of each block.
+const CHECKSUM_SEED: u32 = 0x9747b28c;
+
+/// A match can't start in the last 12 bytes, or end in the last 5.
+const MATCH_START_LIMIT: usize = 12;
+const LAST_LITERALS: usize = 5;
+const MIN_MATCH: usize = 4;
+
+fn invalid(msg: &'static str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub fn decompress(mut data: &[u8]) -> io::Result<Vec<u8>> {
+    let mut out = vec![];
+    while !data.is_empty() {
+        if data.len() < HEADER_LEN || &data[..8] != MAGIC {
+            return Err(invalid("bad LZ4 block header"));
+        }
+        let method = data[8] & 0xf0;
+        let compressed_len = LittleEndian::read_u32(&data[9..]) as usize;
+        let len = LittleEndian::read_u32(&data[13..]) as usize;
+        let checksum = LittleEndian::read_u32(&data[17..]);
+        data = &data[HEADER_LEN..];
+        if len == 0 {
+            break;
+        }
+        if data.len() < compressed_len {
+            return Err(invalid("truncated LZ4 block"));
+        }
+        let start = out.len();
+        match method {
+            METHOD_RAW if compressed_len == len => out.extend_from_slice(&data[..len]),
+            METHOD_LZ4 => try!(decompress_block(&data[..compressed_len], &mut out)),
+            _ => return Err(invalid("bad LZ4 block method"))
+        }
+        if out.len() - start != len {
+            return Err(invalid("LZ4 block has the wrong length"));
+        }
+        if xxhash32(&out[start..], CHECKSUM_SEED) & 0x0fffffff != checksum {
+            return Err(invalid("LZ4 block checksum mismatch"));
+        }
+        data = &data[compressed_len..];
+    }
+    Ok(out)
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    for block in data.chunks(BLOCK_SIZE) {
+        let start = out.len();
+        out.extend_from_slice(&[0; HEADER_LEN]);
+        compress_block(block, &mut out);
+        let compressed_len = out.len() - start - HEADER_LEN;
+        // Keep blocks that don't shrink as they are.
+        let method = if compressed_len < block.len() {
+            METHOD_LZ4
+        } else {
+            out.truncate(start + HEADER_LEN);
+            out.extend_from_slice(block);
+            METHOD_RAW
+        };
+        let header = &mut out[start..start + HEADER_LEN];
+        header[..8].copy_from_slice(MAGIC);
+        header[8] = method | BLOCK_SIZE_LEVEL;
+        let compressed_len = if method == METHOD_RAW { block.len() } else { compressed_len };
+        LittleEndian::write_u32(&mut header[9..], compressed_len as u32);
+        LittleEndian::write_u32(&mut header[13..], block.len() as u32);
+        LittleEndian::write_u32(&mut header[17..], xxhash32(block, CHECKSUM_SEED) & 0x0fffffff);
+    }
+    // The end of the stream is marked by an empty raw block.
+    out.extend_from_slice(MAGIC);
+    out.push(METHOD_RAW | BLOCK_SIZE_LEVEL);
+    out.extend_from_slice(&[0; 12]);
+    out
+}
+
+/// Reads a length continued in bytes of 255, as used for the literal and
+/// match lengths of a sequence.
+fn read_length(data: &[u8], i: &mut usize, mut len: usize) -> io::Result<usize> {
+    if len == 15 {
+        loop {
+            let byte = *try!(data.get(*i).ok_or(invalid("truncated LZ4 length")));
+            *i += 1;
+            len += byte as usize;
+            if byte != 255 {
+                break;
+            }
+        }
+    }
+    Ok(len)
+}
+
+fn decompress_block(data: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+    let start = out.len();
+    let mut i = 0;
+    while i < data.len() {
+        let token = data[i];
+        i += 1;
+        let literals = try!(read_length(data, &mut i, (token >> 4) as usize));
+        if data.len() < i + literals {
+            return Err(invalid("truncated LZ4 literals"));
+        }
+        out.extend_from_slice(&data[i..i + literals]);
+        i += literals;
+        // The last sequence has no match.
+        if i == data.len() {
+            break;
+        }
+        if data.len() < i + 2 {
+            return Err(invalid("truncated LZ4 offset"));
+        }
+        let offset = LittleEndian::read_u16(&data[i..]) as usize;
+        i += 2;
+        if offset == 0 || offset > out.len() - start {
+            return Err(invalid("bad LZ4 offset"));
+        }
+        let len = try!(read_length(data, &mut i, (token & 0x0f) as usize)) + MIN_MATCH;
+        // Matches may overlap what they copy, so go byte by byte.
+        let from = out.len() - offset;
+        for j in 0..len {
+            let byte = out[from + j];
+            out.push(byte);
+        }
+    }
+    Ok(())
+}
+
+fn write_length(out: &mut Vec<u8>, mut len: usize) {
+    while len >= 255 {
+        out.push(255);
+        len -= 255;
+    }
+    out.push(len as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], offset: usize, len: usize) {
+    let match_len = len.saturating_sub(MIN_MATCH);
+    let token = (::std::cmp::min(literals.len(), 15) << 4) as u8
+              | if len == 0 { 0 } else { ::std::cmp::min(match_len, 15) as u8 };
+    out.push(token);
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+    if len != 0 {
+        out.push(offset as u8);
+        out.push((offset >> 8) as u8);
+        if match_len >= 15 {
+            write_length(out, match_len - 15);
+        }
+    }
+}
+
+/// Compresses greedily, finding matches through a small hash table of the
+/// last position of each 4-byte sequence.
+fn compress_block(data: &[u8], out: &mut Vec<u8>) {
+    let mut table = vec![0usize; 1 << 12];
+    let mut anchor = 0;
+    let mut i = 0;
+    while i + MATCH_START_LIMIT <= data.len() {
+        let sequence = LittleEndian::read_u32(&data[i..]);
+        let hash = (sequence.wrapping_mul(2654435761) >> 20) as usize;
+        // Positions are stored plus one, so that zero means none.
+        let candidate = table[hash];
+        table[hash] = i + 1;
+        if candidate != 0 && i + 1 - candidate <= 0xffff
+           && &data[candidate - 1..candidate + 3] == &data[i..i + 4] {
+            let from = candidate - 1;
+            let mut len = MIN_MATCH;
+            while i + len < data.len() - LAST_LITERALS && data[from + len] == data[i + len] {
+                len += 1;
+            }
+            write_sequence(out, &data[anchor..i], i - from, len);
+            i += len;
+            anchor = i;
+        } else {
+            i += 1;
+        }
+    }
+    write_sequence(out, &data[anchor..], 0, 0);
+}
+
+/// The 32-bit xxHash of `data`.
+fn xxhash32(data: &[u8], seed: u32) -> u32 {
+    const P1: u32 = 2654435761;
+    const P2: u32 = 2246822519;
+    const P3: u32 = 3266489917;
+    const P4: u32 = 668265263;
+    const P5: u32 = 374761393;
+    let round = |acc: u32, input: u32| {
+        acc.wrapping_add(input.wrapping_mul(P2)).rotate_left(13).wrapping_mul(P1)
+    };
+
+    let mut i = 0;
+    let mut hash = if data.len() >= 16 {
+        let mut v = [
+            seed.wrapping_add(P1).wrapping_add(P2),
+            seed.wrapping_add(P2),
+            seed,
+            seed.wrapping_sub(P1)
+        ];
+        while i + 16 <= data.len() {
+            for (j, lane) in v.iter_mut().enumerate() {
+                *lane = round(*lane, LittleEndian::read_u32(&data[i + j * 4..]));
+            }
+            i += 16;
+        }
+        v[0].rotate_left(1).wrapping_add(v[1].rotate_left(7))
+            .wrapping_add(v[2].rotate_left(12)).wrapping_add(v[3].rotate_left(18))
+    } else {
+        seed.wrapping_add(P5)
+    };
+    hash = hash.wrapping_add(data.len() as u32);
+    while i + 4 <= data.len() {
+        hash = hash.wrapping_add(LittleEndian::read_u32(&data[i..]).wrapping_mul(P3));
+        hash = hash.rotate_left(17).wrapping_mul(P4);
+        i += 4;
+    }
+    for &byte in &data[i..] {
+        hash = hash.wrapping_add((byte as u32).wrapping_mul(P5));
+        hash = hash.rotate_left(11).wrapping_mul(P1);
+    }
+    hash ^= hash >> 15;
+    hash = hash.wrapping_mul(P2);
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(P3);
+    hash ^ (hash >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use minecraft::region::tests::{ inflate, CHUNK_MCREGION };
+    use super::*;
+
+    /// The McRegion fixture chunk in lz4-java's framing, in two blocks
+    /// compressed by the reference LZ4 library.
+    const CHUNK_MCREGION_LZ4: &'static [u8] =
+        include_bytes!("../../../fixtures/chunk_mcregion.lz4");
+
+    /// Bytes that don't compress, from a linear congruential generator.
+    fn noise(len: usize) -> Vec<u8> {
+        let mut seed = 12345u32;
+        (0..len).map(|_| {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            (seed >> 16) as u8
+        }).collect()
+    }
+
+    fn assert_round_trip(data: &[u8]) -> Vec<u8> {
+        let compressed = compress(data);
+        assert_eq!(decompress(&compressed).unwrap(), data);
+        compressed
+    }
+
+    #[test]
+    fn round_trips() {
+        // Only the end of the stream is left.
+        assert_eq!(assert_round_trip(&[]).len(), HEADER_LEN);
+        assert_round_trip(b"a");
+        assert_round_trip(b"short, under the twelve bytes a match needs at the end");
+
+        let repetitive: Vec<u8> = (0..50000).map(|i| (i % 7 * 3 + i / 1000 % 5) as u8).collect();
+        let compressed = assert_round_trip(&repetitive);
+        assert_eq!(compressed[8], METHOD_LZ4 | BLOCK_SIZE_LEVEL);
+        assert!(compressed.len() < repetitive.len() / 10);
+        assert_round_trip(&vec![0; 1000]);
+
+        let incompressible = noise(5000);
+        let compressed = assert_round_trip(&incompressible);
+        assert_eq!(compressed[8], METHOD_RAW | BLOCK_SIZE_LEVEL);
+        assert_eq!(compressed.len(), incompressible.len() + 2 * HEADER_LEN);
+    }
+
+    #[test]
+    fn long_inputs_are_split_into_blocks() {
+        let mut data: Vec<u8> = (0..150000).map(|i| (i / 300) as u8).collect();
+        data.extend(noise(BLOCK_SIZE));
+        data.extend(vec![7; BLOCK_SIZE + 1]);
+        let compressed = assert_round_trip(&data);
+        let mut blocks = 0;
+        let mut rest = &compressed[..];
+        while rest.len() > HEADER_LEN {
+            let len = LittleEndian::read_u32(&rest[13..]) as usize;
+            assert!(len <= BLOCK_SIZE);
+            rest = &rest[HEADER_LEN + LittleEndian::read_u32(&rest[9..]) as usize..];
+            blocks += 1;
+        }
+        assert_eq!(blocks, (data.len() + BLOCK_SIZE - 1) / BLOCK_SIZE);
+    }
+
+    #[test]
+    fn reads_lz4_java_streams() {
+        let nbt = inflate(CHUNK_MCREGION);
+        assert!(nbt.len() > BLOCK_SIZE);
+        assert_eq!(decompress(CHUNK_MCREGION_LZ4).unwrap(), nbt);
+    }
+
+    #[test]
+    fn xxhash32_test_vectors() {
+        assert_eq!(xxhash32(b"", 0), 0x02cc5d05);
+        assert_eq!(xxhash32(b"abc", 0), 0x32d153ff);
+        assert_eq!(xxhash32(b"Nobody inspects the spammish repetition", 0), 0xe2293b2f);
+    }
+
+    #[test]
+    fn bad_checksums_are_rejected() {
+        let mut stream = compress(b"some data that will be checked");
+        stream[17] ^= 1;
+        assert!(decompress(&stream).is_err());
+
+        let mut stream = compress(&vec![1; 100]);
+        let last = stream.len() - HEADER_LEN - 1;
+        stream[last] ^= 1;
+        assert!(decompress(&stream).is_err());
+    }
+
+    #[test]
+    fn bad_offsets_are_rejected() {
+        // One literal, then a match at offset 0.
+        assert!(decompress_block(&[0x10, b'a', 0, 0], &mut vec![]).is_err());
+        // An offset before the start of the output.
+        assert!(decompress_block(&[0x10, b'a', 2, 0], &mut vec![]).is_err());
+        // Or before the start of the block, as blocks are independent.
+        assert!(decompress_block(&[0x10, b'a', 2, 0], &mut vec![1, 2, 3]).is_err());
+
+        let mut out = vec![1, 2, 3];
+        decompress_block(&[0x10, b'a', 1, 0, 0x10, b'b'], &mut out).unwrap();
+        assert_eq!(out, b"\x01\x02\x03aaaaab");
+    }
+
+    #[test]
+    fn truncated_input_is_rejected() {
+        let stream = compress(&(0..1000).map(|i| (i % 10) as u8).collect::<Vec<_>>());
+        for &len in &[1, HEADER_LEN - 1, HEADER_LEN + 3, stream.len() - HEADER_LEN - 1] {
+            assert!(decompress(&stream[..len]).is_err(), "{} bytes", len);
+        }
+        // Literals, an offset and a length that run past the block.
+        assert!(decompress_block(&[0x30, b'a'], &mut vec![]).is_err());
+        assert!(decompress_block(&[0x10, b'a', 1], &mut vec![]).is_err());
+        assert!(decompress_block(&[0xf0], &mut vec![]).is_err());
+    }
+}
@@ -0,0 +1,831 @@
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use byteorder::{ BigEndian, ByteOrder };
+use flate2;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use memmap::{Mmap, Protection};
+
+use array::*;
+use chunk::{
+    BiomeId,
+    BlockState,
+    Chunk,
+    ChunkColumn,
+    LightLevel,
+    SIZE
+};
+use minecraft::nbt::NbtReaderError;
+use minecraft::nbt::slice::{ self, Value };
+
+pub use self::legacy::AlphaRegion;
+pub use self::writer::{ RegionWriteError, RegionWriter };
+
+pub mod check;
+pub mod compact;
+mod legacy;
+mod lz4;
+pub mod palette;
+pub mod trim;
+pub mod writer;
+
+const SECTOR: usize = 4096;
+
+/// Biomes are plains where a chunk doesn't say.
+const DEFAULT_BIOME: u8 = 1;
+
+pub struct Region {
+    mmap: Mmap,
+    path: PathBuf,
+    format: Format,
+}
+
+/// The chunk format inside a region file, which is told by its extension.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Format {
+    /// `.mca` files, since 1.2.
+    Anvil,
+    /// `.mcr` files, from Beta 1.3 to 1.1, with 128 block tall chunks.
+    McRegion,
+}
+
+/// Anything chunk columns can be read from by their position in a 32x32
+/// region, whatever the format of the world.
+pub trait ChunkSource {
+    fn get_chunk_column(&self, x: u8, z: u8) -> Result<Option<ChunkColumn>, RegionError>;
+}
+
+/// Opens region `x`, `z` of a dimension folder (the world itself, or e.g.
+/// `DIM-1`) in the newest format it has: an Anvil region, a McRegion
+/// region, or else the chunk files of an Alpha world. `None` if the
+/// dimension has regions, but not this one.
+pub fn open(dimension: &Path, x: i32, z: i32) -> io::Result<Option<Box<ChunkSource>>> {
+    let regions = dimension.join("region");
+    for extension in &["mca", "mcr"] {
+        let path = regions.join(format!("r.{}.{}.{}", x, z, extension));
+        if path.exists() {
+            return Ok(Some(Box::new(try!(Region::open(&path)))));
+        }
+    }
+    if regions.is_dir() {
+        return Ok(None);
+    }
+    Ok(Some(Box::new(AlphaRegion::new(dimension, x, z))))
+}
+
+/// Why a chunk couldn't be read. The rest of the region may still be fine.
+#[derive(Debug)]
+pub enum RegionError {
+    /// The chunk's own file couldn't be read.
+    Io(io::Error),
+    /// The file ends before the header or the chunk's data does.
+    Truncated,
+    /// The chunk's location points into the header.
+    BadOffset(usize),
+    /// The chunk's stored length is zero or more than its sectors hold.
+    BadLength(usize),
+    UnknownCompression(u8),
+    /// The chunk's data doesn't decompress.
+    Decompression(io::Error),
+    Nbt(NbtReaderError),
+    /// The chunk is NBT, but a tag is missing or has the wrong type or
+    /// size. Carries the name of the tag.
+    Schema(&'static str),
+}
+
+impl From<NbtReaderError> for RegionError {
+    fn from(err: NbtReaderError) -> RegionError { RegionError::Nbt(err) }
+}
+
+/// How a chunk is compressed, as stored in the byte before its data.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Compression {
+    Gzip = 1,
+    Zlib = 2,
+    /// Since 1.15.1.
+    None = 3,
+    /// lz4-java's block stream, since 24w04a.
+    Lz4 = 4,
+}
+
+/// Set in the compression byte of a chunk too big for the region, whose
+/// data is in a `c.X.Z.mcc` file next to it instead.
+pub const EXTERNAL: u8 = 0x80;
+
+impl Compression {
+    pub fn from_id(id: u8) -> Option<Compression> {
+        match id {
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Zlib),
+            3 => Some(Compression::None),
+            4 => Some(Compression::Lz4),
+            _ => None
+        }
+    }
+
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.compress_with(data, flate2::Compression::Default)
+    }
+
+    /// Compresses at a given level, which only gzip and zlib have.
+    pub fn compress_with(self, data: &[u8], level: flate2::Compression) -> io::Result<Vec<u8>> {
+        match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(vec![], level);
+                try!(encoder.write_all(data));
+                encoder.finish()
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(vec![], level);
+                try!(encoder.write_all(data));
+                encoder.finish()
+            }
+            Compression::None => Ok(data.to_vec()),
+            Compression::Lz4 => Ok(lz4::compress(data))
+        }
+    }
+
+    pub fn decompress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut out = vec![];
+        match self {
+            Compression::Gzip => {
+                try!(GzDecoder::new(data).and_then(|mut r| r.read_to_end(&mut out)));
+            }
+            Compression::Zlib => { try!(ZlibDecoder::new(data).read_to_end(&mut out)); }
+            Compression::None => out.extend_from_slice(data),
+            Compression::Lz4 => out = try!(lz4::decompress(data))
+        }
+        Ok(out)
+    }
+}
+
+/// The region coordinates in a region file name like `r.-1.2.mca`.
+pub fn region_position(path: &Path) -> Option<(i32, i32)> {
+    let name = match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name,
+        None => return None
+    };
+    let parts: Vec<&str> = name.split('.').collect();
+    match (parts.len(), parts.first()) {
+        (4, Some(&"r")) => match (parts[1].parse(), parts[2].parse()) {
+            (Ok(x), Ok(z)) => Some((x, z)),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+/// Where a region keeps chunk `x`, `z` (of the region) if it is too big.
+pub fn external_path(region: &Path, x: u8, z: u8) -> Option<PathBuf> {
+    region_position(region).map(|(rx, rz)| {
+        let (x, z) = (rx * 32 + (x % 32) as i32, rz * 32 + (z % 32) as i32);
+        region.with_file_name(format!("c.{}.{}.mcc", x, z))
+    })
+}
+
+/// The 4-bit value at `i` of a nibble array.
+fn nibble(array: &[u8], i: usize) -> u8 {
+    (array[i >> 1] >> ((i & 1) * 4)) & 0x0f
+}
+
+/// A byte array tag that must have exactly `len` entries.
+fn byte_array<'a>(value: Value<'a>, len: usize, name: &'static str)
+                  -> Result<&'a [u8], RegionError> {
+    match value.as_bytearray() {
+        Some(array) if array.len() == len => Ok(array),
+        _ => Err(RegionError::Schema(name))
+    }
+}
+
+fn array_16x16x16<T, F>(mut f: F) -> [[[T; SIZE]; SIZE]; SIZE]
+    where F: FnMut(usize, usize, usize) -> T
+{
+    Array::from_fn(|y| -> [[T; SIZE]; SIZE] {
+        Array::from_fn(|z| -> [T; 16] {
+            Array::from_fn(|x| f(x, y, z))
+        })
+    })
+}
+
+impl Region {
+    pub fn open(filename: &Path) -> io::Result<Region> {
+        let mmap = try!(Mmap::open_path(filename, Protection::Read));
+        let format = match filename.extension().and_then(|ext| ext.to_str()) {
+            Some("mcr") => Format::McRegion,
+            _ => Format::Anvil
+        };
+        Ok(Region{mmap: mmap, path: filename.to_path_buf(), format: format})
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    fn as_slice(&self) -> &[u8] {
+        unsafe {
+            self.mmap.as_slice()
+        }
+    }
+
+    /// Reads a chunk column, or `None` if it hasn't been generated.
+    pub fn get_chunk_column(&self, x: u8, z: u8) -> Result<Option<ChunkColumn>, RegionError> {
+        let data = match try!(self.chunk_data(x, z)) {
+            Some(data) => data,
+            None => return Ok(None)
+        };
+        match self.format {
+            Format::Anvil => read_chunk_column(&data).map(Some),
+            Format::McRegion => legacy::read_chunk_column(&data).map(Some)
+        }
+    }
+
+    /// Where and how a chunk is stored, or `None` if it hasn't been
+    /// generated. Only the tables and the chunk's header are read.
+    pub fn chunk_info(&self, x: u8, z: u8) -> Result<Option<ChunkInfo>, RegionError> {
+        let file = self.as_slice();
+        if file.len() < 2 * SECTOR {
+            return Err(RegionError::Truncated);
+        }
+        let (x, z) = (x % 32, z % 32);
+        let i = 4 * (x as usize + z as usize * 32);
+        let start = ((file[i] as usize) << 16)
+                  | ((file[i + 1] as usize) << 8)
+                  | (file[i + 2] as usize);
+        let num = file[i + 3] as usize;
+        if start == 0 && num == 0 { return Ok(None); }
+        if start < 2 {
+            return Err(RegionError::BadOffset(start));
+        }
+
+        let offset = start * SECTOR;
+        if file.len() < offset + 5 {
+            return Err(RegionError::Truncated);
+        }
+        let len = BigEndian::read_u32(&file[offset..]) as usize;
+        if len == 0 || len + 4 > num * SECTOR {
+            return Err(RegionError::BadLength(len));
+        }
+        // The last sector isn't always padded out, so only the data
+        // itself has to be there.
+        if file.len() < offset + 4 + len {
+            return Err(RegionError::Truncated);
+        }
+        let id = file[offset + 4];
+        let external = id & EXTERNAL != 0;
+        Ok(Some(ChunkInfo {
+            x: x,
+            z: z,
+            offset: start,
+            sectors: num,
+            compressed_len: if external { None } else { Some(len - 1) },
+            compression: Compression::from_id(id & !EXTERNAL),
+            external: external,
+            timestamp: BigEndian::read_u32(&file[SECTOR + i..]),
+            id: id
+        }))
+    }
+
+    /// Every chunk in the region, in the order of the tables (X first).
+    pub fn chunks(&self) -> Chunks {
+        Chunks {
+            region: self,
+            i: 0
+        }
+    }
+
+    fn external_path(&self, x: u8, z: u8) -> Result<PathBuf, RegionError> {
+        external_path(&self.path, x, z).ok_or_else(|| {
+            RegionError::Io(io::Error::new(io::ErrorKind::NotFound,
+                                           "external chunk of an unnamed region"))
+        })
+    }
+
+    /// A chunk's data as stored, still compressed, from the region or its
+    /// own file.
+    pub fn chunk_raw(&self, x: u8, z: u8) -> Result<Option<(Compression, Vec<u8>)>, RegionError> {
+        match try!(self.chunk_info(x, z)) {
+            Some(info) => self.read_raw(&info).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// The data of a chunk found with `chunk_info`, like `chunk_raw`.
+    pub fn read_raw(&self, info: &ChunkInfo) -> Result<(Compression, Vec<u8>), RegionError> {
+        let compression = try!(info.compression.ok_or(RegionError::UnknownCompression(info.id)));
+        let data = match info.compressed_len {
+            Some(len) => {
+                let offset = info.offset * SECTOR + 5;
+                self.as_slice()[offset..offset + len].to_vec()
+            }
+            None => {
+                let mut external = vec![];
+                try!(File::open(try!(self.external_path(info.x, info.z)))
+                         .and_then(|mut f| f.read_to_end(&mut external))
+                         .map_err(RegionError::Io));
+                external
+            }
+        };
+        Ok((compression, data))
+    }
+
+    /// The size of the file of an external chunk, which `chunk_info`
+    /// doesn't look up so that listing chunks only reads the region.
+    pub fn external_len(&self, info: &ChunkInfo) -> Result<u64, RegionError> {
+        let path = try!(self.external_path(info.x, info.z));
+        Ok(try!(fs::metadata(path).map_err(RegionError::Io)).len())
+    }
+
+    /// Finds a chunk and decompresses its NBT.
+    fn chunk_data(&self, x: u8, z: u8) -> Result<Option<Vec<u8>>, RegionError> {
+        match try!(self.chunk_info(x, z)) {
+            Some(info) => self.read_data(&info).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// The NBT of a chunk found with `chunk_info`, decompressed.
+    pub fn read_data(&self, info: &ChunkInfo) -> Result<Vec<u8>, RegionError> {
+        let (compression, data) = try!(self.read_raw(info));
+        compression.decompress(&data).map_err(RegionError::Decompression)
+    }
+}
+
+/// Where and how a chunk is stored in its region.
+#[derive(Copy, Clone, Debug)]
+pub struct ChunkInfo {
+    /// The position of the chunk in the region.
+    pub x: u8,
+    pub z: u8,
+    /// The first sector of the chunk.
+    pub offset: usize,
+    pub sectors: usize,
+    /// The size of the compressed data in the region, or `None` if it is
+    /// in a file of its own; see `Region::external_len`.
+    pub compressed_len: Option<usize>,
+    /// `None` for a compression type we don't know.
+    pub compression: Option<Compression>,
+    /// Whether the data is in a `c.X.Z.mcc` file of its own.
+    pub external: bool,
+    /// When the chunk was last saved, in seconds since the UNIX epoch.
+    pub timestamp: u32,
+    /// The compression byte as stored.
+    id: u8
+}
+
+impl ChunkInfo {
+    /// When the chunk was last saved.
+    pub fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.timestamp as u64)
+    }
+}
+
+/// An iterator over the chunks of a region, skipping ungenerated ones.
+pub struct Chunks<'a> {
+    region: &'a Region,
+    i: usize
+}
+
+impl<'a> Iterator for Chunks<'a> {
+    type Item = Result<ChunkInfo, RegionError>;
+
+    fn next(&mut self) -> Option<Result<ChunkInfo, RegionError>> {
+        while self.i < 1024 {
+            let (x, z) = ((self.i % 32) as u8, (self.i / 32) as u8);
+            self.i += 1;
+            match self.region.chunk_info(x, z) {
+                Ok(None) => {}
+                // Without tables there is nothing else to find.
+                Err(RegionError::Truncated) if self.region.as_slice().len() < 2 * SECTOR => {
+                    self.i = 1024;
+                    return Some(Err(RegionError::Truncated));
+                }
+                result => return Some(result.map(Option::unwrap))
+            }
+        }
+        None
+    }
+}
+
+impl ChunkSource for Region {
+    fn get_chunk_column(&self, x: u8, z: u8) -> Result<Option<ChunkColumn>, RegionError> {
+        Region::get_chunk_column(self, x, z)
+    }
+}
+
+/// Builds a chunk from a section's block states and light.
+fn section_chunk(states: &[BlockState], block_light: Option<&[u8]>, sky_light: Option<&[u8]>)
+                 -> Chunk {
+    Chunk {
+        blocks: array_16x16x16(|x, y, z| states[(y * SIZE + z) * SIZE + x]),
+        // Without sky light (e.g. in the Nether) everything is lit as if
+        // by the sky, like in an empty chunk.
+        light_levels: array_16x16x16(|x, y, z| {
+            let i = (y * 16 + z) * 16 + x;
+            let block = block_light.map_or(0, |light| nibble(light, i));
+            let sky = sky_light.map_or(0x0f, |light| nibble(light, i));
+            LightLevel {
+                value: block | (sky << 4)
+            }
+        }),
+    }
+}
+
+/// Builds a chunk column from its NBT, walked in place so that only the
+/// tags we need are looked at.
+fn read_chunk_column(data: &[u8]) -> Result<ChunkColumn, RegionError> {
+    let (_, root) = try!(slice::root(data));
+    let root = try!(root.as_compound().ok_or(RegionError::Schema("root")));
+    // Chunks from before 1.9 have no version.
+    let version = try!(root.get("DataVersion")).and_then(|v| v.as_int()).unwrap_or(0);
+    if version >= palette::SECTIONED_BIOMES {
+        return read_sectioned_chunk_column(root);
+    }
+    let level = try!(try!(root.get("Level")).and_then(|level| level.as_compound())
+                                            .ok_or(RegionError::Schema("Level")));
+    let mut sections = None;
+    let mut biomes = None;
+    for entry in level.iter() {
+        let (name, value) = try!(entry);
+        match &*name.to_str() {
+            "Sections" => {
+                sections = Some(try!(value.as_list().ok_or(RegionError::Schema("Sections"))));
+            }
+            "Biomes" => biomes = Some(value),
+            _ => {}
+        }
+    }
+
+    let mut column = ChunkColumn::new(0, SIZE as i32 - 1,
+                                      [[BiomeId { value: DEFAULT_BIOME }; SIZE]; SIZE]);
+    // A column that is all air may have no sections at all.
+    for section in sections.iter().flat_map(|sections| sections.iter()) {
+        let section = try!(try!(section).as_compound().ok_or(RegionError::Schema("Sections")));
+        let mut y = None;
+        let mut blocks = None;
+        let mut blocks_top = None;
+        let mut blocks_data = None;
+        let mut palette = None;
+        let mut block_states = None;
+        let mut block_light = None;
+        let mut sky_light = None;
+        for entry in section.iter() {
+            let (name, value) = try!(entry);
+            match &*name.to_str() {
+                "Y" => y = value.as_byte(),
+                "Blocks" => blocks = Some(try!(byte_array(value, 4096, "Blocks"))),
+                "Add" => blocks_top = Some(try!(byte_array(value, 2048, "Add"))),
+                "Data" => blocks_data = Some(try!(byte_array(value, 2048, "Data"))),
+                "Palette" => {
+                    palette = Some(try!(value.as_list().ok_or(RegionError::Schema("Palette"))));
+                }
+                "BlockStates" => {
+                    block_states = Some(try!(value.as_long_array()
+                                                  .ok_or(RegionError::Schema("BlockStates"))));
+                }
+                "BlockLight" => block_light = Some(try!(byte_array(value, 2048, "BlockLight"))),
+                "SkyLight" => sky_light = Some(try!(byte_array(value, 2048, "SkyLight"))),
+                _ => {}
+            }
+        }
+        let y = try!(y.ok_or(RegionError::Schema("Y"))) as i32;
+        // Newer versions keep light for the sections just outside the
+        // world, which never have blocks.
+        if y < column.min_section || y > column.max_section {
+            continue;
+        }
+
+        let states = if version < palette::FLATTENING {
+            let blocks = try!(blocks.ok_or(RegionError::Schema("Blocks")));
+            (0..4096).map(|i| {
+                let top = blocks_top.map_or(0, |top| nibble(top, i));
+                let data = blocks_data.map_or(0, |data| nibble(data, i));
+                BlockState {
+                    value: ((blocks[i] as u16) << 4)
+                         | ((top as u16) << 12)
+                         | (data as u16)
+                }
+            }).collect::<Vec<_>>()
+        } else {
+            // Sections with only light have no palette.
+            match palette {
+                Some(palette) => {
+                    let spanning = version < palette::UNSPANNED;
+                    try!(palette::block_states(palette, block_states, spanning))
+                }
+                None => continue
+            }
+        };
+        column.set_chunk(y, section_chunk(&states, block_light, sky_light));
+    }
+
+    // Biomes were bytes until 1.13 made them ints.
+    let mut biome_ids = [DEFAULT_BIOME; SIZE * SIZE];
+    match biomes {
+        None => {}
+        Some(Value::ByteArray(array)) if array.len() == biome_ids.len() => {
+            biome_ids.copy_from_slice(array);
+        }
+        Some(Value::IntArray(array)) if array.len() == biome_ids.len() => {
+            for (id, biome) in biome_ids.iter_mut().zip(array.iter()) {
+                *id = biome as u8;
+            }
+        }
+        // Since 1.15 there is a biome per 4x4x4 cells, YZX. Use the layer
+        // at sea level.
+        Some(Value::IntArray(array)) if array.len() == 1024 => {
+            for (i, id) in biome_ids.iter_mut().enumerate() {
+                let (x, z) = (i % SIZE / 4, i / SIZE / 4);
+                *id = array.get((16 * 4 + z) * 4 + x).unwrap() as u8;
+            }
+        }
+        Some(_) => return Err(RegionError::Schema("Biomes"))
+    }
+    column.biomes = Array::from_fn(|z| -> [BiomeId; SIZE] {
+        Array::from_fn(|x| BiomeId { value: biome_ids[z * SIZE + x] })
+    });
+    Ok(column)
+}
+
+/// The sections of a column since 1.18, when the world grew downwards.
+const SECTIONED_MIN_SECTION: i32 = -4;
+const SECTIONED_MAX_SECTION: i32 = 19;
+
+/// Builds a chunk column in the layout used since 1.18 (21w43a): no
+/// `Level` compound, sections that may be below zero, and block states and
+/// biomes as palettes per section.
+fn read_sectioned_chunk_column(root: slice::CompoundRef) -> Result<ChunkColumn, RegionError> {
+    let mut sections = None;
+    let mut min_section = SECTIONED_MIN_SECTION;
+    for entry in root.iter() {
+        let (name, value) = try!(entry);
+        match &*name.to_str() {
+            "sections" => {
+                sections = Some(try!(value.as_list().ok_or(RegionError::Schema("sections"))));
+            }
+            "yPos" => min_section = try!(value.as_int().ok_or(RegionError::Schema("yPos"))),
+            _ => {}
+        }
+    }
+
+    let max_section = min_section + SECTIONED_MAX_SECTION - SECTIONED_MIN_SECTION;
+    let mut column = ChunkColumn::new(min_section, max_section,
+                                      [[BiomeId { value: DEFAULT_BIOME }; SIZE]; SIZE]);
+    // The biomes of the column are the ones at sea level.
+    let sea_level_section = 64 >> 4;
+    for section in sections.iter().flat_map(|sections| sections.iter()) {
+        let section = try!(try!(section).as_compound().ok_or(RegionError::Schema("sections")));
+        let mut y = None;
+        let mut block_states = None;
+        let mut biomes = None;
+        let mut block_light = None;
+        let mut sky_light = None;
+        for entry in section.iter() {
+            let (name, value) = try!(entry);
+            match &*name.to_str() {
+                "Y" => y = value.as_byte(),
+                "block_states" => {
+                    block_states = Some(try!(value.as_compound()
+                                                  .ok_or(RegionError::Schema("block_states"))));
+                }
+                "biomes" => {
+                    biomes = Some(try!(value.as_compound().ok_or(RegionError::Schema("biomes"))));
+                }
+                "BlockLight" => block_light = Some(try!(byte_array(value, 2048, "BlockLight"))),
+                "SkyLight" => sky_light = Some(try!(byte_array(value, 2048, "SkyLight"))),
+                _ => {}
+            }
+        }
+        let y = try!(y.ok_or(RegionError::Schema("Y"))) as i32;
+        if y < column.min_section || y > column.max_section {
+            continue;
+        }
+
+        if let Some(block_states) = block_states {
+            let (palette, data) = try!(paletted_container(block_states, "block_states"));
+            let states = try!(palette::block_states(palette, data, false));
+            column.set_chunk(y, section_chunk(&states, block_light, sky_light));
+        }
+
+        if let (true, Some(biomes)) = (y == sea_level_section, biomes) {
+            let (palette, data) = try!(paletted_container(biomes, "biomes"));
+            let mut ids = Vec::with_capacity(palette.len());
+            for name in palette.iter() {
+                let name = try!(try!(name).as_str().ok_or(RegionError::Schema("biomes")));
+                ids.push(palette::biome_id(&name.to_str(), DEFAULT_BIOME));
+            }
+            let indices = match data {
+                Some(data) => {
+                    let bits = palette::index_bits(ids.len());
+                    try!(palette::unpack(data, bits, false, 64)
+                             .ok_or(RegionError::Schema("biomes")))
+                }
+                None => vec![0; 64]
+            };
+            // 4x4x4 cells in YZX order; the bottom layer is at sea level.
+            column.biomes = Array::from_fn(|z| -> [BiomeId; SIZE] {
+                Array::from_fn(|x| {
+                    let index = indices[(z / 4) * 4 + x / 4] as usize;
+                    BiomeId { value: ids.get(index).cloned().unwrap_or(DEFAULT_BIOME) }
+                })
+            });
+        }
+    }
+    Ok(column)
+}
+
+/// The `palette` and optional packed `data` of a compound.
+fn paletted_container<'a>(container: slice::CompoundRef<'a>, name: &'static str)
+                          -> Result<(slice::ListRef<'a>, Option<slice::Array<'a, i64>>),
+                                    RegionError> {
+    let mut palette = None;
+    let mut data = None;
+    for entry in container.iter() {
+        let (key, value) = try!(entry);
+        match &*key.to_str() {
+            "palette" => palette = Some(try!(value.as_list().ok_or(RegionError::Schema(name)))),
+            "data" => data = Some(try!(value.as_long_array().ok_or(RegionError::Schema(name)))),
+            _ => {}
+        }
+    }
+    Ok((try!(palette.ok_or(RegionError::Schema(name))), data))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::env;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use chunk::{ ChunkColumn, EMPTY_CHUNK };
+    use super::*;
+
+    // Built by `fixtures/make_fixtures.py`, compressed as in a region.
+    pub const CHUNK_MCREGION: &'static [u8] =
+        include_bytes!("../../../fixtures/chunk_mcregion.zlib");
+    pub const CHUNK_1_12: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_12.zlib");
+    pub const CHUNK_1_13: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_13.zlib");
+    pub const CHUNK_1_18: &'static [u8] = include_bytes!("../../../fixtures/chunk_1_18.zlib");
+
+    /// An empty folder for a test to write regions in.
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("hematite-test-{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    pub fn inflate(data: &[u8]) -> Vec<u8> {
+        Compression::Zlib.decompress(data).unwrap()
+    }
+
+    fn block(column: &ChunkColumn, x: usize, y: i32, z: usize) -> u16 {
+        column.chunk(y >> 4).unwrap_or(EMPTY_CHUNK).blocks[(y & 15) as usize][z][x].value
+    }
+
+    /// The block and sky light of a block.
+    fn light(column: &ChunkColumn, x: usize, y: i32, z: usize) -> (u8, u8) {
+        let light = column.chunk(y >> 4).unwrap_or(EMPTY_CHUNK)
+                          .light_levels[(y & 15) as usize][z][x];
+        (light.block_light(), light.sky_light())
+    }
+
+    #[test]
+    fn mcregion_chunk() {
+        let column = legacy::read_chunk_column(&inflate(CHUNK_MCREGION)).unwrap();
+        assert_eq!((column.min_section, column.max_section), (0, 7));
+        // Sections 4 to 7 are only air.
+        assert_eq!(column.chunks.len(), 4);
+        assert_eq!(block(&column, 0, 0, 0), 0x0070);
+        assert_eq!(block(&column, 9, 30, 4), 0x0010);
+        assert_eq!(block(&column, 15, 61, 15), 0x0020);
+        assert_eq!(block(&column, 1, 62, 2), 0x023e);
+        assert_eq!(block(&column, 3, 62, 3), 0x0325);
+        assert_eq!(light(&column, 3, 62, 3), (14, 15));
+        assert_eq!(light(&column, 3, 61, 3), (0, 0));
+    }
+
+    #[test]
+    fn pre_flattening_chunk() {
+        let column = read_chunk_column(&inflate(CHUNK_1_12)).unwrap();
+        assert_eq!((column.min_section, column.max_section), (0, 15));
+        assert_eq!(column.chunks.len(), 2);
+        assert_eq!(block(&column, 0, 0, 0), 0x0070);
+        assert_eq!(block(&column, 7, 2, 7), 0x0010);
+        assert_eq!(block(&column, 15, 5, 15), 0x0020);
+        assert_eq!(block(&column, 1, 6, 2), 0x023e);
+        assert_eq!(block(&column, 3, 6, 3), 0x0325);
+        assert_eq!(block(&column, 8, 20, 8), 0x0590);
+        assert_eq!(light(&column, 3, 6, 3), (14, 15));
+        assert_eq!(light(&column, 4, 6, 3), (13, 15));
+        assert_eq!(light(&column, 3, 3, 3), (0, 0));
+        assert_eq!(light(&column, 8, 20, 8), (15, 15));
+        assert_eq!(column.biomes[0][0].value, 4);
+        assert_eq!(column.biomes[15][15].value, 7);
+    }
+
+    #[test]
+    fn palette_chunk() {
+        let column = read_chunk_column(&inflate(CHUNK_1_13)).unwrap();
+        assert_eq!(column.chunks.len(), 1);
+        assert_eq!(block(&column, 0, 0, 0), 0x0070);
+        assert_eq!(block(&column, 0, 3, 0), 0x0010);
+        assert_eq!(block(&column, 0, 4, 0), 0x0030);
+        assert_eq!(block(&column, 0, 5, 0), 0x0020);
+        // Sand, gravel, ..., an oak log along X, ..., diamond ore.
+        let row = [0x00c0, 0x00d0, 0x0040, 0x0050, 0x0114, 0x00e0, 0x00f0, 0x0100, 0x0140,
+                   0x0150, 0x0380, 0x0000];
+        for (x, &state) in row.iter().enumerate() {
+            assert_eq!(block(&column, x, 6, 0), state);
+        }
+        // The 17th entry, whose 5-bit indices span longs.
+        assert_eq!(block(&column, 5, 7, 5), 0x0324);
+        assert_eq!(light(&column, 5, 7, 5), (14, 15));
+        assert_eq!(light(&column, 5, 5, 5), (0, 0));
+        assert_eq!(column.biomes[3][9].value, 2);
+    }
+
+    #[test]
+    fn sectioned_chunk() {
+        let column = read_chunk_column(&inflate(CHUNK_1_18)).unwrap();
+        assert_eq!((column.min_section, column.max_section), (-4, 19));
+        // Up to section 4, and not section -5, which only has light.
+        assert_eq!(column.chunks.len(), 9);
+        assert!(column.chunk(-5).is_none());
+        assert_eq!(block(&column, 0, -64, 0), 0x0070);
+        // Deepslate didn't exist in 1.8.
+        assert_eq!(block(&column, 3, -63, 9), 0x0010);
+        assert_eq!(block(&column, 0, -16, 0), 0x0010);
+        assert_eq!(block(&column, 5, -10, 5), 0x0000);
+        assert_eq!(light(&column, 5, -10, 5), (7, 0));
+        assert_eq!(block(&column, 5, -20, 5), 0x0000);
+        assert_eq!(block(&column, 5, 70, 5), 0x0000);
+        assert_eq!(light(&column, 5, 70, 5), (0, 15));
+        // Forest in the west half, plains in the east.
+        assert_eq!(column.biomes[0][0].value, 4);
+        assert_eq!(column.biomes[15][7].value, 4);
+        assert_eq!(column.biomes[0][8].value, 1);
+    }
+
+    #[test]
+    fn region_reads_back_chunks() {
+        let dir = temp_dir("region-read");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(0, 0, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.write_raw(31, 0, Compression::Zlib, CHUNK_1_13).unwrap();
+        let data = Compression::Gzip.compress(&inflate(CHUNK_1_18)).unwrap();
+        writer.write_raw(5, 31, Compression::Gzip, &data).unwrap();
+        writer.save().unwrap();
+
+        let region = Region::open(&path).unwrap();
+        assert_eq!(region.format(), Format::Anvil);
+        assert_eq!(region.chunks().count(), 3);
+        let column = region.get_chunk_column(0, 0).unwrap().unwrap();
+        assert_eq!(block(&column, 8, 20, 8), 0x0590);
+        let column = region.get_chunk_column(31, 0).unwrap().unwrap();
+        assert_eq!(block(&column, 5, 7, 5), 0x0324);
+        let column = region.get_chunk_column(5, 31).unwrap().unwrap();
+        assert_eq!(light(&column, 5, -10, 5), (7, 0));
+        assert!(region.get_chunk_column(1, 0).unwrap().is_none());
+
+        let path = dir.join("r.0.0.mcr");
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(2, 3, Compression::Zlib, CHUNK_MCREGION).unwrap();
+        writer.save().unwrap();
+        let region = Region::open(&path).unwrap();
+        assert_eq!(region.format(), Format::McRegion);
+        let column = region.get_chunk_column(2, 3).unwrap().unwrap();
+        assert_eq!(block(&column, 3, 62, 3), 0x0325);
+
+        drop(region);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn external_chunks_are_listed_without_their_files() {
+        let dir = temp_dir("region-external");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        let large = vec![7; 2 * 1024 * 1024];
+        writer.write_raw(4, 5, Compression::None, &large).unwrap();
+        writer.save().unwrap();
+        fs::remove_file(dir.join("c.4.5.mcc")).unwrap();
+
+        let region = Region::open(&path).unwrap();
+        let info = region.chunk_info(4, 5).unwrap().unwrap();
+        assert!(info.external);
+        assert_eq!(info.compressed_len, None);
+        assert_eq!(region.chunks().count(), 1);
+        // Only reading the chunk needs its file.
+        assert!(region.external_len(&info).is_err());
+        assert!(region.read_raw(&info).is_err());
+        drop(region);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
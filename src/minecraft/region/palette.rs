@@ -0,0 +1,279 @@
+//! Block states in the format used since 1.13, where each section has a
+//! palette of namespaced states and a packed array of indices into it.
+//!
+//! The renderer only knows the 1.8 block states, so palette entries are
+//! mapped onto the closest one of those.
+
+use std::borrow::Cow;
+use std::cmp::max;
+
+use chunk::BlockState;
+use minecraft::data::BLOCK_STATES;
+use minecraft::nbt::slice::{ Array, CompoundRef, ListRef };
+use minecraft::region::RegionError;
+
+/// The first `DataVersion` with palettes, 17w47a.
+pub const FLATTENING: i32 = 1451;
+/// The first `DataVersion` whose packed entries never span two longs,
+/// 20w17a.
+pub const UNSPANNED: i32 = 2529;
+/// The first `DataVersion` without the `Level` wrapper, with lowercase
+/// section tags and biomes in the sections, 21w43a.
+pub const SECTIONED_BIOMES: i32 = 2844;
+
+/// Blocks that 1.8 doesn't have become stone, so that terrain keeps its
+/// shape.
+const UNKNOWN: BlockState = BlockState { value: 0x0010 };
+
+/// Blocks whose name changed with the flattening, mapped to the name of
+/// their 1.8 model.
+const RENAMES: &'static [(&'static str, &'static str)] = &[
+    ("grass_block", "grass"),
+    ("grass", "tall_grass"),
+    ("short_grass", "tall_grass"),
+    ("tall_grass", "double_grass"),
+    ("large_fern", "double_fern"),
+    ("lilac", "syringa"),
+    ("rose_bush", "double_rose"),
+    ("peony", "paeonia"),
+    ("azure_bluet", "houstonia"),
+    ("sugar_cane", "reeds"),
+    ("lily_pad", "waterlily"),
+    ("cobweb", "web"),
+    ("melon", "melon_block"),
+    ("note_block", "noteblock"),
+    ("spawner", "mob_spawner"),
+    ("slime_block", "slime"),
+    ("bricks", "brick_block"),
+    ("nether_bricks", "nether_brick"),
+    ("stone_bricks", "stonebrick"),
+    ("mossy_stone_bricks", "mossy_stonebrick"),
+    ("cracked_stone_bricks", "cracked_stonebrick"),
+    ("chiseled_stone_bricks", "chiseled_stonebrick"),
+    ("terracotta", "hardened_clay"),
+    ("nether_quartz_ore", "quartz_ore"),
+    ("jack_o_lantern", "lit_pumpkin"),
+    ("snow", "snow_layer"),
+    ("snow_block", "snow"),
+    ("wall_torch", "torch"),
+    ("redstone_wall_torch", "redstone_torch"),
+    ("oak_door", "wooden_door"),
+    ("oak_fence", "fence"),
+    ("oak_fence_gate", "fence_gate"),
+    ("oak_trapdoor", "trapdoor"),
+    ("oak_button", "wooden_button"),
+    ("oak_pressure_plate", "wooden_pressure_plate"),
+    ("oak_wood", "oak_log"),
+    ("spruce_wood", "spruce_log"),
+    ("birch_wood", "birch_log"),
+    ("jungle_wood", "jungle_log"),
+    ("infested_stone", "stone_monster_egg"),
+    ("infested_cobblestone", "cobblestone_monster_egg"),
+    ("infested_stone_bricks", "stone_brick_monster_egg"),
+    ("repeater", "unpowered_repeater"),
+    ("comparator", "unpowered_comparator"),
+];
+
+/// Maps a palette entry, a compound with a `Name` and optional
+/// `Properties`, onto a 1.8 block state.
+pub fn block_state(entry: CompoundRef) -> Result<BlockState, RegionError> {
+    let mut name = None;
+    let mut properties = vec![];
+    for entry in entry.iter() {
+        let (key, value) = try!(entry);
+        match &*key.to_str() {
+            "Name" => name = value.as_str().map(|s| s.to_str()),
+            "Properties" => {
+                let compound = try!(value.as_compound().ok_or(RegionError::Schema("Properties")));
+                for property in compound.iter() {
+                    let (key, value) = try!(property);
+                    let value = try!(value.as_str().ok_or(RegionError::Schema("Properties")));
+                    properties.push((key.to_str(), value.to_str()));
+                }
+            }
+            _ => {}
+        }
+    }
+    let name = try!(name.ok_or(RegionError::Schema("Name")));
+    Ok(legacy_block_state(&name, &properties))
+}
+
+fn legacy_block_state(name: &str, properties: &[(Cow<str>, Cow<str>)]) -> BlockState {
+    let name = if name.starts_with("minecraft:") { &name[10..] } else { name };
+    let property = |key: &str| {
+        properties.iter().find(|&&(ref k, _)| k == key).map(|&(_, ref v)| &v[..])
+    };
+
+    // Liquids have no model, but keep their 1.8 id for the level.
+    let level = property("level").and_then(|l| l.parse::<u16>().ok()).unwrap_or(0) & 0xf;
+    match name {
+        "air" | "cave_air" | "void_air" => return BlockState { value: 0 },
+        "water" | "bubble_column" | "seagrass" | "tall_seagrass" | "kelp" | "kelp_plant" => {
+            return BlockState { value: 0x0090 | level };
+        }
+        "lava" => return BlockState { value: 0x00b0 | level },
+        _ => {}
+    }
+
+    let mut name = Cow::Borrowed(RENAMES.iter().find(|&&(new, _)| new == name)
+                                              .map_or(name, |&(_, old)| old));
+    if name.starts_with("light_gray_") {
+        name = Cow::Owned(format!("silver_{}", &name[11..]));
+    }
+    if name.ends_with("_terracotta") && !name.ends_with("glazed_terracotta") {
+        name = Cow::Owned(format!("{}_stained_hardened_clay", &name[..name.len() - 11]));
+    }
+    if name.ends_with("_slab") && property("type") == Some("double") {
+        name = Cow::Owned(format!("{}_double_slab", &name[..name.len() - 5]));
+    }
+    if property("lit") == Some("true") || property("powered") == Some("true") {
+        let lit = format!("lit_{}", name);
+        let powered = name.replace("unpowered_", "powered_");
+        if BLOCK_STATES.iter().any(|&(_, n, _)| n == lit) {
+            name = Cow::Owned(lit);
+        } else if BLOCK_STATES.iter().any(|&(_, n, _)| n == powered) {
+            name = Cow::Owned(powered);
+        }
+    }
+    if name == "redstone_torch" && property("lit") == Some("false") {
+        name = Cow::Borrowed("unlit_redstone_torch");
+    }
+
+    // Pick the first state of the block whose variant agrees with the
+    // properties, or any state of it if none does. Slabs used to say
+    // `half` for what is now `type`.
+    let agrees = |variant: &str| variant == "normal" || variant.split(',').all(|pair| {
+        let mut pair = pair.splitn(2, '=');
+        let (key, value) = (pair.next().unwrap(), pair.next());
+        property(key) == value || key == "half" && property("type") == value
+    });
+    let states = || BLOCK_STATES.iter().filter(|&&(_, n, _)| n == name);
+    states().find(|&&(_, _, variant)| agrees(variant))
+            .or_else(|| states().next())
+            .map_or(UNKNOWN, |&(id, _, _)| BlockState { value: id })
+}
+
+/// Biomes that were renamed or added since 1.8, mapped to the id of the
+/// closest 1.8 biome.
+const BIOMES: &'static [(&'static str, u8)] = &[
+    ("ocean", 0), ("warm_ocean", 0), ("lukewarm_ocean", 0), ("cold_ocean", 0),
+    ("plains", 1), ("meadow", 1), ("the_void", 1),
+    ("desert", 2),
+    ("mountains", 3), ("windswept_hills", 3), ("stony_peaks", 3),
+    ("forest", 4), ("cherry_grove", 4),
+    ("taiga", 5),
+    ("swamp", 6), ("mangrove_swamp", 6),
+    ("river", 7),
+    ("nether_wastes", 8), ("nether", 8), ("soul_sand_valley", 8), ("crimson_forest", 8),
+    ("warped_forest", 8), ("basalt_deltas", 8),
+    ("the_end", 9), ("small_end_islands", 9), ("end_midlands", 9), ("end_highlands", 9),
+    ("end_barrens", 9),
+    ("frozen_ocean", 10), ("deep_frozen_ocean", 10),
+    ("frozen_river", 11),
+    ("snowy_tundra", 12), ("snowy_plains", 12), ("snowy_slopes", 12),
+    ("snowy_mountains", 13), ("frozen_peaks", 13), ("jagged_peaks", 13),
+    ("mushroom_fields", 14),
+    ("beach", 16),
+    ("jungle", 21), ("bamboo_jungle", 21),
+    ("jungle_edge", 23), ("sparse_jungle", 23),
+    ("deep_ocean", 24), ("deep_warm_ocean", 24), ("deep_lukewarm_ocean", 24),
+    ("deep_cold_ocean", 24),
+    ("stone_shore", 25), ("stony_shore", 25),
+    ("snowy_beach", 26),
+    ("birch_forest", 27),
+    ("dark_forest", 29),
+    ("snowy_taiga", 30), ("grove", 30),
+    ("giant_tree_taiga", 32), ("old_growth_pine_taiga", 32), ("old_growth_spruce_taiga", 32),
+    ("wooded_mountains", 34), ("windswept_forest", 34),
+    ("savanna", 35),
+    ("savanna_plateau", 36),
+    ("badlands", 37),
+    ("wooded_badlands", 38), ("wooded_badlands_plateau", 38),
+    ("badlands_plateau", 39),
+    ("sunflower_plains", 129),
+    ("gravelly_mountains", 131), ("windswept_gravelly_hills", 131),
+    ("flower_forest", 132),
+    ("ice_spikes", 140),
+    ("tall_birch_forest", 155), ("old_growth_birch_forest", 155),
+    ("shattered_savanna", 163), ("windswept_savanna", 163),
+    ("eroded_badlands", 165),
+];
+
+/// The 1.8 id of a namespaced biome, or `default` for one without a
+/// counterpart.
+pub fn biome_id(name: &str, default: u8) -> u8 {
+    let name = if name.starts_with("minecraft:") { &name[10..] } else { name };
+    BIOMES.iter().find(|&&(n, _)| n == name).map_or(default, |&(_, id)| id)
+}
+
+/// The 4096 block states of a section, from its palette and packed
+/// indices. A palette of one entry needs no indices.
+pub fn block_states(palette: ListRef, data: Option<Array<i64>>, spanning: bool)
+                    -> Result<Vec<BlockState>, RegionError> {
+    let mut legacy = Vec::with_capacity(palette.len());
+    for entry in palette.iter() {
+        let entry = try!(try!(entry).as_compound().ok_or(RegionError::Schema("Palette")));
+        legacy.push(try!(block_state(entry)));
+    }
+    let indices = match data {
+        Some(data) => {
+            let bits = max(4, index_bits(legacy.len()));
+            try!(unpack(data, bits, spanning, 4096).ok_or(RegionError::Schema("BlockStates")))
+        }
+        None if legacy.len() == 1 => vec![0; 4096],
+        None => return Err(RegionError::Schema("BlockStates"))
+    };
+    let mut states = Vec::with_capacity(4096);
+    for index in indices {
+        states.push(*try!(legacy.get(index as usize).ok_or(RegionError::Schema("BlockStates"))));
+    }
+    Ok(states)
+}
+
+/// The bits per index for a palette of `len` entries.
+pub fn index_bits(len: usize) -> usize {
+    64 - (len.saturating_sub(1) as u64).leading_zeros() as usize
+}
+
+/// Unpacks `count` indices of `bits` bits each. Before `UNSPANNED` an index
+/// could continue in the next long; since, the leftover high bits of each
+/// long are unused. Returns `None` if the array has the wrong length.
+pub fn unpack(longs: Array<i64>, bits: usize, spanning: bool, count: usize) -> Option<Vec<u16>> {
+    if bits == 0 {
+        return None;
+    }
+    let per_long = 64 / bits;
+    let expected = if spanning {
+        (count * bits + 63) / 64
+    } else {
+        (count + per_long - 1) / per_long
+    };
+    if longs.len() != expected {
+        return None;
+    }
+    let longs = longs.to_vec();
+    let mask = (1u64 << bits) - 1;
+    let mut indices = Vec::with_capacity(count);
+    if spanning {
+        for i in 0..count {
+            let (word, shift) = (i * bits / 64, i * bits % 64);
+            let mut value = longs[word] as u64 >> shift;
+            if shift + bits > 64 {
+                value |= (longs[word + 1] as u64) << (64 - shift);
+            }
+            indices.push((value & mask) as u16);
+        }
+    } else {
+        for &long in &longs {
+            let mut value = long as u64;
+            for _ in 0..per_long {
+                if indices.len() == count {
+                    break;
+                }
+                indices.push((value & mask) as u16);
+                value >>= bits;
+            }
+        }
+    }
+    Some(indices)
+}
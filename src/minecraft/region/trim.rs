@@ -0,0 +1,210 @@
+//! Deleting chunks that nobody spent time in, or that are outside the
+//! areas of a world worth keeping.
+
+use std::cmp::{ max, min };
+use std::path::Path;
+
+use minecraft::nbt::slice;
+use minecraft::region::{ Region, RegionError, RegionWriteError, RegionWriter, SECTOR };
+
+/// A part of the world, in block coordinates on the X/Z plane.
+#[derive(Copy, Clone, Debug)]
+pub enum Area {
+    /// The blocks from one corner to the other, both included.
+    Box([i32; 2], [i32; 2]),
+    /// The blocks at most `radius` away from a point.
+    Radius([i32; 2], i32),
+}
+
+impl Area {
+    /// Whether any block of chunk `x`, `z` is in the area.
+    pub fn touches_chunk(&self, x: i32, z: i32) -> bool {
+        let (min_x, min_z) = (x * 16, z * 16);
+        let (max_x, max_z) = (min_x + 15, min_z + 15);
+        match *self {
+            Area::Box(a, b) => {
+                min_x <= max(a[0], b[0]) && max_x >= min(a[0], b[0]) &&
+                min_z <= max(a[1], b[1]) && max_z >= min(a[1], b[1])
+            }
+            Area::Radius(center, radius) => {
+                // The distance from the center to the closest block.
+                let dx = (min(max(center[0], min_x), max_x) - center[0]) as i64;
+                let dz = (min(max(center[1], min_z), max_z) - center[1]) as i64;
+                dx * dx + dz * dz <= radius as i64 * radius as i64
+            }
+        }
+    }
+}
+
+/// Which chunks to delete. Chunks in a keep-area are never deleted. Of
+/// the others, those inhabited for less than `min_inhabited` ticks go,
+/// or all of them if there is no threshold. Without either, nothing is
+/// deleted.
+#[derive(Clone, Debug, Default)]
+pub struct Trim {
+    pub min_inhabited: Option<i64>,
+    pub keep: Vec<Area>
+}
+
+/// A chunk picked for deletion.
+#[derive(Copy, Clone, Debug)]
+pub struct Trimmed {
+    /// The position of the chunk in the region.
+    pub x: u8,
+    pub z: u8,
+    /// How long players have been near it, in ticks.
+    pub inhabited: Option<i64>
+}
+
+/// The `InhabitedTime` of a chunk, which is in `Level` before 21w43a.
+/// Chunks from before 1.6 don't have it.
+fn inhabited_time(data: &[u8]) -> Result<Option<i64>, RegionError> {
+    let (_, root) = try!(slice::root(data));
+    let root = try!(root.as_compound().ok_or(RegionError::Schema("root")));
+    let level = match try!(root.get("Level")) {
+        Some(level) => try!(level.as_compound().ok_or(RegionError::Schema("Level"))),
+        None => root
+    };
+    Ok(try!(level.get("InhabitedTime")).and_then(|time| time.as_long()))
+}
+
+impl Trim {
+    /// Picks the chunks of the region at `rx`, `rz` to delete, without
+    /// changing anything. Chunks whose time can't be told, because they
+    /// are too old or can't be read, are kept.
+    pub fn plan(&self, region: &Region, rx: i32, rz: i32) -> Result<Vec<Trimmed>, RegionError> {
+        let mut trimmed = vec![];
+        if self.min_inhabited.is_none() && self.keep.is_empty() {
+            return Ok(trimmed);
+        }
+        for info in region.chunks() {
+            let info = match info {
+                Ok(info) => info,
+                // Without tables there is nothing to plan.
+                Err(RegionError::Truncated) if region.as_slice().len() < 2 * SECTOR => {
+                    return Err(RegionError::Truncated);
+                }
+                Err(_) => continue
+            };
+            let (x, z) = (rx * 32 + info.x as i32, rz * 32 + info.z as i32);
+            if self.keep.iter().any(|area| area.touches_chunk(x, z)) {
+                continue;
+            }
+            let inhabited = match self.min_inhabited {
+                Some(min) => {
+                    match region.read_data(&info).and_then(|data| inhabited_time(&data)) {
+                        Ok(Some(time)) if time < min => Some(time),
+                        _ => continue
+                    }
+                }
+                None => None
+            };
+            trimmed.push(Trimmed {
+                x: info.x,
+                z: info.z,
+                inhabited: inhabited
+            });
+        }
+        Ok(trimmed)
+    }
+}
+
+/// Deletes the planned chunks from the region file at `path`.
+pub fn trim(path: &Path, trimmed: &[Trimmed]) -> Result<(), RegionWriteError> {
+    let mut writer = try!(RegionWriter::open(path));
+    for chunk in trimmed {
+        writer.delete_chunk(chunk.x, chunk.z);
+    }
+    try!(writer.save());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use minecraft::region::{ Compression, Region, RegionWriter };
+    use minecraft::region::tests::{ temp_dir, CHUNK_1_12, CHUNK_1_18 };
+    use super::*;
+
+    #[test]
+    fn boxes_at_chunk_edges() {
+        let area = Area::Box([-1, -1], [-16, -16]);
+        assert!(area.touches_chunk(-1, -1));
+        assert!(!area.touches_chunk(0, -1));
+        assert!(!area.touches_chunk(-1, 0));
+        assert!(!area.touches_chunk(-2, -1));
+        assert!(!area.touches_chunk(-1, -2));
+
+        let area = Area::Box([-17, 0], [-17, 0]);
+        assert!(area.touches_chunk(-2, 0));
+        assert!(!area.touches_chunk(-1, 0));
+        assert!(!area.touches_chunk(-2, -1));
+    }
+
+    #[test]
+    fn radii_at_chunk_edges() {
+        let area = Area::Radius([0, 0], 1);
+        assert!(area.touches_chunk(0, 0));
+        assert!(area.touches_chunk(-1, 0));
+        assert!(area.touches_chunk(0, -1));
+        // Block -1, -1 is further away than 1.
+        assert!(!area.touches_chunk(-1, -1));
+
+        let area = Area::Radius([-1, -1], 16);
+        assert!(area.touches_chunk(-2, -1));
+        assert!(area.touches_chunk(0, -1));
+        assert!(!area.touches_chunk(-3, -1));
+        assert!(!area.touches_chunk(-2, -2));
+
+        let area = Area::Radius([-16, -16], 0);
+        assert!(area.touches_chunk(-1, -1));
+        assert!(!area.touches_chunk(-2, -1));
+        assert!(!area.touches_chunk(-1, -2));
+    }
+
+    fn planned(plan: &Trim, region: &Region, rx: i32, rz: i32) -> Vec<(u8, u8, Option<i64>)> {
+        plan.plan(region, rx, rz).unwrap().iter().map(|chunk| (chunk.x, chunk.z, chunk.inhabited)).collect()
+    }
+
+    #[test]
+    fn inhabited_time_threshold() {
+        let dir = temp_dir("trim-inhabited");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        // In `Level` at 1200 ticks, and at the root at 5312.
+        writer.write_raw(0, 0, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.write_raw(1, 0, Compression::Zlib, CHUNK_1_18).unwrap();
+        // Unreadable, so its time is unknown.
+        writer.write_raw(2, 0, Compression::Zlib, b"not zlib").unwrap();
+        writer.save().unwrap();
+        let region = Region::open(&path).unwrap();
+
+        let at = |min| Trim { min_inhabited: Some(min), keep: vec![] };
+        assert_eq!(planned(&at(1199), &region, 0, 0), []);
+        assert_eq!(planned(&at(1200), &region, 0, 0), []);
+        assert_eq!(planned(&at(1201), &region, 0, 0), [(0, 0, Some(1200))]);
+        assert_eq!(planned(&at(5312), &region, 0, 0), [(0, 0, Some(1200))]);
+        assert_eq!(planned(&at(5313), &region, 0, 0), [(0, 0, Some(1200)), (1, 0, Some(5312))]);
+
+        // Keep-areas are in world coordinates, so they depend on where the
+        // region is.
+        let plan = Trim { min_inhabited: Some(10000), keep: vec![Area::Box([-512, -512], [-512, -512])] };
+        assert_eq!(planned(&plan, &region, -1, -1), [(1, 0, Some(5312))]);
+        assert_eq!(planned(&plan, &region, 0, 0), [(0, 0, Some(1200)), (1, 0, Some(5312))]);
+
+        // Without a threshold everything outside the areas goes, readable
+        // or not.
+        let plan = Trim { min_inhabited: None, keep: vec![Area::Radius([0, 0], 0)] };
+        assert_eq!(planned(&plan, &region, 0, 0), [(1, 0, None), (2, 0, None)]);
+        assert_eq!(planned(&Trim::default(), &region, 0, 0), []);
+
+        drop(region);
+        trim(&path, &plan.plan(&Region::open(&path).unwrap(), 0, 0).unwrap()).unwrap();
+        let region = Region::open(&path).unwrap();
+        let left: Vec<_> = region.chunks().map(|info| info.unwrap().x).collect();
+        assert_eq!(left, [0]);
+        drop(region);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
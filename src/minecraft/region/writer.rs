@@ -0,0 +1,510 @@
+//! Writing chunks into region files.
+
+use std::collections::BTreeMap;
+use std::fs::{ self, File };
+use std::io::{ self, Read, Write };
+use std::path::{ Path, PathBuf };
+use std::time::{ SystemTime, UNIX_EPOCH };
+
+use byteorder::{ BigEndian, ByteOrder };
+
+use minecraft::nbt::{ Nbt, NbtWriterError };
+use minecraft::region::{ external_path, Compression, EXTERNAL, SECTOR };
+
+/// The location and timestamp tables take up the first two sectors.
+const HEADER_SECTORS: usize = 2;
+/// The sector count of a chunk is stored in a byte.
+const MAX_CHUNK_SECTORS: usize = 255;
+
+#[derive(Debug)]
+pub enum RegionWriteError {
+    Io(io::Error),
+    Nbt(NbtWriterError),
+    /// A chunk that needs more than the 255 sectors (about 1 MiB) a
+    /// region has room for, in a region whose file name doesn't say where
+    /// to put it instead. Carries its compressed size.
+    ChunkTooLarge(usize),
+}
+
+impl From<io::Error> for RegionWriteError {
+    fn from(err: io::Error) -> RegionWriteError { RegionWriteError::Io(err) }
+}
+
+impl From<NbtWriterError> for RegionWriteError {
+    fn from(err: NbtWriterError) -> RegionWriteError { RegionWriteError::Nbt(err) }
+}
+
+/// Edits a region file in memory and writes it back out with `save`.
+///
+/// A chunk goes in the first run of free sectors that is big enough for
+/// it, so the space of deleted and moved chunks gets reused. `save` writes
+/// a new file next to the old one and renames it into place, so a crash
+/// leaves either the old or the new version behind, never a mix.
+///
+/// Chunks too big for the region go in `c.X.Z.mcc` files next to it,
+/// which are also only written by `save`.
+pub struct RegionWriter {
+    path: PathBuf,
+    /// The whole file, tables included, a multiple of `SECTOR` long.
+    data: Vec<u8>,
+    /// Which sectors are taken by the tables or a chunk.
+    used: Vec<bool>,
+    /// External chunk files to write, or to remove if `None`, by index.
+    external: BTreeMap<usize, Option<Vec<u8>>>
+}
+
+fn index(x: u8, z: u8) -> usize {
+    (x % 32) as usize + (z % 32) as usize * 32
+}
+
+/// The sectors needed for `len` bytes of chunk data and its header.
+fn sectors_for(len: usize) -> usize {
+    (5 + len + SECTOR - 1) / SECTOR
+}
+
+fn now() -> u32 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs() as u32).unwrap_or(0)
+}
+
+impl RegionWriter {
+    /// Opens a region file for editing, or starts an empty one if it
+    /// doesn't exist. Table entries pointing outside the file are dropped,
+    /// and a chunk sharing sectors with one before it in the table gets a
+    /// copy of them, so that changing one chunk leaves the other alone.
+    pub fn open(path: &Path) -> io::Result<RegionWriter> {
+        let mut data = vec![];
+        match File::open(path) {
+            Ok(mut file) => { try!(file.read_to_end(&mut data)); }
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err)
+        }
+        let sectors = ::std::cmp::max(HEADER_SECTORS, (data.len() + SECTOR - 1) / SECTOR);
+        data.resize(sectors * SECTOR, 0);
+
+        let mut writer = RegionWriter {
+            path: path.to_path_buf(),
+            data: data,
+            used: vec![false; sectors],
+            external: BTreeMap::new()
+        };
+        for s in 0..HEADER_SECTORS {
+            writer.used[s] = true;
+        }
+        let mut overlapping = vec![];
+        for i in 0..1024 {
+            let (start, count) = writer.location(i);
+            if count == 0 && start == 0 {
+                continue;
+            }
+            if start < HEADER_SECTORS || count == 0 || start + count > sectors {
+                writer.set_location(i, 0, 0);
+                continue;
+            }
+            if (start..start + count).any(|s| writer.used[s]) {
+                overlapping.push(i);
+                continue;
+            }
+            for s in start..start + count {
+                writer.used[s] = true;
+            }
+        }
+        // Only once every other chunk has its sectors can free ones be
+        // told apart.
+        for i in overlapping {
+            let (start, count) = writer.location(i);
+            let sectors = writer.data[start * SECTOR..(start + count) * SECTOR].to_vec();
+            let new = writer.allocate(count);
+            writer.data[new * SECTOR..(new + count) * SECTOR].copy_from_slice(&sectors);
+            writer.set_location(i, new, count);
+        }
+        Ok(writer)
+    }
+
+    /// Starts an empty region, which replaces any file at `path` when
+    /// saved, along with the external chunks of that file.
+    pub fn create(path: &Path) -> RegionWriter {
+        let mut writer = RegionWriter {
+            path: path.to_path_buf(),
+            data: vec![0; HEADER_SECTORS * SECTOR],
+            used: vec![true; HEADER_SECTORS],
+            external: BTreeMap::new()
+        };
+        if external_path(path, 0, 0).is_some() {
+            if let Ok(old) = RegionWriter::open(path) {
+                for i in (0..1024).filter(|&i| old.is_external(i)) {
+                    writer.external.insert(i, None);
+                }
+            }
+        }
+        writer
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn location(&self, i: usize) -> (usize, usize) {
+        let entry = BigEndian::read_u32(&self.data[i * 4..]);
+        ((entry >> 8) as usize, (entry & 0xff) as usize)
+    }
+
+    fn set_location(&mut self, i: usize, start: usize, count: usize) {
+        BigEndian::write_u32(&mut self.data[i * 4..], ((start as u32) << 8) | count as u32);
+    }
+
+    fn write_timestamp(&mut self, i: usize, timestamp: u32) {
+        BigEndian::write_u32(&mut self.data[SECTOR + i * 4..], timestamp);
+    }
+
+    /// Sets when a chunk was last saved, in seconds since the UNIX epoch.
+    /// Writing a chunk sets it to now.
+    pub fn set_timestamp(&mut self, x: u8, z: u8, timestamp: u32) {
+        self.write_timestamp(index(x, z), timestamp);
+    }
+
+    pub fn contains_chunk(&self, x: u8, z: u8) -> bool {
+        self.location(index(x, z)).1 != 0
+    }
+
+    /// Compresses and stores a chunk, replacing any chunk already there.
+    pub fn write_chunk(&mut self, x: u8, z: u8, nbt: &Nbt, compression: Compression)
+                       -> Result<(), RegionWriteError> {
+        let mut data = vec![];
+        try!(nbt.to_writer(&mut data));
+        let data = try!(compression.compress(&data));
+        self.write_raw(x, z, compression, &data)
+    }
+
+    /// Stores already compressed chunk data.
+    pub fn write_raw(&mut self, x: u8, z: u8, compression: Compression, data: &[u8])
+                     -> Result<(), RegionWriteError> {
+        let i = index(x, z);
+        let was_external = self.is_external(i);
+        if sectors_for(data.len()) <= MAX_CHUNK_SECTORS {
+            self.store(i, compression.id(), data);
+            if was_external {
+                self.external.insert(i, None);
+            }
+            return Ok(());
+        }
+        // Too big: only the compression type stays in the region.
+        match external_path(&self.path, x, z) {
+            Some(_) => {
+                self.store(i, compression.id() | EXTERNAL, &[]);
+                self.external.insert(i, Some(data.to_vec()));
+                Ok(())
+            }
+            None => Err(RegionWriteError::ChunkTooLarge(data.len()))
+        }
+    }
+
+    /// Removes a chunk, returning whether there was one.
+    pub fn delete_chunk(&mut self, x: u8, z: u8) -> bool {
+        let i = index(x, z);
+        let present = self.location(i).1 != 0;
+        if self.is_external(i) {
+            self.external.insert(i, None);
+        }
+        self.free(i);
+        self.write_timestamp(i, 0);
+        present
+    }
+
+    /// Whether the chunk at `i` is kept in its own file.
+    fn is_external(&self, i: usize) -> bool {
+        let (start, count) = self.location(i);
+        count != 0 && self.data[start * SECTOR + 4] & EXTERNAL != 0
+    }
+
+    /// Puts a chunk's length, compression byte and data in free sectors.
+    fn store(&mut self, i: usize, id: u8, data: &[u8]) {
+        // A big-endian length (counting the compression byte) comes first.
+        let len = 5 + data.len();
+        let count = sectors_for(data.len());
+        self.free(i);
+        let start = self.allocate(count);
+
+        let sectors = &mut self.data[start * SECTOR..(start + count) * SECTOR];
+        BigEndian::write_u32(sectors, (data.len() + 1) as u32);
+        sectors[4] = id;
+        sectors[5..len].copy_from_slice(data);
+        for b in sectors[len..].iter_mut() {
+            *b = 0;
+        }
+        self.set_location(i, start, count);
+        self.write_timestamp(i, now());
+    }
+
+    fn free(&mut self, i: usize) {
+        let (start, count) = self.location(i);
+        for s in start..start + count {
+            self.used[s] = false;
+        }
+        self.set_location(i, 0, 0);
+    }
+
+    /// Finds room for `count` sectors, growing the file if no free run
+    /// is long enough, and marks them as used.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut run = 0;
+        let mut start = None;
+        for s in HEADER_SECTORS..self.used.len() {
+            if self.used[s] {
+                run = 0;
+                continue;
+            }
+            run += 1;
+            if run == count {
+                start = Some(s + 1 - count);
+                break;
+            }
+        }
+        // Otherwise extend the free run at the end of the file, if any.
+        let start = start.unwrap_or(self.used.len() - run);
+        if start + count > self.used.len() {
+            self.used.resize(start + count, false);
+            self.data.resize((start + count) * SECTOR, 0);
+        }
+        for s in start..start + count {
+            self.used[s] = true;
+        }
+        start
+    }
+
+    /// Writes the region out, dropping free sectors at the end of it.
+    pub fn save(&mut self) -> io::Result<()> {
+        let mut sectors = self.used.len();
+        while sectors > HEADER_SECTORS && !self.used[sectors - 1] {
+            sectors -= 1;
+        }
+        self.used.truncate(sectors);
+        self.data.truncate(sectors * SECTOR);
+
+        // New external chunks go first, so the region never points at a
+        // file that isn't there; old ones are removed once it doesn't
+        // point at them any more.
+        for (&i, data) in &self.external {
+            if let Some(ref data) = *data {
+                try!(replace(&self.external_path(i), data));
+            }
+        }
+        try!(replace(&self.path, &self.data));
+        for (&i, data) in &self.external {
+            if data.is_some() {
+                continue;
+            }
+            if let Err(err) = fs::remove_file(self.external_path(i)) {
+                if err.kind() != io::ErrorKind::NotFound {
+                    return Err(err);
+                }
+            }
+        }
+        self.external.clear();
+        Ok(())
+    }
+
+    fn external_path(&self, i: usize) -> PathBuf {
+        external_path(&self.path, (i % 32) as u8, (i / 32) as u8)
+            .expect("external chunks are only kept for named regions")
+    }
+}
+
+/// Writes a file next to `path` and renames it over `path`. If anything
+/// fails, the new file is removed again.
+fn replace(path: &Path, data: &[u8]) -> io::Result<()> {
+    let mut tmp = path.to_path_buf().into_os_string();
+    tmp.push(".tmp");
+    let tmp = PathBuf::from(tmp);
+    let result = File::create(&tmp).and_then(|mut file| {
+        try!(file.write_all(data));
+        file.sync_all()
+    }).and_then(|_| fs::rename(&tmp, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use minecraft::region::{ Compression, Region, SECTOR };
+    use minecraft::region::tests::{ temp_dir, CHUNK_1_12, CHUNK_1_13, CHUNK_1_18 };
+    use super::*;
+
+    /// `len` bytes that don't repeat within a sector.
+    fn filler(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7919 % 251) as u8).collect()
+    }
+
+    fn offset(region: &Region, x: u8, z: u8) -> usize {
+        region.chunk_info(x, z).unwrap().unwrap().offset
+    }
+
+    #[test]
+    fn freed_sectors_are_reused() {
+        let dir = temp_dir("writer-reuse");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(0, 0, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.write_raw(1, 0, Compression::Zlib, &filler(3 * SECTOR - 100)).unwrap();
+        writer.write_raw(2, 0, Compression::Zlib, CHUNK_1_18).unwrap();
+        writer.save().unwrap();
+        {
+            let region = Region::open(&path).unwrap();
+            assert_eq!((offset(&region, 0, 0), offset(&region, 1, 0), offset(&region, 2, 0)),
+                       (2, 3, 6));
+        }
+
+        // The three sectors of the deleted chunk take a one sector chunk
+        // and a two sector one, so the file doesn't grow.
+        let mut writer = RegionWriter::open(&path).unwrap();
+        assert!(writer.delete_chunk(1, 0));
+        writer.write_raw(3, 0, Compression::Zlib, CHUNK_1_13).unwrap();
+        writer.write_raw(4, 0, Compression::Zlib, &filler(2 * SECTOR - 100)).unwrap();
+        // A chunk that outgrows its sector moves to the end.
+        writer.write_raw(0, 0, Compression::Zlib, &filler(SECTOR)).unwrap();
+        writer.save().unwrap();
+        {
+            let region = Region::open(&path).unwrap();
+            assert_eq!((offset(&region, 3, 0), offset(&region, 4, 0), offset(&region, 2, 0)),
+                       (3, 4, 6));
+            assert_eq!(offset(&region, 0, 0), 7);
+            assert_eq!(fs::metadata(&path).unwrap().len(), 9 * SECTOR as u64);
+            assert!(region.chunk_info(1, 0).unwrap().is_none());
+            assert_eq!(region.chunk_raw(0, 0).unwrap().unwrap().1, filler(SECTOR));
+            assert_eq!(region.chunk_raw(4, 0).unwrap().unwrap().1, filler(2 * SECTOR - 100));
+            assert!(region.get_chunk_column(2, 0).unwrap().is_some());
+            assert!(region.get_chunk_column(3, 0).unwrap().is_some());
+        }
+
+        // Now the chunk's old sector is free for a small one.
+        let mut writer = RegionWriter::open(&path).unwrap();
+        writer.write_raw(5, 0, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.save().unwrap();
+        {
+            let region = Region::open(&path).unwrap();
+            assert_eq!(offset(&region, 5, 0), 2);
+            assert_eq!(region.chunks().count(), 5);
+            assert!(region.get_chunk_column(5, 0).unwrap().is_some());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn large_chunks_go_in_their_own_file() {
+        let dir = temp_dir("writer-external");
+        let path = dir.join("r.-1.2.mca");
+        let external = dir.join("c.-25.73.mcc");
+        let large = filler(256 * SECTOR);
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(7, 9, Compression::Zlib, &large).unwrap();
+        // Nothing is written before saving.
+        assert!(!external.exists());
+        writer.save().unwrap();
+        {
+            let region = Region::open(&path).unwrap();
+            let info = region.chunk_info(7, 9).unwrap().unwrap();
+            assert!(info.external);
+            assert_eq!(info.sectors, 1);
+            assert_eq!(info.compressed_len, None);
+            assert_eq!(region.external_len(&info).unwrap(), large.len() as u64);
+            assert_eq!(region.chunk_raw(7, 9).unwrap().unwrap().1, large);
+            assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        }
+
+        // Once the chunk fits in the region again, its file goes.
+        let mut writer = RegionWriter::open(&path).unwrap();
+        writer.write_raw(7, 9, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.save().unwrap();
+        assert!(!external.exists());
+        {
+            let region = Region::open(&path).unwrap();
+            assert!(!region.chunk_info(7, 9).unwrap().unwrap().external);
+            assert!(region.get_chunk_column(7, 9).unwrap().is_some());
+        }
+
+        // As it does when the chunk is deleted.
+        let mut writer = RegionWriter::open(&path).unwrap();
+        writer.write_raw(7, 9, Compression::Zlib, &large).unwrap();
+        writer.save().unwrap();
+        assert!(external.exists());
+        let mut writer = RegionWriter::open(&path).unwrap();
+        writer.delete_chunk(7, 9);
+        writer.save().unwrap();
+        assert!(!external.exists());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn created_regions_replace_external_chunks() {
+        let dir = temp_dir("writer-create-external");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(1, 2, Compression::Zlib, &filler(256 * SECTOR)).unwrap();
+        writer.write_raw(3, 4, Compression::Zlib, &filler(256 * SECTOR)).unwrap();
+        writer.save().unwrap();
+        assert!(dir.join("c.1.2.mcc").exists());
+        assert!(dir.join("c.3.4.mcc").exists());
+
+        // One external chunk is written again, the other is gone.
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(1, 2, Compression::Zlib, &filler(257 * SECTOR)).unwrap();
+        writer.write_raw(5, 6, Compression::Zlib, CHUNK_1_12).unwrap();
+        // The old files stay until saving.
+        assert!(dir.join("c.3.4.mcc").exists());
+        writer.save().unwrap();
+        assert!(!dir.join("c.3.4.mcc").exists());
+        {
+            let region = Region::open(&path).unwrap();
+            assert_eq!(region.chunk_raw(1, 2).unwrap().unwrap().1, filler(257 * SECTOR));
+            assert_eq!(region.chunks().count(), 2);
+        }
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 2);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn shared_sectors_are_copied_on_open() {
+        let dir = temp_dir("writer-overlap");
+        let path = dir.join("r.0.0.mca");
+        let mut writer = RegionWriter::create(&path);
+        writer.write_raw(0, 0, Compression::Zlib, &filler(2 * SECTOR)).unwrap();
+        writer.write_raw(1, 0, Compression::Zlib, CHUNK_1_12).unwrap();
+        writer.set_timestamp(1, 0, 1234);
+        writer.save().unwrap();
+
+        // Point chunk 1 at the sectors of chunk 0.
+        let mut data = vec![];
+        File::open(&path).unwrap().read_to_end(&mut data).unwrap();
+        let location = data[0..4].to_vec();
+        data[4..8].copy_from_slice(&location);
+        File::create(&path).unwrap().write_all(&data).unwrap();
+
+        // Rewriting chunk 0 in place leaves chunk 1 as it was.
+        let mut writer = RegionWriter::open(&path).unwrap();
+        writer.write_raw(0, 0, Compression::Zlib, &filler(3 * SECTOR - 100)).unwrap();
+        writer.save().unwrap();
+        let region = Region::open(&path).unwrap();
+        assert_eq!((offset(&region, 0, 0), offset(&region, 1, 0)), (2, 5));
+        assert_eq!(region.chunk_raw(0, 0).unwrap().unwrap().1, filler(3 * SECTOR - 100));
+        assert_eq!(region.chunk_raw(1, 0).unwrap().unwrap().1, filler(2 * SECTOR));
+        assert_eq!(region.chunk_info(1, 0).unwrap().unwrap().timestamp, 1234);
+        assert_eq!(fs::metadata(&path).unwrap().len(), 8 * SECTOR as u64);
+        drop(region);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn failed_replace_removes_the_new_file() {
+        let dir = temp_dir("writer-replace");
+        // A file can't be renamed over a folder that isn't empty.
+        let path = dir.join("r.0.0.mca");
+        fs::create_dir_all(path.join("taken")).unwrap();
+        assert!(replace(&path, b"data").is_err());
+        assert!(!dir.join("r.0.0.mca.tmp").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
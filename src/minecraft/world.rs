@@ -0,0 +1,293 @@
+//! The blocks of a dimension in world coordinates, read from whichever
+//! region has them.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::path::{ Path, PathBuf };
+use std::rc::Rc;
+
+use chunk::{ BiomeId, BlockState, ChunkColumn, EMPTY_CHUNK, LightLevel };
+use minecraft::region::{ self, ChunkSource, RegionError };
+
+/// The regions of a dimension, opened as they are needed, and the chunk
+/// columns read from them so far.
+///
+/// Block coordinates work like in the game: chunk `x >> 4` has block `x`,
+/// and region `x >> 5` has chunk `x`, negative coordinates included.
+pub struct World {
+    dir: PathBuf,
+    /// Regions by region coordinates, `None` where there is no file.
+    regions: RefCell<HashMap<(i32, i32), Option<Box<ChunkSource>>>>,
+    /// Columns by chunk coordinates, `None` where none was generated.
+    columns: RefCell<HashMap<(i32, i32), Option<Rc<ChunkColumn>>>>
+}
+
+impl World {
+    /// A world reading the regions in `dir`, the folder of a dimension.
+    pub fn new(dir: &Path) -> World {
+        World {
+            dir: dir.to_path_buf(),
+            regions: RefCell::new(HashMap::new()),
+            columns: RefCell::new(HashMap::new())
+        }
+    }
+
+    /// The chunk column at chunk `x`, `z`, or `None` if it hasn't been
+    /// generated. A column is only read once; a failed read is retried.
+    pub fn column(&self, x: i32, z: i32) -> Result<Option<Rc<ChunkColumn>>, RegionError> {
+        if let Some(column) = self.columns.borrow().get(&(x, z)) {
+            return Ok(column.clone());
+        }
+        let (rx, rz) = (x >> 5, z >> 5);
+        if !self.regions.borrow().contains_key(&(rx, rz)) {
+            let region = try!(region::open(&self.dir, rx, rz).map_err(RegionError::Io));
+            self.regions.borrow_mut().insert((rx, rz), region);
+        }
+        let column = match self.regions.borrow()[&(rx, rz)] {
+            Some(ref region) => try!(region.get_chunk_column((x & 31) as u8, (z & 31) as u8)),
+            None => None
+        };
+        let column = column.map(Rc::new);
+        self.columns.borrow_mut().insert((x, z), column.clone());
+        Ok(column)
+    }
+
+    /// Drops a column from the cache, so it's read again next time.
+    pub fn forget(&self, x: i32, z: i32) {
+        self.columns.borrow_mut().remove(&(x, z));
+    }
+
+    /// The block at `x`, `y`, `z`, or `None` if its chunk column hasn't
+    /// been generated. Above and below the sections of a column is air.
+    pub fn get_block(&self, x: i32, y: i32, z: i32) -> Result<Option<BlockState>, RegionError> {
+        let column = try!(self.column(x >> 4, z >> 4));
+        Ok(column.map(|column| {
+            let chunk = column.chunk(y >> 4).unwrap_or(EMPTY_CHUNK);
+            chunk.blocks[(y & 15) as usize][(z & 15) as usize][(x & 15) as usize]
+        }))
+    }
+
+    /// The light at `x`, `y`, `z`, like `get_block`. Outside the sections
+    /// of a column is full sky light.
+    pub fn get_light(&self, x: i32, y: i32, z: i32) -> Result<Option<LightLevel>, RegionError> {
+        let column = try!(self.column(x >> 4, z >> 4));
+        Ok(column.map(|column| {
+            let chunk = column.chunk(y >> 4).unwrap_or(EMPTY_CHUNK);
+            chunk.light_levels[(y & 15) as usize][(z & 15) as usize][(x & 15) as usize]
+        }))
+    }
+
+    /// The biome of the block column at `x`, `z`, like `get_block`.
+    pub fn get_biome(&self, x: i32, z: i32) -> Result<Option<BiomeId>, RegionError> {
+        let column = try!(self.column(x >> 4, z >> 4));
+        Ok(column.map(|column| column.biomes[(z & 15) as usize][(x & 15) as usize]))
+    }
+
+    /// The generated chunk columns from chunk `min` to chunk `max`, both
+    /// included, row by row (X first).
+    pub fn columns(&self, min: [i32; 2], max: [i32; 2]) -> Columns {
+        Columns {
+            world: self,
+            min: min,
+            max: max,
+            next: min
+        }
+    }
+
+    /// The generated chunk columns within `radius` chunks of the one with
+    /// block `x`, `z` on either axis.
+    pub fn columns_around(&self, x: i32, z: i32, radius: i32) -> Columns {
+        let (cx, cz) = (x >> 4, z >> 4);
+        self.columns([cx - radius, cz - radius], [cx + radius, cz + radius])
+    }
+}
+
+/// An iterator over the chunk columns of an area, with their chunk
+/// coordinates, skipping ungenerated ones.
+pub struct Columns<'a> {
+    world: &'a World,
+    min: [i32; 2],
+    max: [i32; 2],
+    next: [i32; 2]
+}
+
+impl<'a> Iterator for Columns<'a> {
+    type Item = Result<(i32, i32, Rc<ChunkColumn>), RegionError>;
+
+    fn next(&mut self) -> Option<Result<(i32, i32, Rc<ChunkColumn>), RegionError>> {
+        while self.next[1] <= self.max[1] && self.min[0] <= self.max[0] {
+            let (x, z) = (self.next[0], self.next[1]);
+            if x < self.max[0] {
+                self.next[0] += 1;
+            } else {
+                self.next = [self.min[0], z + 1];
+            }
+            match self.world.column(x, z) {
+                Ok(Some(column)) => return Some(Ok((x, z, column))),
+                Ok(None) => {}
+                Err(err) => return Some(Err(err))
+            }
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use minecraft::nbt::{ Compound, List, Nbt };
+    use minecraft::region::{ Compression, RegionWriter };
+    use minecraft::region::tests::temp_dir;
+    use super::World;
+
+    const STONE: u16 = 0x0010;
+
+    fn compound(entries: Vec<(&str, Nbt)>) -> Compound {
+        entries.into_iter().map(|(k, v)| (k.to_string(), v)).collect()
+    }
+
+    /// A 1.18 chunk of air with stone, lit at 9, at each of `stones` (X,
+    /// Y, Z in the chunk), and `biome` everywhere.
+    fn chunk(biome: &str, stones: &[(usize, i32, usize)]) -> Nbt {
+        let mut sections = BTreeMap::new();
+        for &(x, y, z) in stones {
+            let &mut (ref mut indices, ref mut light) =
+                sections.entry(y >> 4).or_insert((vec![0u64; 256], vec![0u8; 2048]));
+            let i = (((y & 15) as usize * 16 + z) * 16) + x;
+            indices[i / 16] |= 1 << (i % 16 * 4);
+            light[i / 2] |= 9 << (i % 2 * 4);
+        }
+        sections.entry(4).or_insert((vec![0; 256], vec![0; 2048]));
+        let air = compound(vec![("Name", Nbt::String("minecraft:air".to_string()))]);
+        let stone = compound(vec![("Name", Nbt::String("minecraft:stone".to_string()))]);
+        let sections = sections.into_iter().map(|(y, (indices, light))| compound(vec![
+            ("Y", Nbt::Byte(y as i8)),
+            ("block_states", Nbt::Compound(compound(vec![
+                ("palette", Nbt::List(List::Compound(vec![air.clone(), stone.clone()]))),
+                ("data", Nbt::LongArray(indices.into_iter().map(|l| l as i64).collect()))
+            ]))),
+            ("biomes", Nbt::Compound(compound(vec![
+                ("palette", Nbt::List(List::String(vec![biome.to_string()])))
+            ]))),
+            ("BlockLight", Nbt::ByteArray(light))
+        ])).collect();
+        Nbt::Compound(compound(vec![
+            ("DataVersion", Nbt::Int(2975)),
+            ("yPos", Nbt::Int(-4)),
+            ("sections", Nbt::List(List::Compound(sections)))
+        ]))
+    }
+
+    fn world(name: &str) -> (World, PathBuf) {
+        let dir = temp_dir(name);
+        fs::create_dir(dir.join("region")).unwrap();
+        let mut writer = RegionWriter::create(&dir.join("region").join("r.-1.-1.mca"));
+        // Chunk -1, -1, with the blocks at -1 and -16 on both axes.
+        let stones = [(15, 5, 15), (0, -20, 0)];
+        writer.write_chunk(31, 31, &chunk("minecraft:plains", &stones), Compression::Zlib)
+              .unwrap();
+        // Chunk -2, -1, with blocks at X -17.
+        let stones = [(15, 5, 15), (15, 5, 0)];
+        writer.write_chunk(30, 31, &chunk("minecraft:desert", &stones), Compression::Zlib).unwrap();
+        // Chunk -32, -32, at the corner of the region.
+        let stones = [(0, -64, 0)];
+        writer.write_chunk(0, 0, &chunk("minecraft:forest", &stones), Compression::Zlib).unwrap();
+        writer.save().unwrap();
+        let mut writer = RegionWriter::create(&dir.join("region").join("r.0.0.mca"));
+        let stones = [(0, 0, 0)];
+        writer.write_chunk(0, 0, &chunk("minecraft:plains", &stones), Compression::Zlib).unwrap();
+        writer.save().unwrap();
+        (World::new(&dir), dir)
+    }
+
+    fn block(world: &World, x: i32, y: i32, z: i32) -> Option<u16> {
+        world.get_block(x, y, z).unwrap().map(|block| block.value)
+    }
+
+    #[test]
+    fn negative_coordinates() {
+        let (world, dir) = world("world-negative");
+        assert_eq!(block(&world, -1, 5, -1), Some(STONE));
+        assert_eq!(block(&world, -1, 5, -2), Some(0));
+        assert_eq!(block(&world, -16, -20, -16), Some(STONE));
+        assert_eq!(block(&world, -16, -19, -16), Some(0));
+        assert_eq!(block(&world, -17, 5, -1), Some(STONE));
+        assert_eq!(block(&world, -17, 5, -16), Some(STONE));
+        assert_eq!(block(&world, -17, 5, -17), None);
+        assert_eq!(block(&world, -512, -64, -512), Some(STONE));
+        assert_eq!(block(&world, -511, -64, -512), Some(0));
+        // In region -2, -2, which has no file.
+        assert_eq!(block(&world, -513, -64, -513), None);
+        assert_eq!(block(&world, -513, -64, -512), None);
+        assert_eq!(block(&world, 0, 0, 0), Some(STONE));
+
+        let light = world.get_light(-17, 5, -16).unwrap().unwrap();
+        assert_eq!((light.block_light(), light.sky_light()), (9, 15));
+        let light = world.get_light(-17, 4, -16).unwrap().unwrap();
+        assert_eq!((light.block_light(), light.sky_light()), (0, 15));
+
+        assert_eq!(world.get_biome(-1, -1).unwrap().unwrap().value, 1);
+        assert_eq!(world.get_biome(-16, -16).unwrap().unwrap().value, 1);
+        assert_eq!(world.get_biome(-17, -1).unwrap().unwrap().value, 2);
+        assert_eq!(world.get_biome(-512, -497).unwrap().unwrap().value, 4);
+        assert!(world.get_biome(-17, -17).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sections_below_zero() {
+        let (world, dir) = world("world-below-zero");
+        // Chunk 0, 0 has no sections below zero, which is air.
+        assert_eq!(block(&world, 0, -1, 0), Some(0));
+        assert_eq!(block(&world, 0, -64, 0), Some(0));
+        let light = world.get_light(0, -64, 0).unwrap().unwrap();
+        assert_eq!((light.block_light(), light.sky_light()), (0, 15));
+        assert_eq!(block(&world, -16, -20, -16), Some(STONE));
+        assert_eq!(block(&world, -16, -36, -16), Some(0));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_columns_are_none() {
+        let (world, dir) = world("world-missing");
+        assert!(world.column(1, 0).unwrap().is_none());
+        assert_eq!(block(&world, 16, 0, 0), None);
+        // Region -1, 0 has no file either.
+        assert!(world.column(-1, 0).unwrap().is_none());
+        assert!(world.get_light(-1, 0, 0).unwrap().is_none());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columns_are_cached_until_forgotten() {
+        let (world, dir) = world("world-forget");
+        let column = world.column(-1, -1).unwrap().unwrap();
+        let same = world.column(-1, -1).unwrap().unwrap();
+        assert!(&*column as *const _ == &*same as *const _);
+        world.forget(-1, -1);
+        let again = world.column(-1, -1).unwrap().unwrap();
+        assert!(&*column as *const _ != &*again as *const _);
+        assert_eq!(again.chunk(0).unwrap().blocks[5][15][15].value, STONE);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn columns_of_an_area() {
+        let (world, dir) = world("world-columns");
+        let positions = |columns: super::Columns| -> Vec<(i32, i32)> {
+            columns.map(|column| {
+                let (x, z, _) = column.unwrap();
+                (x, z)
+            }).collect()
+        };
+        assert_eq!(positions(world.columns([-2, -1], [0, 0])), [(-2, -1), (-1, -1), (0, 0)]);
+        assert_eq!(positions(world.columns_around(-1, -1, 1)), [(-2, -1), (-1, -1), (0, 0)]);
+        assert_eq!(positions(world.columns_around(-500, -500, 1)), [(-32, -32)]);
+        assert_eq!(positions(world.columns([-40, -40], [-33, -33])), []);
+        assert_eq!(positions(world.columns([1, 1], [0, 0])), []);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -3,6 +3,8 @@ use gfx::pso::DataLink;
 use gfx;
 use vecmath::{self, Matrix4};
 
+use minecraft::dimension::Dimension;
+
 static VERTEX: &'static [u8] = b"
     #version 150 core
     uniform mat4 u_projection, u_view;
@@ -62,6 +64,7 @@ pub struct Renderer<R: gfx::Resources, F: gfx::Factory<R>, C: gfx::CommandBuffer
     clear_color: [f32; 4],
     clear_depth: f32,
     clear_stencil: u8,
+    sky_light: Option<u8>,
     slice: gfx::Slice<R>,
 }
 
@@ -107,6 +110,7 @@ impl<R: gfx::Resources, F: gfx::Factory<R>, C: gfx::CommandBuffer<R>> Renderer<R
             clear_color: [0.81, 0.8, 1.0, 1.0],
             clear_depth: 1.0,
             clear_stencil: 0,
+            sky_light: None,
             slice: slice,
         }
     }
@@ -119,6 +123,17 @@ impl<R: gfx::Resources, F: gfx::Factory<R>, C: gfx::CommandBuffer<R>> Renderer<R
         self.data.view = view_mat;
     }
 
+    /// Uses the clear color and sky light of a dimension.
+    pub fn set_dimension(&mut self, dimension: &Dimension) {
+        self.clear_color = dimension.clear_color;
+        self.sky_light = dimension.sky_light;
+    }
+
+    /// The light to use instead of the sky light of chunks, if any.
+    pub fn sky_light(&self) -> Option<u8> {
+        self.sky_light
+    }
+
     pub fn clear(&mut self) {
         self.encoder.clear(&self.data.out_color, self.clear_color);
         self.encoder.clear_depth(&self.data.out_depth, self.clear_depth);